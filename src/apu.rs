@@ -15,6 +15,7 @@ use pulse::{Pulse, PulseChannel};
 use triangle::Triangle;
 
 use crate::cpu::CPU;
+use crate::savestate::{Reader, Snapshot, StateError, Writer};
 
 pub struct APU {
   pulse_one: Pulse,
@@ -28,10 +29,16 @@ pub struct APU {
   irq: IRQ,
   cycles: usize,
 
+  muted: [bool; 0x05],
   samples: Vec<f32>,
   pub mixer: Mixer,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Channel {
+  Pulse1, Pulse2, Triangle, Noise, DMC, Expansion(u8),
+}
+
 #[derive(PartialEq, Eq)]
 enum SequencerMode {
   StepFour,
@@ -85,6 +92,7 @@ impl APU {
       irq: IRQ::new(),
       cycles: 0,
 
+      muted: [false; 0x05],
       samples: vec![],
       mixer: Mixer::new(),
     }
@@ -93,7 +101,7 @@ impl APU {
   pub fn read(&mut self, addr: u16) -> u8 {
     match addr {
       0x4015 => self.read_status(),
-      _ => unreachable!("Should not happen!"),
+      _ => 0, // Write-only registers
     }
   }
 
@@ -134,11 +142,11 @@ impl APU {
 
       // Frame Counter
       0x4017 => self.write_frame_counter(data),
-      _ => unreachable!("Should not happen!"),
+      _ => { }
     }
   }
 
-  pub fn tick(&mut self) {
+  pub fn tick(&mut self, expansion: f32) {
     let prev = self.cycles as f32;
     self.cycles = self.cycles.wrapping_add(1);
     let post = self.cycles as f32;
@@ -150,10 +158,10 @@ impl APU {
       self.frame();
     }
 
-    self.signal();
+    self.signal(expansion);
   }
 
-  pub fn poll(&mut self) -> bool {
+  pub fn poll(&self) -> bool {
     self.irq.pending || self.dmc.irq.pending
   }
 
@@ -211,17 +219,39 @@ impl APU {
     }
   }
 
-  fn signal(&mut self) {
-    let p1 = self.pulse_one.signal();
-    let p2 = self.pulse_two.signal();
-    let t = self.triangle.signal();
-    let n = self.noise.signal();
-    let d = self.dmc.signal();
+  pub fn mute(&mut self, channel: Channel, muted: bool) {
+    let idx = match channel {
+      Channel::Pulse1 => 0,
+      Channel::Pulse2 => 1,
+      Channel::Triangle => 2,
+      Channel::Noise => 3,
+      Channel::DMC => 4,
+      Channel::Expansion(_) => return,
+    };
+    self.muted[idx] = muted;
+  }
+
+  fn signal(&mut self, expansion: f32) {
+    let mut signals = [
+      self.pulse_one.signal(),
+      self.pulse_two.signal(),
+      self.triangle.signal(),
+      self.noise.signal(),
+      self.dmc.signal(),
+    ];
+
+    for (signal, muted) in signals.iter_mut().zip(self.muted) {
+      if muted {
+        *signal = 0.0;
+      }
+    }
+
+    let [p1, p2, t, n, d] = signals;
 
     let pulse = (95.88) / ((8128.0 / (p1 + p2)) + 100.0);
     let tnd = (159.79) / ((1.0 / ((t / 8227.0) + (n / 12241.0) + (d / 22638.0))) + 100.0);
 
-    self.samples.push(pulse + tnd);
+    self.samples.push(pulse + tnd + expansion);
   }
 
   pub fn mix(&mut self) {
@@ -288,3 +318,45 @@ impl APU {
     }
   }
 }
+
+impl Snapshot for IRQ {
+  fn save(&self, state: &mut Writer) {
+    state.bool(self.enabled);
+    state.bool(self.pending);
+  }
+
+  fn load(&mut self, state: &mut Reader) -> Result<(), StateError> {
+    self.enabled = state.bool()?;
+    self.pending = state.bool()?;
+    Ok(())
+  }
+}
+
+impl Snapshot for APU {
+  fn save(&self, state: &mut Writer) {
+    self.pulse_one.save(state);
+    self.pulse_two.save(state);
+    self.triangle.save(state);
+    self.noise.save(state);
+    self.dmc.save(state);
+
+    state.bool(self.mode == SequencerMode::StepFive);
+    state.u8(self.step);
+    self.irq.save(state);
+    state.usize(self.cycles);
+  }
+
+  fn load(&mut self, state: &mut Reader) -> Result<(), StateError> {
+    self.pulse_one.load(state)?;
+    self.pulse_two.load(state)?;
+    self.triangle.load(state)?;
+    self.noise.load(state)?;
+    Snapshot::load(&mut self.dmc, state)?;
+
+    self.mode = if state.bool()? { SequencerMode::StepFive } else { SequencerMode::StepFour };
+    self.step = state.u8()? % self.mode.steps();
+    self.irq.load(state)?;
+    self.cycles = state.usize()?;
+    Ok(())
+  }
+}
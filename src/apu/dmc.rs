@@ -1,3 +1,5 @@
+use crate::savestate::{Reader, Snapshot, StateError, Writer};
+
 use super::{timer::Timer, IRQ};
 
 pub struct DMC {
@@ -203,3 +205,48 @@ impl DMC {
     self.buffer.value as f32
   }
 }
+
+impl Snapshot for DMC {
+  fn save(&self, state: &mut Writer) {
+    self.irq.save(state);
+    state.bool(self.looped);
+    self.timer.save(state);
+
+    state.u16(self.sample.address);
+    state.u16(self.sample.length);
+    state.u16(self.load.address);
+    state.u16(self.load.length);
+
+    state.bool(self.dma.pending);
+    state.u8(self.dma.delay);
+
+    state.u8(self.buffer.value);
+    state.u8(self.buffer.bits);
+    state.u8(self.buffer.shift);
+    state.bool(self.buffer.silent);
+    state.bool(self.buffer.reload);
+    state.u8(self.buffer.sample);
+  }
+
+  fn load(&mut self, state: &mut Reader) -> Result<(), StateError> {
+    self.irq.load(state)?;
+    self.looped = state.bool()?;
+    self.timer.load(state)?;
+
+    self.sample.address = state.u16()?;
+    self.sample.length = state.u16()?;
+    self.load.address = state.u16()?;
+    self.load.length = state.u16()?;
+
+    self.dma.pending = state.bool()?;
+    self.dma.delay = state.u8()?;
+
+    self.buffer.value = state.u8()?;
+    self.buffer.bits = state.u8()?;
+    self.buffer.shift = state.u8()?;
+    self.buffer.silent = state.bool()?;
+    self.buffer.reload = state.bool()?;
+    self.buffer.sample = state.u8()?;
+    Ok(())
+  }
+}
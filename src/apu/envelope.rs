@@ -1,3 +1,5 @@
+use crate::savestate::{Reader, Snapshot, StateError, Writer};
+
 pub struct Envelope {
   pub looped: bool,
   pub enabled: bool,
@@ -47,3 +49,24 @@ impl Envelope {
     }
   }
 }
+
+impl Snapshot for Envelope {
+  fn save(&self, state: &mut Writer) {
+    state.bool(self.looped);
+    state.bool(self.enabled);
+    state.u8(self.rate);
+    state.u8(self.volume);
+    state.bool(self.reset);
+    state.u8(self.counter);
+  }
+
+  fn load(&mut self, state: &mut Reader) -> Result<(), StateError> {
+    self.looped = state.bool()?;
+    self.enabled = state.bool()?;
+    self.rate = state.u8()?;
+    self.volume = state.u8()?;
+    self.reset = state.bool()?;
+    self.counter = state.u8()?;
+    Ok(())
+  }
+}
@@ -1,3 +1,5 @@
+use crate::savestate::{Reader, Snapshot, StateError, Writer};
+
 pub struct LengthCounter {
   pub counter: u8,
   pub halted: bool,
@@ -28,3 +30,16 @@ impl LengthCounter {
     }
   }
 }
+
+impl Snapshot for LengthCounter {
+  fn save(&self, state: &mut Writer) {
+    state.u8(self.counter);
+    state.bool(self.halted);
+  }
+
+  fn load(&mut self, state: &mut Reader) -> Result<(), StateError> {
+    self.counter = state.u8()?;
+    self.halted = state.bool()?;
+    Ok(())
+  }
+}
@@ -107,8 +107,18 @@ impl Mixer {
         let sample = self.filters.iter_mut()
           .fold(self.sampling.average / self.sampling.count, |s, filter| filter.process(s));
         if self.producer.try_push(sample).is_err() {
-          #[cfg(not(target_arch = "wasm32"))]
-          std::thread::sleep(std::time::Duration::from_micros(10));
+          match self.consumer.as_mut() {
+            // Nobody is playing the buffer back in real time, so make room
+            // for the newest sample instead of waiting.
+            Some(consumer) => {
+              consumer.try_pop();
+              let _ = self.producer.try_push(sample);
+            }
+            None => {
+              #[cfg(not(target_arch = "wasm32"))]
+              std::thread::sleep(std::time::Duration::from_micros(10));
+            }
+          }
         }
 
         self.sampling.average = 0.0;
@@ -120,6 +130,10 @@ impl Mixer {
     }
   }
 
+  pub fn drain(&mut self) -> Vec<f32> {
+    self.consumer.as_mut().map(|consumer| consumer.pop_iter().collect()).unwrap_or_default()
+  }
+
   pub fn consumer(&mut self) -> Consumer {
     self.consumer.take().unwrap_or_else(|| panic!("Can only obtain audio consumer once."))
   }
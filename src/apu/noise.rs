@@ -1,3 +1,5 @@
+use crate::savestate::{Reader, Snapshot, StateError, Writer};
+
 use super::{envelope::Envelope, lengthcounter::LengthCounter, timer::Timer};
 
 pub struct Noise {
@@ -111,3 +113,27 @@ impl Noise {
     }
   }
 }
+
+impl Snapshot for Noise {
+  fn save(&self, state: &mut Writer) {
+    state.bool(self.enabled);
+    self.envelope.save(state);
+    self.length.save(state);
+
+    state.bool(matches!(self.shift.mode, ShiftMode::Six));
+    state.u16(self.shift.value);
+
+    self.timer.save(state);
+  }
+
+  fn load(&mut self, state: &mut Reader) -> Result<(), StateError> {
+    self.enabled = state.bool()?;
+    self.envelope.load(state)?;
+    self.length.load(state)?;
+
+    self.shift.mode = if state.bool()? { ShiftMode::Six } else { ShiftMode::One };
+    self.shift.value = state.u16()?;
+
+    self.timer.load(state)
+  }
+}
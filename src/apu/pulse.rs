@@ -1,3 +1,5 @@
+use crate::savestate::{Reader, Snapshot, StateError, Writer};
+
 use super::{envelope::Envelope, lengthcounter::LengthCounter, timer::Timer};
 
 pub struct Pulse {
@@ -182,3 +184,42 @@ impl Pulse {
       }
   }
 }
+
+impl Snapshot for Pulse {
+  fn save(&self, state: &mut Writer) {
+    state.bool(self.enabled);
+
+    state.bool(self.sweep.enabled);
+    state.u8(self.sweep.period);
+    state.u8(self.sweep.counter);
+    state.bool(self.sweep.negated);
+    state.u8(self.sweep.shift);
+    state.bool(self.sweep.reload);
+
+    self.timer.save(state);
+    self.length.save(state);
+    self.envelope.save(state);
+
+    state.u8(self.duty.value);
+    state.u8(self.duty.cycle);
+  }
+
+  fn load(&mut self, state: &mut Reader) -> Result<(), StateError> {
+    self.enabled = state.bool()?;
+
+    self.sweep.enabled = state.bool()?;
+    self.sweep.period = state.u8()?;
+    self.sweep.counter = state.u8()?;
+    self.sweep.negated = state.bool()?;
+    self.sweep.shift = state.u8()?;
+    self.sweep.reload = state.bool()?;
+
+    self.timer.load(state)?;
+    self.length.load(state)?;
+    self.envelope.load(state)?;
+
+    self.duty.value = state.u8()? % 0x08;
+    self.duty.cycle = state.u8()? & 0x03;
+    Ok(())
+  }
+}
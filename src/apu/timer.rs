@@ -1,3 +1,5 @@
+use crate::savestate::{Reader, Snapshot, StateError, Writer};
+
 pub struct Timer {
   pub period: u16,
   current: u16,
@@ -29,3 +31,16 @@ impl Timer {
     }
   }
 }
+
+impl Snapshot for Timer {
+  fn save(&self, state: &mut Writer) {
+    state.u16(self.period);
+    state.u16(self.current);
+  }
+
+  fn load(&mut self, state: &mut Reader) -> Result<(), StateError> {
+    self.period = state.u16()?;
+    self.current = state.u16()?;
+    Ok(())
+  }
+}
@@ -1,3 +1,5 @@
+use crate::savestate::{Reader, Snapshot, StateError, Writer};
+
 use super::{lengthcounter::LengthCounter, timer::Timer};
 
 pub struct Triangle {
@@ -113,3 +115,32 @@ impl Triangle {
     }
   }
 }
+
+impl Snapshot for Triangle {
+  fn save(&self, state: &mut Writer) {
+    state.bool(self.enabled);
+
+    state.bool(self.linear.reload);
+    state.bool(self.linear.control);
+    state.u8(self.linear.counter);
+    state.u8(self.linear.period);
+
+    self.length.save(state);
+    self.timer.save(state);
+    state.u8(self.step);
+  }
+
+  fn load(&mut self, state: &mut Reader) -> Result<(), StateError> {
+    self.enabled = state.bool()?;
+
+    self.linear.reload = state.bool()?;
+    self.linear.control = state.bool()?;
+    self.linear.counter = state.u8()?;
+    self.linear.period = state.u8()?;
+
+    self.length.load(state)?;
+    self.timer.load(state)?;
+    self.step = state.u8()? % 0x20;
+    Ok(())
+  }
+}
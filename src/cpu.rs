@@ -1,16 +1,25 @@
+pub mod debugger;
+pub mod disassembler;
 mod instruction;
 mod interrupt;
 mod register;
+pub mod tracer;
 
+use crate::error::NeoNESError;
+use crate::savestate::{Reader, Snapshot, StateError, Writer};
 use crate::system::System;
-use instruction::{Addressing, Instruction, OpCode, Operand, OperandAddress};
-use interrupt::Interrupt;
+use debugger::{CallKind, Debugger, Event, Space};
+use instruction::{Access, Addressing, Instruction, OpCode, Operand, OperandAddress};
+use interrupt::{Interrupt, Polling};
 use register::{Flag, Register, Registers};
+use tracer::Tracer;
 
 pub struct CPU {
   registers: Registers,
   pub(crate) system: System,
-  branched: u8,
+  polling: Polling,
+  tracer: Option<Tracer>,
+  debugger: Option<Debugger>,
 }
 
 impl CPU {
@@ -21,47 +30,127 @@ impl CPU {
     let mut cpu = CPU {
       registers: Registers::new(),
       system,
-      branched: 0,
+      polling: Polling::new(),
+      tracer: None,
+      debugger: None,
     };
     cpu.reset();
     cpu
   }
 
-  fn reset(&mut self) {
+  pub(crate) fn reset(&mut self) {
     self.registers = Registers::new();
-    self.registers.set_pc(self.system.readu16(0xFFFC));
+    self.polling = Polling::new();
+
+    // Reset runs through the interrupt sequence with its stack writes turned
+    // into reads, taking seven cycles before the first instruction.
+    self.system.tick(5);
+    let pc = self.bus_readu16(0xFFFC);
+    self.registers.set_pc(pc);
   }
 
-  pub fn start(&mut self) {
-    loop { self.step(); }
+  // Runs until the debugger stops, if there is one to do so
+  pub fn start(&mut self) -> Result<(), NeoNESError> {
+    while !self.paused() {
+      self.step()?;
+    }
+    Ok(())
   }
 
-  pub fn step_frame(&mut self) {
+  pub fn step_frame(&mut self) -> Result<(), NeoNESError> {
     let current = self.system.ppu.frame.number;
-    while current == self.system.ppu.frame.number {
-      self.step();
+    while current == self.system.ppu.frame.number && !self.paused() {
+      self.step()?;
     }
+    Ok(())
+  }
+
+  pub fn disassemble(&self, addr: u16) -> (String, u16) {
+    disassembler::disassemble_at(&self.system, addr, self.registers.get(Register::X), self.registers.get(Register::Y))
+  }
+
+  pub fn pc(&self) -> u16 {
+    self.registers.get_pc()
+  }
+
+  pub fn debugger(&mut self) -> Option<&mut Debugger> {
+    self.debugger.as_mut()
+  }
+
+  pub fn set_debugger(&mut self, debugger: Option<Debugger>) -> Option<Debugger> {
+    std::mem::replace(&mut self.debugger, debugger)
+  }
+
+  // A stopped debugger holds the CPU until told to resume
+  pub fn paused(&self) -> bool {
+    self.debugger.as_ref().is_some_and(|debugger| debugger.stopped().is_some())
+  }
+
+  pub fn tracer(&mut self) -> Option<&mut Tracer> {
+    self.tracer.as_mut()
   }
 
-  fn step(&mut self) {
-    if self.system.poll_nmi() {
+  pub fn set_tracer(&mut self, tracer: Option<Tracer>) -> Option<Tracer> {
+    std::mem::replace(&mut self.tracer, tracer)
+  }
+
+  // One line of nestest.log, taken before the instruction at PC runs
+  pub fn trace(&self) -> String {
+    let pc = self.registers.get_pc();
+    let (text, len) = self.disassemble(pc);
+    let bytes = (0 .. len)
+      .map(|offset| format!("{:02X}", self.system.peek(pc.wrapping_add(offset))))
+      .collect::<Vec<_>>()
+      .join(" ");
+
+    // Unofficial opcodes are marked in the column before the mnemonic
+    let text = if text.starts_with('*') { text } else { format!(" {text}") };
+    let (line, dot) = self.system.ppu.position();
+
+    format!(
+      "{pc:04X}  {bytes:<8} {text:<32} A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X} PPU:{line:3},{dot:3} CYC:{}",
+      self.registers.get(Register::A),
+      self.registers.get(Register::X),
+      self.registers.get(Register::Y),
+      self.registers.get(Register::P),
+      self.registers.get(Register::SP),
+      self.system.cycles,
+    )
+  }
+
+  fn step(&mut self) -> Result<(), NeoNESError> {
+    if self.polling.prev_nmi {
       self.interrupt(Interrupt::NMI);
-    } else if self.system.poll_irq() {
+    } else if self.polling.prev_irq {
       self.interrupt(Interrupt::IRQ);
     }
 
-    let instruction = Instruction::get(self.read());
-    let operand = self.get_operand(instruction.mode, instruction.needs_data());
+    if let Some(debugger) = self.debugger.as_mut() {
+      let opcode = self.system.peek(self.registers.get_pc());
+      if debugger.before(&self.registers, opcode, self.system.ppu.position().0) {
+        return Ok(());
+      }
+    }
 
-    self.execute(instruction.opcode, operand);
+    if self.tracer.as_ref().is_some_and(|tracer| tracer.traces(self.registers.get_pc())) {
+      let line = self.trace();
+      if let Some(tracer) = self.tracer.as_mut() {
+        tracer.log(&line).map_err(|e| NeoNESError::TraceFailed(e.kind()))?;
+      }
+    }
+
+    let instruction = Instruction::get(self.read());
+    let operand = self.get_operand(instruction.mode, instruction.access());
 
-    let cycles = instruction.cycles + instruction.extra * (operand.0.2 as u8) + self.branched();
-    self.system.tick(cycles as u16);
+    self.execute(instruction.opcode, operand)
   }
 
   fn interrupt(&mut self, interrupt: Interrupt) {
-    if self.registers.get_flag(Flag::InterruptDisable) && (interrupt == Interrupt::IRQ) {
-      return;
+    // BRK has already fetched its padding byte, where NMI and IRQ spend those
+    // two cycles reading the opcode they preempted.
+    if interrupt != Interrupt::BRK {
+      self.bus_read(self.registers.get_pc());
+      self.bus_read(self.registers.get_pc());
     }
 
     self
@@ -72,31 +161,103 @@ impl CPU {
       .change_flag(Flag::B2, interrupt.mask & 0b00100000 == 0b00100000);
 
     self.stack_pushu16(self.registers.get_pc());
+
+    // An NMI that arrives before the status is pushed hijacks the vector fetch
+    // of a BRK or IRQ already under way.
+    let vector = if std::mem::take(&mut self.polling.nmi) {
+      Interrupt::NMI.read_address
+    } else {
+      interrupt.read_address
+    };
+
     self.stack_push(self.registers.get(Register::P));
 
     self.registers.set_flag(Flag::InterruptDisable);
 
-    self.system.tick(interrupt.cycles as u16);
+    let from = self.registers.get_pc();
+    let pc = self.bus_readu16(vector);
+    self.registers.set_pc(pc);
 
-    self
-      .registers
-      .set_pc(self.system.readu16(interrupt.read_address));
+    if let Some(debugger) = self.debugger.as_mut() {
+      let event = if vector == Interrupt::NMI.read_address {
+        Event::NMI
+      } else if interrupt == Interrupt::BRK {
+        Event::BRK
+      } else {
+        Event::IRQ
+      };
+      debugger.call(CallKind::Interrupt(event), from, pc);
+    }
+
+    // The first instruction of the handler always runs before another
+    // interrupt is taken.
+    self.polling.prev_nmi = false;
+  }
+
+  // Every cycle of an instruction is a bus access, so the rest of the system
+  // is clocked one cycle at a time as the CPU touches the bus.
+  fn bus_read(&mut self, addr: u16) -> u8 {
+    let vram = self.watched_vram(addr);
+    let data = self.system.read(addr);
+    self.system.tick(1);
+    self.poll();
+    self.watch(addr, vram, data, false);
+    data
+  }
+
+  fn bus_write(&mut self, addr: u16, data: u8) {
+    let vram = self.watched_vram(addr);
+    self.system.write(addr, data);
+    self.system.tick(1);
+    self.poll();
+    self.watch(addr, vram, data, true);
+  }
+
+  // PPU memory is only reachable from the CPU through PPUDATA, so that is
+  // where watchpoints on it are checked
+  fn watched_vram(&self, addr: u16) -> Option<u16> {
+    let ppudata = (System::PPU ..= System::PPU_END).contains(&addr) && addr & 0x0007 == 0x0007;
+    (self.debugger.is_some() && ppudata).then(|| self.system.ppu.data_address())
+  }
+
+  fn watch(&mut self, addr: u16, vram: Option<u16>, data: u8, write: bool) {
+    if let Some(debugger) = self.debugger.as_mut() {
+      debugger.access(Space::CPU, addr, data, write);
+
+      if let Some(vram) = vram {
+        debugger.access(Space::PPU, vram, data, write);
+      }
+    }
+  }
+
+  fn poll(&mut self) {
+    let irq = self.system.irq_line() && !self.registers.get_flag(Flag::InterruptDisable);
+    if self.system.nmi_cancelled() {
+      self.polling.cancel_nmi();
+    }
+    self.polling.sample(self.system.nmi_line(), irq);
+  }
+
+  fn bus_readu16(&mut self, addr: u16) -> u16 {
+    let lo = self.bus_read(addr) as u16;
+    let hi = self.bus_read(addr.wrapping_add(1)) as u16;
+    (hi << 8) | lo
   }
 
   fn read(&mut self) -> u8 {
-    let res = self.system.read(self.registers.get_pc());
+    let res = self.bus_read(self.registers.get_pc());
     self.increment_pc(1);
     res
   }
 
   fn readu16(&mut self) -> u16 {
-    let res = self.system.readu16(self.registers.get_pc());
+    let res = self.bus_readu16(self.registers.get_pc());
     self.increment_pc(2);
     res
   }
 
   fn stack_push(&mut self, data: u8) {
-    self.system.write(
+    self.bus_write(
       CPU::STACK_START.wrapping_add(self.registers.get(Register::SP) as u16),
       data,
     );
@@ -111,9 +272,13 @@ impl CPU {
       Register::SP,
       self.registers.get(Register::SP).wrapping_add(1),
     );
-    self
-      .system
-      .read(CPU::STACK_START.wrapping_add(self.registers.get(Register::SP) as u16))
+    self.bus_read(CPU::STACK_START.wrapping_add(self.registers.get(Register::SP) as u16))
+  }
+
+  // Reads the stack without moving it, as the CPU does while it adjusts the
+  // stack pointer before a pull or a JSR's pushes.
+  fn stack_idle(&mut self) {
+    self.bus_read(CPU::STACK_START.wrapping_add(self.registers.get(Register::SP) as u16));
   }
 
   fn stack_pushu16(&mut self, data: u16) {
@@ -137,12 +302,6 @@ impl CPU {
       .set_pc(self.registers.get_pc().wrapping_add(i));
   }
 
-  fn branched(&mut self) -> u8 {
-    let res = self.branched;
-    self.branched = 0;
-    res
-  }
-
   fn update_zero_negative(&mut self, res: u8) {
     self.registers.change_flag(Flag::Zero, res == 0x00);
     self
@@ -150,96 +309,127 @@ impl CPU {
       .change_flag(Flag::Negative, res & 0x80 == 0x80);
   }
 
-  fn get_operand_addr(&mut self, mode: Addressing) -> OperandAddress {
+  // Read-modify-write instructions write the unmodified value back before the
+  // result, which mappers like MMC1 can see.
+  fn modify(&mut self, addr: u16, old: u8, new: u8) {
+    self.bus_write(addr, old);
+    self.bus_write(addr, new);
+  }
+
+  // Indexing first reads from the address before the carry into the high byte
+  // is fixed up. Reads skip that cycle when no page is crossed.
+  fn indexed(&mut self, base: u16, index: u8, mode: Addressing, access: Access) -> OperandAddress {
+    let addr = base.wrapping_add(index as u16);
+
+    if (addr & 0xFF00) != (base & 0xFF00) || access != Access::Read {
+      self.bus_read((base & 0xFF00) | (addr & 0x00FF));
+    }
+
+    OperandAddress(addr, mode)
+  }
+
+  fn branch(&mut self, condition: bool, addr: u16) {
+    if condition {
+      let pc = self.registers.get_pc();
+
+      // The extra cycle of a taken branch does not poll for interrupts, so an
+      // IRQ arriving just before it waits for another instruction.
+      if self.polling.irq && !self.polling.prev_irq {
+        self.polling.irq = false;
+      }
+
+      // A taken branch reads the next opcode, and again from the wrong page
+      // if the target lies on another one.
+      self.bus_read(pc);
+      if (pc & 0xFF00) != (addr & 0xFF00) {
+        self.bus_read((pc & 0xFF00) | (addr & 0x00FF));
+      }
+
+      self.registers.set_pc(addr);
+    }
+  }
+
+  fn get_operand_addr(&mut self, mode: Addressing, access: Access) -> OperandAddress {
     match mode {
-      Addressing::Accumulator => OperandAddress(0, mode, false),
-      Addressing::Absolute => OperandAddress(self.readu16(), mode, false),
+      Addressing::Accumulator | Addressing::Implied => {
+        // Single byte instructions still read the byte after the opcode
+        self.bus_read(self.registers.get_pc());
+        OperandAddress(0, mode)
+      }
+      Addressing::Absolute => OperandAddress(self.readu16(), mode),
       Addressing::AbsoluteX => {
         let base_addr = self.readu16();
-        let addr = base_addr.wrapping_add(self.registers.get(Register::X) as u16);
-        let extra = (addr & 0xFF00) != (base_addr & 0xFF00);
-
-        OperandAddress(addr, mode, extra)
+        self.indexed(base_addr, self.registers.get(Register::X), mode, access)
       }
       Addressing::AbsoluteY => {
         let base_addr = self.readu16();
-        let addr = base_addr.wrapping_add(self.registers.get(Register::Y) as u16);
-        let extra = (addr & 0xFF00) != (base_addr & 0xFF00);
-
-        OperandAddress(addr, mode, extra)
+        self.indexed(base_addr, self.registers.get(Register::Y), mode, access)
       }
-      Addressing::Immediate => OperandAddress(self.registers.get_pc(), mode, false),
-      Addressing::Implied => OperandAddress(0, mode, false),
+      Addressing::Immediate => OperandAddress(self.registers.get_pc(), mode),
       Addressing::Indirect => {
         let base_addr = self.readu16();
-        let addr = if base_addr & 0x00FF == 0x00FF {
-          let lo = self.system.read(base_addr) as u16;
-          let hi = self.system.read(base_addr & 0xFF00) as u16;
-
-          (hi << 8) | lo
-        } else {
-          self.system.readu16(base_addr)
-        };
+        // The pointer's high byte never carries over into the next page
+        let lo = self.bus_read(base_addr) as u16;
+        let hi = self.bus_read((base_addr & 0xFF00) | (base_addr.wrapping_add(1) & 0x00FF)) as u16;
 
-        OperandAddress(addr, mode, false)
+        OperandAddress((hi << 8) | lo, mode)
       }
       Addressing::IndirectX => {
-        let fetch_addr = self.read().wrapping_add(self.registers.get(Register::X));
-        let lo = self.system.read(fetch_addr as u16) as u16;
-        let hi = self.system.read(fetch_addr.wrapping_add(1) as u16) as u16;
-        let addr = (hi << 8) | lo;
+        let base_addr = self.read();
+        self.bus_read(base_addr as u16);
 
-        OperandAddress(addr, mode, false)
+        let fetch_addr = base_addr.wrapping_add(self.registers.get(Register::X));
+        let lo = self.bus_read(fetch_addr as u16) as u16;
+        let hi = self.bus_read(fetch_addr.wrapping_add(1) as u16) as u16;
+
+        OperandAddress((hi << 8) | lo, mode)
       }
       Addressing::IndirectY => {
         let fetch_addr = self.read();
-        let lo = self.system.read(fetch_addr as u16) as u16;
-        let hi = self.system.read((fetch_addr).wrapping_add(1) as u16) as u16;
-        let base_addr = (hi << 8) | lo;
-        let addr = base_addr.wrapping_add(self.registers.get(Register::Y) as u16);
-        let extra = (addr & 0xFF00) != (base_addr & 0xFF00);
+        let lo = self.bus_read(fetch_addr as u16) as u16;
+        let hi = self.bus_read(fetch_addr.wrapping_add(1) as u16) as u16;
 
-        OperandAddress(addr, mode, extra)
+        self.indexed((hi << 8) | lo, self.registers.get(Register::Y), mode, access)
       }
       Addressing::Relative => {
         let delta = self.read() as i8;
         let base_addr = self.registers.get_pc();
         let addr = base_addr.wrapping_add(delta as u16);
 
-        OperandAddress(addr, mode, false)
+        OperandAddress(addr, mode)
+      }
+      Addressing::ZeroPage => OperandAddress(self.read() as u16, mode),
+      Addressing::ZeroPageX => {
+        let base_addr = self.read();
+        self.bus_read(base_addr as u16);
+        OperandAddress(base_addr.wrapping_add(self.registers.get(Register::X)) as u16, mode)
+      }
+      Addressing::ZeroPageY => {
+        let base_addr = self.read();
+        self.bus_read(base_addr as u16);
+        OperandAddress(base_addr.wrapping_add(self.registers.get(Register::Y)) as u16, mode)
       }
-      Addressing::ZeroPage => OperandAddress(self.read() as u16, mode, false),
-      Addressing::ZeroPageX => OperandAddress(
-        self.read().wrapping_add(self.registers.get(Register::X)) as u16,
-        mode,
-        false,
-      ),
-      Addressing::ZeroPageY => OperandAddress(
-        self.read().wrapping_add(self.registers.get(Register::Y)) as u16,
-        mode,
-        false,
-      ),
     }
   }
 
-  fn get_operand(&mut self, mode: Addressing, read_data: bool) -> Operand {
-    match (mode, read_data) {
+  fn get_operand(&mut self, mode: Addressing, access: Access) -> Operand {
+    match (mode, access) {
       (Addressing::Accumulator, _) => {
-        Operand(self.get_operand_addr(mode), self.registers.get(Register::A))
+        Operand(self.get_operand_addr(mode, access), self.registers.get(Register::A))
       }
       (Addressing::Implied | Addressing::Indirect | Addressing::Relative, _) => {
-        Operand(self.get_operand_addr(mode), 0x0)
+        Operand(self.get_operand_addr(mode, access), 0x0)
       }
-      (Addressing::Immediate, _) => Operand(self.get_operand_addr(mode), self.read()),
-      (_, false) => Operand(self.get_operand_addr(mode), 0x0),
+      (Addressing::Immediate, _) => Operand(self.get_operand_addr(mode, access), self.read()),
+      (_, Access::Write | Access::Jump) => Operand(self.get_operand_addr(mode, access), 0x0),
       _ => {
-        let OperandAddress(addr, mode, extra) = self.get_operand_addr(mode);
-        Operand(OperandAddress(addr, mode, extra), self.system.read(addr))
+        let OperandAddress(addr, mode) = self.get_operand_addr(mode, access);
+        Operand(OperandAddress(addr, mode), self.bus_read(addr))
       }
     }
   }
 
-  fn execute(&mut self, opcode: OpCode, operand: Operand) {
+  fn execute(&mut self, opcode: OpCode, operand: Operand) -> Result<(), NeoNESError> {
     match opcode {
       OpCode::ADC   =>  self.adc(operand),
       OpCode::XALR  =>  self.alr(operand),
@@ -247,7 +437,7 @@ impl CPU {
       OpCode::AND   =>  self.and(operand),
       OpCode::XANE  =>  self.ane(operand),
       OpCode::XARR  =>  self.arr(operand),
-      OpCode::ASL   =>  self.asl(operand),
+      OpCode::ASL   =>  { self.asl(operand); },
       OpCode::BCC   =>  self.bcc(operand),
       OpCode::BCS   =>  self.bcs(operand),
       OpCode::BEQ   =>  self.beq(operand),
@@ -266,15 +456,15 @@ impl CPU {
       OpCode::CPX   =>  self.cpx(operand),
       OpCode::CPY   =>  self.cpy(operand),
       OpCode::XDCP  =>  self.dcp(operand),
-      OpCode::DEC   =>  self.dec(operand),
+      OpCode::DEC   =>  { self.dec(operand); },
       OpCode::DEX   =>  self.dex(),
       OpCode::DEY   =>  self.dey(),
       OpCode::EOR   =>  self.eor(operand),
-      OpCode::INC   =>  self.inc(operand),
+      OpCode::INC   =>  { self.inc(operand); },
       OpCode::INX   =>  self.inx(),
       OpCode::INY   =>  self.iny(),
       OpCode::XISC  =>  self.isc(operand),
-      OpCode::JAM   =>  panic!("Console was jammed, please reboot."),
+      OpCode::JAM   =>  return Err(self.jam()),
       OpCode::JMP   =>  self.jmp(operand),
       OpCode::JSR   =>  self.jsr(operand),
       OpCode::XLAS  =>  self.las(operand),
@@ -282,7 +472,7 @@ impl CPU {
       OpCode::LDA   =>  self.lda(operand),
       OpCode::LDX   =>  self.ldx(operand),
       OpCode::LDY   =>  self.ldy(operand),
-      OpCode::LSR   =>  self.lsr(operand),
+      OpCode::LSR   =>  { self.lsr(operand); },
       OpCode::NOP   =>  self.nop(),
       OpCode::XNOP  =>  self.nop(),
       OpCode::ORA   =>  self.ora(operand),
@@ -291,8 +481,8 @@ impl CPU {
       OpCode::PLA   =>  self.pla(),
       OpCode::PLP   =>  self.plp(),
       OpCode::XRLA  =>  self.rla(operand),
-      OpCode::ROL   =>  self.rol(operand),
-      OpCode::ROR   =>  self.ror(operand),
+      OpCode::ROL   =>  { self.rol(operand); },
+      OpCode::ROR   =>  { self.ror(operand); },
       OpCode::XRRA  =>  self.rra(operand),
       OpCode::RTI   =>  self.rti(),
       OpCode::RTS   =>  self.rts(),
@@ -319,6 +509,7 @@ impl CPU {
       OpCode::TXS   =>  self.txs(),
       OpCode::TYA   =>  self.tya(),
     }
+    Ok(())
   }
 
   fn adc(&mut self, Operand(_, data): Operand) {
@@ -342,7 +533,7 @@ impl CPU {
 
   fn alr(&mut self, operand: Operand) {
     self.and(operand);
-    self.lsr(Operand(OperandAddress(0x0, Addressing::Accumulator, false), self.registers.get(Register::A)));
+    self.lsr(Operand(OperandAddress(0x0, Addressing::Accumulator), self.registers.get(Register::A)));
   }
 
   fn anc(&mut self, operand: Operand) {
@@ -367,7 +558,7 @@ impl CPU {
 
   fn arr(&mut self, operand: Operand) {
     self.and(operand);
-    self.ror(Operand(OperandAddress(0x0, Addressing::Accumulator, false), self.registers.get(Register::A)));
+    self.ror(Operand(OperandAddress(0x0, Addressing::Accumulator), self.registers.get(Register::A)));
 
     let b5 = self.registers.get(Register::A) & 0x20 == 0x20;
     let b6 = self.registers.get(Register::A) & 0x40 == 0x40;
@@ -376,7 +567,7 @@ impl CPU {
     self.registers.change_flag(Flag::Overflow, b5 ^ b6);
   }
 
-  fn asl(&mut self, Operand(OperandAddress(addr, mode, _), data): Operand) {
+  fn asl(&mut self, Operand(OperandAddress(addr, mode), data): Operand) -> u8 {
     self.registers.change_flag(Flag::Carry, data & 0x80 == 0x80);
     self.update_zero_negative(data << 1);
 
@@ -385,30 +576,22 @@ impl CPU {
         self.registers.set(Register::A, data << 1);
       }
       _ => {
-        self.system.write(addr, data << 1);
+        self.modify(addr, data, data << 1);
       }
     }
+    data << 1
   }
 
-  fn bcc(&mut self, Operand(OperandAddress(addr, _, _), _): Operand) {
-    if !self.registers.get_flag(Flag::Carry) {
-      self.branched = 1 + ((self.registers.get_pc() & 0xFF00) != (addr & 0xFF00)) as u8;
-      self.registers.set_pc(addr);
-    }
+  fn bcc(&mut self, Operand(OperandAddress(addr, _), _): Operand) {
+    self.branch(!self.registers.get_flag(Flag::Carry), addr);
   }
 
-  fn bcs(&mut self, Operand(OperandAddress(addr, _, _), _): Operand) {
-    if self.registers.get_flag(Flag::Carry) {
-      self.branched = 1 + ((self.registers.get_pc() & 0xFF00) != (addr & 0xFF00)) as u8;
-      self.registers.set_pc(addr);
-    }
+  fn bcs(&mut self, Operand(OperandAddress(addr, _), _): Operand) {
+    self.branch(self.registers.get_flag(Flag::Carry), addr);
   }
 
-  fn beq(&mut self, Operand(OperandAddress(addr, _, _), _): Operand) {
-    if self.registers.get_flag(Flag::Zero) {
-      self.branched = 1 + ((self.registers.get_pc() & 0xFF00) != (addr & 0xFF00)) as u8;
-      self.registers.set_pc(addr);
-    }
+  fn beq(&mut self, Operand(OperandAddress(addr, _), _): Operand) {
+    self.branch(self.registers.get_flag(Flag::Zero), addr);
   }
 
   fn bit(&mut self, Operand(_, data): Operand) {
@@ -423,25 +606,16 @@ impl CPU {
       .change_flag(Flag::Negative, data & 0x80 == 0x80);
   }
 
-  fn bmi(&mut self, Operand(OperandAddress(addr, _, _), _): Operand) {
-    if self.registers.get_flag(Flag::Negative) {
-      self.branched = 1 + ((self.registers.get_pc() & 0xFF00) != (addr & 0xFF00)) as u8;
-      self.registers.set_pc(addr);
-    }
+  fn bmi(&mut self, Operand(OperandAddress(addr, _), _): Operand) {
+    self.branch(self.registers.get_flag(Flag::Negative), addr);
   }
 
-  fn bne(&mut self, Operand(OperandAddress(addr, _, _), _): Operand) {
-    if !self.registers.get_flag(Flag::Zero) {
-      self.branched = 1 + ((self.registers.get_pc() & 0xFF00) != (addr & 0xFF00)) as u8;
-      self.registers.set_pc(addr);
-    }
+  fn bne(&mut self, Operand(OperandAddress(addr, _), _): Operand) {
+    self.branch(!self.registers.get_flag(Flag::Zero), addr);
   }
 
-  fn bpl(&mut self, Operand(OperandAddress(addr, _, _), _): Operand) {
-    if !self.registers.get_flag(Flag::Negative) {
-      self.branched = 1 + ((self.registers.get_pc() & 0xFF00) != (addr & 0xFF00)) as u8;
-      self.registers.set_pc(addr);
-    }
+  fn bpl(&mut self, Operand(OperandAddress(addr, _), _): Operand) {
+    self.branch(!self.registers.get_flag(Flag::Negative), addr);
   }
 
   fn brk(&mut self) {
@@ -449,18 +623,12 @@ impl CPU {
     self.interrupt(Interrupt::BRK);
   }
 
-  fn bvc(&mut self, Operand(OperandAddress(addr, _, _), _): Operand) {
-    if !self.registers.get_flag(Flag::Overflow) {
-      self.branched = 1 + ((self.registers.get_pc() & 0xFF00) != (addr & 0xFF00)) as u8;
-      self.registers.set_pc(addr);
-    }
+  fn bvc(&mut self, Operand(OperandAddress(addr, _), _): Operand) {
+    self.branch(!self.registers.get_flag(Flag::Overflow), addr);
   }
 
-  fn bvs(&mut self, Operand(OperandAddress(addr, _, _), _): Operand) {
-    if self.registers.get_flag(Flag::Overflow) {
-      self.branched = 1 + ((self.registers.get_pc() & 0xFF00) != (addr & 0xFF00)) as u8;
-      self.registers.set_pc(addr);
-    }
+  fn bvs(&mut self, Operand(OperandAddress(addr, _), _): Operand) {
+    self.branch(self.registers.get_flag(Flag::Overflow), addr);
   }
 
   fn clc(&mut self) {
@@ -498,14 +666,15 @@ impl CPU {
   }
 
   fn dcp(&mut self, operand: Operand) {
-    self.dec(operand);
-    let val = self.system.read(operand.0.0);
+    let val = self.dec(operand);
     self.cmp(Operand(operand.0, val));
   }
 
-  fn dec(&mut self, Operand(OperandAddress(addr, _, _), data): Operand) {
-    self.system.write(addr, data.wrapping_sub(1));
-    self.update_zero_negative(data.wrapping_sub(1));
+  fn dec(&mut self, Operand(OperandAddress(addr, _), data): Operand) -> u8 {
+    let res = data.wrapping_sub(1);
+    self.modify(addr, data, res);
+    self.update_zero_negative(res);
+    res
   }
 
   fn dex(&mut self) {
@@ -529,9 +698,11 @@ impl CPU {
     self.update_zero_negative(self.registers.get(Register::A));
   }
 
-  fn inc(&mut self, Operand(OperandAddress(addr, _, _), data): Operand) {
-    self.system.write(addr, data.wrapping_add(1));
-    self.update_zero_negative(data.wrapping_add(1));
+  fn inc(&mut self, Operand(OperandAddress(addr, _), data): Operand) -> u8 {
+    let res = data.wrapping_add(1);
+    self.modify(addr, data, res);
+    self.update_zero_negative(res);
+    res
   }
 
   fn inx(&mut self) {
@@ -549,17 +720,28 @@ impl CPU {
   }
 
   fn isc(&mut self, operand: Operand) {
-    self.inc(operand);
-    let val = self.system.read(operand.0.0);
+    let val = self.inc(operand);
     self.sbc(Operand(operand.0, val));
   }
 
-  fn jmp(&mut self, Operand(OperandAddress(addr, _, _), _): Operand) {
+  fn jam(&mut self) -> NeoNESError {
+    // The CPU halts on the opcode itself, so stepping again jams again.
+    let pc = self.registers.get_pc().wrapping_sub(1);
+    self.registers.set_pc(pc);
+    NeoNESError::Jammed { pc }
+  }
+
+  fn jmp(&mut self, Operand(OperandAddress(addr, _), _): Operand) {
     self.registers.set_pc(addr);
   }
 
-  fn jsr(&mut self, Operand(OperandAddress(addr, _, _), _): Operand) {
+  fn jsr(&mut self, Operand(OperandAddress(addr, _), _): Operand) {
+    self.stack_idle();
     self.stack_pushu16(self.registers.get_pc().wrapping_sub(1));
+
+    if let Some(debugger) = self.debugger.as_mut() {
+      debugger.call(CallKind::Subroutine, self.registers.get_pc().wrapping_sub(3), addr);
+    }
     self.registers.set_pc(addr);
   }
 
@@ -593,7 +775,7 @@ impl CPU {
     self.update_zero_negative(data);
   }
 
-  fn lsr(&mut self, Operand(OperandAddress(addr, mode, _), data): Operand) {
+  fn lsr(&mut self, Operand(OperandAddress(addr, mode), data): Operand) -> u8 {
     self.registers.change_flag(Flag::Carry, data & 0x01 == 0x01);
     self.update_zero_negative(data >> 1);
 
@@ -602,9 +784,10 @@ impl CPU {
         self.registers.set(Register::A, data >> 1);
       }
       _ => {
-        self.system.write(addr, data >> 1);
+        self.modify(addr, data, data >> 1);
       }
     }
+    data >> 1
   }
 
   fn nop(&mut self) {}
@@ -626,23 +809,24 @@ impl CPU {
   }
 
   fn pla(&mut self) {
+    self.stack_idle();
     let val = self.stack_pop();
     self.registers.set(Register::A, val);
     self.update_zero_negative(val);
   }
 
   fn plp(&mut self) {
+    self.stack_idle();
     let status = self.stack_pop() & 0xEF | 0x20;
     self.registers.set(Register::P, status);
   }
 
   fn rla(&mut self, operand: Operand) {
-    self.rol(operand);
-    let val = self.system.read(operand.0.0);
+    let val = self.rol(operand);
     self.and(Operand(operand.0, val));
   }
 
-  fn rol(&mut self, Operand(OperandAddress(addr, mode, _), data): Operand) {
+  fn rol(&mut self, Operand(OperandAddress(addr, mode), data): Operand) -> u8 {
     let prev_carry = self.registers.get_flag(Flag::Carry);
     self.registers.change_flag(Flag::Carry, data & 0x80 == 0x80);
 
@@ -654,12 +838,13 @@ impl CPU {
         self.registers.set(Register::A, res);
       }
       _ => {
-        self.system.write(addr, res);
+        self.modify(addr, data, res);
       }
     }
+    res
   }
 
-  fn ror(&mut self, Operand(OperandAddress(addr, mode, _), data): Operand) {
+  fn ror(&mut self, Operand(OperandAddress(addr, mode), data): Operand) -> u8 {
     let prev_carry = self.registers.get_flag(Flag::Carry);
     self.registers.change_flag(Flag::Carry, data & 0x01 == 0x01);
 
@@ -671,35 +856,48 @@ impl CPU {
         self.registers.set(Register::A, res);
       }
       _ => {
-        self.system.write(addr, res);
+        self.modify(addr, data, res);
       }
     }
+    res
   }
 
   fn rra(&mut self, operand: Operand) {
-    self.ror(operand);
-    let val = self.system.read(operand.0.0);
+    let val = self.ror(operand);
     self.adc(Operand(operand.0, val));
   }
 
   fn rti(&mut self) {
+    self.stack_idle();
     let status = self.stack_pop();
     self.registers.set(Register::P, status & 0xEF | 0x20);
 
     let pc = self.stack_popu16();
     self.registers.set_pc(pc);
+
+    if let Some(debugger) = self.debugger.as_mut() {
+      debugger.ret();
+    }
   }
 
   fn rts(&mut self) {
-    let pc = self.stack_popu16().wrapping_add(1);
-    self.registers.set_pc(pc);
+    self.stack_idle();
+    let pc = self.stack_popu16();
+
+    // The return address is read once more before moving past the JSR
+    self.bus_read(pc);
+    self.registers.set_pc(pc.wrapping_add(1));
+
+    if let Some(debugger) = self.debugger.as_mut() {
+      debugger.ret();
+    }
   }
 
-  fn sax(&mut self, Operand(OperandAddress(addr, _, _), _): Operand) {
+  fn sax(&mut self, Operand(OperandAddress(addr, _), _): Operand) {
     let a = self.registers.get(Register::A);
     let x = self.registers.get(Register::X);
 
-    self.system.write(addr, a & x);
+    self.bus_write(addr, a & x);
   }
 
   fn sbc(&mut self, Operand(_, data): Operand) {
@@ -745,8 +943,8 @@ impl CPU {
     self.registers.set_flag(Flag::InterruptDisable);
   }
 
-  fn sha(&mut self, Operand(OperandAddress(addr, _, _), _): Operand) {
-    self.system.write(
+  fn sha(&mut self, Operand(OperandAddress(addr, _), _): Operand) {
+    self.bus_write(
       addr,
       self.registers.get(Register::A)
         & self.registers.get(Register::X)
@@ -754,54 +952,50 @@ impl CPU {
     );
   }
 
-  fn shx(&mut self, Operand(OperandAddress(addr, _, _), _): Operand) {
+  fn shx(&mut self, Operand(OperandAddress(addr, _), _): Operand) {
     let val = self.registers.get(Register::X) & ((addr >> 8) as u8).wrapping_add(1);
-    self.system.write(
+    self.bus_write(
       (addr & 0xFF) | ((val as u16) << 8),
       val,
     );
   }
 
-  fn shy(&mut self, Operand(OperandAddress(addr, _, _), _): Operand) {
+  fn shy(&mut self, Operand(OperandAddress(addr, _), _): Operand) {
     let val = self.registers.get(Register::Y) & ((addr >> 8) as u8).wrapping_add(1);
-    self.system.write(
+    self.bus_write(
       (addr & 0xFF) | ((val as u16) << 8),
       val,
     );
   }
 
   fn slo(&mut self, operand: Operand) {
-    self.asl(operand);
-    let val = self.system.read(operand.0.0);
-    self.ora(Operand(OperandAddress(0x0, Addressing::Implied, false), val));
+    let val = self.asl(operand);
+    self.ora(Operand(OperandAddress(0x0, Addressing::Implied), val));
   }
 
   fn sre(&mut self, operand: Operand) {
-    self.lsr(operand);
-    let val = self.system.read(operand.0.0);
+    let val = self.lsr(operand);
     self.eor(Operand(operand.0, val));
   }
 
-  fn sta(&mut self, Operand(OperandAddress(addr, _, _), _): Operand) {
-    self.system.write(addr, self.registers.get(Register::A));
+  fn sta(&mut self, Operand(OperandAddress(addr, _), _): Operand) {
+    self.bus_write(addr, self.registers.get(Register::A));
   }
 
-  fn stx(&mut self, Operand(OperandAddress(addr, _, _), _): Operand) {
-    self.system.write(addr, self.registers.get(Register::X));
+  fn stx(&mut self, Operand(OperandAddress(addr, _), _): Operand) {
+    self.bus_write(addr, self.registers.get(Register::X));
   }
 
-  fn sty(&mut self, Operand(OperandAddress(addr, _, _), _): Operand) {
-    self.system.write(addr, self.registers.get(Register::Y));
+  fn sty(&mut self, Operand(OperandAddress(addr, _), _): Operand) {
+    self.bus_write(addr, self.registers.get(Register::Y));
   }
 
-  fn tas(&mut self, Operand(OperandAddress(addr, _, _), _): Operand) {
+  fn tas(&mut self, Operand(OperandAddress(addr, _), _): Operand) {
     let a = self.registers.get(Register::A);
     let x = self.registers.get(Register::X);
 
     self.registers.set(Register::SP, a & x);
-    self
-      .system
-      .write(addr, a & x & ((addr >> 8) as u8).wrapping_add(1));
+    self.bus_write(addr, a & x & ((addr >> 8) as u8).wrapping_add(1));
   }
 
   fn tax(&mut self) {
@@ -845,3 +1039,17 @@ impl CPU {
     self.update_zero_negative(self.registers.get(Register::A));
   }
 }
+
+impl Snapshot for CPU {
+  fn save(&self, state: &mut Writer) {
+    self.registers.save(state);
+    self.polling.save(state);
+    self.system.save(state);
+  }
+
+  fn load(&mut self, state: &mut Reader) -> Result<(), StateError> {
+    self.registers.load(state)?;
+    self.polling.load(state)?;
+    self.system.load(state)
+  }
+}
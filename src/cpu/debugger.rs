@@ -0,0 +1,477 @@
+use std::ops::RangeInclusive;
+
+use crate::error::NeoNESError;
+use super::register::Registers;
+
+pub use super::register::Register;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Event {
+  NMI,
+  IRQ,
+  BRK,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Space {
+  CPU,
+  PPU,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Watch {
+  Read,
+  Write,
+  Access,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Comparison {
+  Equal,
+  NotEqual,
+  Less,
+  LessEqual,
+  Greater,
+  GreaterEqual,
+}
+
+// A test on a register, such as `A == #$3F` or `PC >= $C000`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Condition {
+  pub register: Register,
+  pub comparison: Comparison,
+  pub value: u16,
+}
+
+#[derive(Clone, Debug)]
+pub struct Breakpoint {
+  pub addr: u16,
+  pub condition: Option<Condition>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Watchpoint {
+  pub space: Space,
+  pub range: RangeInclusive<u16>,
+  pub watch: Watch,
+}
+
+// Why execution stopped. Breakpoints and watchpoints are referred to by the id
+// they were added under.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stop {
+  Pause,
+  Step,
+  Scanline(u16),
+  Breakpoint(usize),
+  Watchpoint { id: usize, addr: u16, data: u8, write: bool },
+  Interrupt(Event),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CallKind {
+  Subroutine,
+  Interrupt(Event),
+}
+
+// One entry of the call stack: where control left from and where it went
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Call {
+  pub kind: CallKind,
+  pub from: u16,
+  pub to: u16,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Run {
+  Continue,
+  Into,
+  Over,
+  Return { addr: u16, depth: usize },
+  Out(usize),
+  Scanline(u16),
+}
+
+pub struct Debugger {
+  breakpoints: Vec<Option<Breakpoint>>,
+  watchpoints: Vec<Option<Watchpoint>>,
+  events: Vec<Event>,
+  calls: Vec<Call>,
+  stop: Option<Stop>,
+  run: Run,
+  resumed: bool,
+  line: u16,
+}
+
+impl Debugger {
+  // Games that leave subroutines without RTS would otherwise grow the call
+  // stack forever
+  const MAX_CALLS: usize = 0x100;
+
+  pub fn new() -> Self {
+    Debugger {
+      breakpoints: Vec::new(),
+      watchpoints: Vec::new(),
+      events: Vec::new(),
+      calls: Vec::new(),
+      stop: None,
+      run: Run::Continue,
+      resumed: false,
+      line: 0,
+    }
+  }
+
+  pub fn add_breakpoint(&mut self, addr: u16, condition: Option<Condition>) -> usize {
+    self.breakpoints.push(Some(Breakpoint { addr, condition }));
+    self.breakpoints.len() - 1
+  }
+
+  pub fn add_watchpoint(&mut self, space: Space, range: RangeInclusive<u16>, watch: Watch) -> usize {
+    self.watchpoints.push(Some(Watchpoint { space, range, watch }));
+    self.watchpoints.len() - 1
+  }
+
+  // Ids stay valid after removal, so the slot is only emptied
+  pub fn remove_breakpoint(&mut self, id: usize) -> Option<Breakpoint> {
+    self.breakpoints.get_mut(id).and_then(Option::take)
+  }
+
+  pub fn remove_watchpoint(&mut self, id: usize) -> Option<Watchpoint> {
+    self.watchpoints.get_mut(id).and_then(Option::take)
+  }
+
+  pub fn breakpoints(&self) -> impl Iterator<Item = (usize, &Breakpoint)> {
+    self.breakpoints.iter().enumerate().filter_map(|(id, bp)| bp.as_ref().map(|bp| (id, bp)))
+  }
+
+  pub fn watchpoints(&self) -> impl Iterator<Item = (usize, &Watchpoint)> {
+    self.watchpoints.iter().enumerate().filter_map(|(id, wp)| wp.as_ref().map(|wp| (id, wp)))
+  }
+
+  pub fn break_on(&mut self, event: Event, enabled: bool) {
+    self.events.retain(|e| *e != event);
+    if enabled {
+      self.events.push(event);
+    }
+  }
+
+  pub fn calls(&self) -> &[Call] {
+    &self.calls
+  }
+
+  pub fn stopped(&self) -> Option<Stop> {
+    self.stop
+  }
+
+  pub fn pause(&mut self) {
+    self.stop.get_or_insert(Stop::Pause);
+  }
+
+  pub fn resume(&mut self) {
+    self.start(Run::Continue);
+  }
+
+  pub fn step_into(&mut self) {
+    self.start(Run::Into);
+  }
+
+  // Runs through a JSR as if it were one instruction
+  pub fn step_over(&mut self) {
+    self.start(Run::Over);
+  }
+
+  pub fn step_out(&mut self) {
+    self.start(Run::Out(self.calls.len()));
+  }
+
+  pub fn run_to_scanline(&mut self, line: u16) {
+    self.start(Run::Scanline(line));
+  }
+
+  fn start(&mut self, run: Run) {
+    self.run = run;
+    self.stop = None;
+    self.resumed = true;
+  }
+
+  // Called before each instruction, returning whether to stop ahead of it. The
+  // first instruction after resuming never stops, so that continuing from a
+  // breakpoint does not hit it again.
+  pub(super) fn before(&mut self, registers: &Registers, opcode: u8, line: u16) -> bool {
+    if self.stop.is_some() {
+      return true;
+    }
+
+    let pc = registers.get_pc();
+    let entered = line != std::mem::replace(&mut self.line, line);
+
+    if std::mem::take(&mut self.resumed) {
+      if self.run == Run::Over {
+        // JSR $xxxx
+        self.run = if opcode == 0x20 {
+          Run::Return { addr: pc.wrapping_add(3), depth: self.calls.len() }
+        } else {
+          Run::Into
+        };
+      }
+      return false;
+    }
+
+    let step = match self.run {
+      Run::Continue | Run::Over => None,
+      Run::Into => Some(Stop::Step),
+      Run::Return { addr, depth } => (pc == addr && self.calls.len() <= depth).then_some(Stop::Step),
+      Run::Out(depth) => (self.calls.len() < depth).then_some(Stop::Step),
+      Run::Scanline(target) => (entered && line == target).then_some(Stop::Scanline(target)),
+    };
+
+    self.stop = step.or_else(|| {
+      self.breakpoints().find(|(_, bp)| {
+        bp.addr == pc && bp.condition.is_none_or(|condition| condition.holds(registers))
+      }).map(|(id, _)| Stop::Breakpoint(id))
+    });
+
+    self.stop.is_some()
+  }
+
+  // Watchpoints let the access finish, stopping before the next instruction
+  pub(super) fn access(&mut self, space: Space, addr: u16, data: u8, write: bool) {
+    if self.stop.is_some() {
+      return;
+    }
+
+    let hit = self.watchpoints().find(|(_, wp)| {
+      wp.space == space && wp.range.contains(&addr) && match wp.watch {
+        Watch::Read => !write,
+        Watch::Write => write,
+        Watch::Access => true,
+      }
+    }).map(|(id, _)| id);
+
+    if let Some(id) = hit {
+      self.stop = Some(Stop::Watchpoint { id, addr, data, write });
+    }
+  }
+
+  pub(super) fn call(&mut self, kind: CallKind, from: u16, to: u16) {
+    if self.calls.len() == Debugger::MAX_CALLS {
+      self.calls.remove(0);
+    }
+    self.calls.push(Call { kind, from, to });
+
+    if let CallKind::Interrupt(event) = kind {
+      if self.stop.is_none() && self.events.contains(&event) {
+        self.stop = Some(Stop::Interrupt(event));
+      }
+    }
+  }
+
+  pub(super) fn ret(&mut self) {
+    self.calls.pop();
+  }
+}
+
+impl Default for Debugger {
+  fn default() -> Self {
+    Debugger::new()
+  }
+}
+
+impl Condition {
+  pub(super) fn holds(&self, registers: &Registers) -> bool {
+    let value = match self.register {
+      Register::PC => registers.get_pc(),
+      reg => registers.get(reg) as u16,
+    };
+
+    match self.comparison {
+      Comparison::Equal => value == self.value,
+      Comparison::NotEqual => value != self.value,
+      Comparison::Less => value < self.value,
+      Comparison::LessEqual => value <= self.value,
+      Comparison::Greater => value > self.value,
+      Comparison::GreaterEqual => value >= self.value,
+    }
+  }
+
+  // Values may be written as #$3F, $3F, 0x3F or 63
+  pub fn parse(text: &str) -> Result<Condition, NeoNESError> {
+    let bad = || NeoNESError::BadCondition(text.to_string());
+    let mut parts = text.split_whitespace();
+
+    let register = match parts.next().ok_or_else(bad)?.to_ascii_uppercase().as_str() {
+      "A" => Register::A,
+      "X" => Register::X,
+      "Y" => Register::Y,
+      "P" => Register::P,
+      "SP" => Register::SP,
+      "PC" => Register::PC,
+      _ => return Err(bad()),
+    };
+
+    let comparison = match parts.next().ok_or_else(bad)? {
+      "==" => Comparison::Equal,
+      "!=" => Comparison::NotEqual,
+      "<" => Comparison::Less,
+      "<=" => Comparison::LessEqual,
+      ">" => Comparison::Greater,
+      ">=" => Comparison::GreaterEqual,
+      _ => return Err(bad()),
+    };
+
+    let value = parse_value(parts.next().ok_or_else(bad)?).ok_or_else(bad)?;
+
+    if parts.next().is_some() {
+      return Err(bad());
+    }
+
+    Ok(Condition { register, comparison, value })
+  }
+}
+
+pub fn parse_value(text: &str) -> Option<u16> {
+  let text = text.strip_prefix('#').unwrap_or(text);
+
+  match text.strip_prefix('$').or_else(|| text.strip_prefix("0x")) {
+    Some(hex) => u16::from_str_radix(hex, 16).ok(),
+    None => text.parse().ok(),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{parse_value, Comparison, Condition, Debugger, Register, Space, Stop, Watch};
+  use crate::{cpu::CPU, error::NeoNESError, system::{cartridge::Cartridge, System}};
+
+  // Calls a subroutine that adds one to A and stores it, counting calls in X:
+  //   C000  LDA #$00
+  //   C002  JSR $C010
+  //   C005  INX
+  //   C006  JMP $C002
+  //   C010  ADC #$01
+  //   C012  STA $10
+  //   C014  RTS
+  fn cpu() -> CPU {
+    let mut prg = vec![0xEA; 0x4000];
+    prg[0x00 .. 0x09].copy_from_slice(&[0xA9, 0x00, 0x20, 0x10, 0xC0, 0xE8, 0x4C, 0x02, 0xC0]);
+    prg[0x10 .. 0x15].copy_from_slice(&[0x69, 0x01, 0x85, 0x10, 0x60]);
+    prg[0x3FFC .. 0x3FFE].copy_from_slice(&[0x00, 0xC0]);
+
+    let rom = [b"NES\x1A".as_slice(), &[0x01, 0x01], &[0x00; 0x0A], &prg, &[0x00; 0x2000]].concat();
+    let mut cpu = CPU::new(System::new(Cartridge::new(rom).unwrap(), None));
+    cpu.set_debugger(Some(Debugger::new()));
+    cpu
+  }
+
+  fn debugger(cpu: &mut CPU) -> &mut Debugger {
+    cpu.debugger().unwrap()
+  }
+
+  #[test]
+  fn breakpoints_stop_before_the_instruction() {
+    let mut cpu = cpu();
+    debugger(&mut cpu).add_breakpoint(0xC005, None);
+
+    cpu.start().unwrap();
+    assert_eq!(debugger(&mut cpu).stopped(), Some(Stop::Breakpoint(0)));
+    assert_eq!((cpu.pc(), cpu.registers.get(Register::X)), (0xC005, 0x00));
+
+    // Continuing runs past the breakpoint it stopped at
+    debugger(&mut cpu).resume();
+    cpu.start().unwrap();
+    assert_eq!((cpu.pc(), cpu.registers.get(Register::X)), (0xC005, 0x01));
+  }
+
+  #[test]
+  fn conditional_breakpoints() {
+    let mut cpu = cpu();
+    let condition = Condition::parse("A >= #$03").unwrap();
+    debugger(&mut cpu).add_breakpoint(0xC005, Some(condition));
+
+    cpu.start().unwrap();
+    assert_eq!((cpu.pc(), cpu.registers.get(Register::A)), (0xC005, 0x03));
+  }
+
+  #[test]
+  fn stepping() {
+    let mut cpu = cpu();
+    debugger(&mut cpu).add_breakpoint(0xC002, None);
+    cpu.start().unwrap();
+
+    debugger(&mut cpu).step_into();
+    cpu.start().unwrap();
+    assert_eq!(debugger(&mut cpu).stopped(), Some(Stop::Step));
+    assert_eq!(cpu.pc(), 0xC010);
+    assert_eq!(debugger(&mut cpu).calls().len(), 1);
+
+    debugger(&mut cpu).step_out();
+    cpu.start().unwrap();
+    assert_eq!(cpu.pc(), 0xC005);
+    assert!(debugger(&mut cpu).calls().is_empty());
+
+    debugger(&mut cpu).step_into();
+    cpu.start().unwrap();
+    debugger(&mut cpu).step_into();
+    cpu.start().unwrap();
+    assert_eq!(cpu.pc(), 0xC002);
+
+    // The breakpoint at $C002 is the one being stepped from, so stepping over
+    // the call stops once it returns
+    debugger(&mut cpu).step_over();
+    cpu.start().unwrap();
+    assert_eq!(debugger(&mut cpu).stopped(), Some(Stop::Step));
+    assert_eq!((cpu.pc(), cpu.registers.get(Register::A)), (0xC005, 0x02));
+  }
+
+  #[test]
+  fn watchpoints_stop_after_the_access() {
+    let mut cpu = cpu();
+    debugger(&mut cpu).add_watchpoint(Space::CPU, 0x0010 ..= 0x0010, Watch::Write);
+
+    cpu.start().unwrap();
+    assert_eq!(debugger(&mut cpu).stopped(), Some(Stop::Watchpoint { id: 0, addr: 0x0010, data: 0x01, write: true }));
+    assert_eq!(cpu.pc(), 0xC014);
+  }
+
+  #[test]
+  fn removed_breakpoints_keep_the_other_ids() {
+    let mut debugger = Debugger::new();
+    debugger.add_breakpoint(0xC000, None);
+    debugger.add_breakpoint(0xC005, None);
+
+    assert!(debugger.remove_breakpoint(0).is_some());
+    assert!(debugger.remove_breakpoint(0).is_none());
+    assert_eq!(debugger.breakpoints().map(|(id, bp)| (id, bp.addr)).collect::<Vec<_>>(), [(1, 0xC005)]);
+  }
+
+  #[test]
+  fn conditions_parse() {
+    assert_eq!(
+      Condition::parse("a == #$3F"),
+      Ok(Condition { register: Register::A, comparison: Comparison::Equal, value: 0x3F }),
+    );
+    assert_eq!(
+      Condition::parse("PC >= 0xC000"),
+      Ok(Condition { register: Register::PC, comparison: Comparison::GreaterEqual, value: 0xC000 }),
+    );
+    assert_eq!(
+      Condition::parse(" SP  <  16 "),
+      Ok(Condition { register: Register::SP, comparison: Comparison::Less, value: 16 }),
+    );
+
+    for text in ["", "Q == 1", "A = 1", "A ==", "A == $GG", "A == 1 2", "A==1"] {
+      assert_eq!(Condition::parse(text), Err(NeoNESError::BadCondition(String::from(text))), "{text:?}");
+    }
+  }
+
+  #[test]
+  fn values_parse() {
+    assert_eq!(parse_value("#$3F"), Some(0x3F));
+    assert_eq!(parse_value("$C000"), Some(0xC000));
+    assert_eq!(parse_value("0xff"), Some(0xFF));
+    assert_eq!(parse_value("#63"), Some(63));
+    assert_eq!(parse_value("$10000"), None);
+    assert_eq!(parse_value("-1"), None);
+    assert_eq!(parse_value("x"), None);
+  }
+}
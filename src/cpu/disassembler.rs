@@ -0,0 +1,165 @@
+use std::collections::BTreeMap;
+
+use crate::system::System;
+use super::instruction::{Access, Addressing, Instruction, OpCode};
+
+// Anything that can be read without side effects, such as the console's
+// address space or a raw image of it.
+pub trait Bus {
+  fn peek(&self, addr: u16) -> u8;
+}
+
+impl Bus for [u8] {
+  fn peek(&self, addr: u16) -> u8 {
+    self.get(addr as usize).copied().unwrap_or(0)
+  }
+}
+
+impl Bus for System {
+  fn peek(&self, addr: u16) -> u8 {
+    System::peek(self, addr)
+  }
+}
+
+pub const NMI_VECTOR: u16 = 0xFFFA;
+pub const RESET_VECTOR: u16 = 0xFFFC;
+pub const IRQ_VECTOR: u16 = 0xFFFE;
+
+// Returns the instruction at addr in standard syntax, along with its length in
+// bytes. Branch targets are resolved, as are the pointers of indirect jumps.
+pub fn disassemble(bus: &(impl Bus + ?Sized), addr: u16) -> (String, u16) {
+  let instruction = Instruction::get(bus.peek(addr));
+  let len = length(instruction.mode);
+
+  let lo = bus.peek(addr.wrapping_add(1));
+  let word = u16::from_le_bytes([lo, bus.peek(addr.wrapping_add(2))]);
+
+  let operand = match instruction.mode {
+    Addressing::Accumulator => String::from("A"),
+    Addressing::Implied => String::new(),
+    Addressing::Immediate => format!("#${lo:02X}"),
+    Addressing::ZeroPage => format!("${lo:02X}"),
+    Addressing::ZeroPageX => format!("${lo:02X},X"),
+    Addressing::ZeroPageY => format!("${lo:02X},Y"),
+    Addressing::Absolute => format!("${word:04X}"),
+    Addressing::AbsoluteX => format!("${word:04X},X"),
+    Addressing::AbsoluteY => format!("${word:04X},Y"),
+    Addressing::Indirect => format!("(${word:04X}) = {:04X}", indirect(bus, word)),
+    Addressing::IndirectX => format!("(${lo:02X},X)"),
+    Addressing::IndirectY => format!("(${lo:02X}),Y"),
+    Addressing::Relative => format!("${:04X}", branch_target(addr, lo)),
+  };
+
+  let mnemonic = mnemonic(instruction.opcode);
+  let text = if operand.is_empty() { mnemonic } else { format!("{mnemonic} {operand}") };
+
+  (text, len)
+}
+
+// Like disassemble, but also resolves the address each operand ends up at for
+// the given index registers, and shows the data found there, as nestest does.
+pub fn disassemble_at(bus: &(impl Bus + ?Sized), addr: u16, x: u8, y: u8) -> (String, u16) {
+  let (text, len) = disassemble(bus, addr);
+  let instruction = Instruction::get(bus.peek(addr));
+
+  if instruction.access() == Access::Jump {
+    return (text, len);
+  }
+
+  let lo = bus.peek(addr.wrapping_add(1));
+  let word = u16::from_le_bytes([lo, bus.peek(addr.wrapping_add(2))]);
+  let zeropage = |ptr: u8| u16::from_le_bytes([bus.peek(ptr as u16), bus.peek(ptr.wrapping_add(1) as u16)]);
+
+  let (resolved, effective) = match instruction.mode {
+    Addressing::ZeroPage => (String::new(), lo as u16),
+    Addressing::ZeroPageX => (format!(" @ {:02X}", lo.wrapping_add(x)), lo.wrapping_add(x) as u16),
+    Addressing::ZeroPageY => (format!(" @ {:02X}", lo.wrapping_add(y)), lo.wrapping_add(y) as u16),
+    Addressing::Absolute => (String::new(), word),
+    Addressing::AbsoluteX => (format!(" @ {:04X}", word.wrapping_add(x as u16)), word.wrapping_add(x as u16)),
+    Addressing::AbsoluteY => (format!(" @ {:04X}", word.wrapping_add(y as u16)), word.wrapping_add(y as u16)),
+    Addressing::IndirectX => {
+      let target = zeropage(lo.wrapping_add(x));
+      (format!(" @ {:02X} = {target:04X}", lo.wrapping_add(x)), target)
+    }
+    Addressing::IndirectY => {
+      let base = zeropage(lo);
+      let target = base.wrapping_add(y as u16);
+      (format!(" = {base:04X} @ {target:04X}"), target)
+    }
+    _ => return (text, len),
+  };
+
+  (format!("{text}{resolved} = {:02X}", bus.peek(effective)), len)
+}
+
+// Follows the code reachable from each entry point, taking both sides of every
+// branch and stepping over subroutine calls, until it runs into a return, a
+// BRK or a jam.
+pub fn disassemble_code(bus: &(impl Bus + ?Sized), entries: &[u16]) -> BTreeMap<u16, (String, u16)> {
+  let mut code = BTreeMap::new();
+  let mut pending = entries.to_vec();
+
+  while let Some(addr) = pending.pop() {
+    if code.contains_key(&addr) {
+      continue;
+    }
+
+    let instruction = Instruction::get(bus.peek(addr));
+    let (text, len) = disassemble(bus, addr);
+    code.insert(addr, (text, len));
+
+    let next = addr.wrapping_add(len);
+    let word = u16::from_le_bytes([bus.peek(addr.wrapping_add(1)), bus.peek(addr.wrapping_add(2))]);
+
+    match (instruction.opcode, instruction.mode) {
+      (OpCode::RTS | OpCode::RTI | OpCode::BRK | OpCode::JAM, _) => { },
+      (OpCode::JMP, Addressing::Absolute) => pending.push(word),
+      (OpCode::JMP, _) => pending.push(indirect(bus, word)),
+      (OpCode::JSR, _) => pending.extend([word, next]),
+      (_, Addressing::Relative) => pending.extend([branch_target(addr, bus.peek(addr.wrapping_add(1))), next]),
+      _ => pending.push(next),
+    }
+  }
+
+  code
+}
+
+pub fn disassemble_vectors(bus: &(impl Bus + ?Sized)) -> BTreeMap<u16, (String, u16)> {
+  let entries = [NMI_VECTOR, RESET_VECTOR, IRQ_VECTOR]
+    .map(|vector| u16::from_le_bytes([bus.peek(vector), bus.peek(vector.wrapping_add(1))]));
+
+  disassemble_code(bus, &entries)
+}
+
+fn length(mode: Addressing) -> u16 {
+  match mode {
+    Addressing::Accumulator | Addressing::Implied => 1,
+    Addressing::Absolute | Addressing::AbsoluteX | Addressing::AbsoluteY | Addressing::Indirect => 3,
+    _ => 2,
+  }
+}
+
+// Unofficial opcodes are named with an X prefix, and written with a * instead.
+// ISC is written ISB, as in nestest.log.
+fn mnemonic(opcode: OpCode) -> String {
+  let name = match opcode {
+    OpCode::XISC => String::from("XISB"),
+    _ => format!("{opcode:?}"),
+  };
+
+  match name.strip_prefix('X') {
+    Some(name) => format!("*{name}"),
+    None if matches!(opcode, OpCode::JAM) => format!("*{name}"),
+    None => name,
+  }
+}
+
+fn branch_target(addr: u16, offset: u8) -> u16 {
+  addr.wrapping_add(2).wrapping_add(offset as i8 as u16)
+}
+
+// JMP ($xxFF) fetches its high byte from the start of the same page
+fn indirect(bus: &(impl Bus + ?Sized), pointer: u16) -> u16 {
+  let hi = (pointer & 0xFF00) | (pointer.wrapping_add(1) & 0x00FF);
+  u16::from_le_bytes([bus.peek(pointer), bus.peek(hi)])
+}
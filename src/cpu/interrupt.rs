@@ -1,26 +1,83 @@
+use crate::savestate::{Reader, Snapshot, StateError, Writer};
+
 #[derive(PartialEq, Eq)]
 pub struct Interrupt {
   pub read_address: u16,
   pub mask: u8,
-  pub cycles: u8,
 }
 
 impl Interrupt {
   pub const NMI: Interrupt = Interrupt {
     read_address: 0xFFFA,
     mask: 0b00100000,
-    cycles: 7,
   };
 
   pub const BRK: Interrupt = Interrupt {
     read_address: 0xFFFE,
     mask: 0b00110000,
-    cycles: 0,
   };
 
   pub const IRQ: Interrupt = Interrupt {
     read_address: 0xFFFE,
     mask: 0b00100000,
-    cycles: 7,
   };
 }
+
+// The CPU samples its interrupt lines at the end of every cycle, but only acts
+// on what it saw by the end of an instruction's second to last cycle.
+pub struct Polling {
+  nmi_line: bool,
+  pub nmi: bool,
+  pub prev_nmi: bool,
+  pub irq: bool,
+  pub prev_irq: bool,
+}
+
+impl Polling {
+  pub fn new() -> Self {
+    Polling {
+      nmi_line: false,
+      nmi: false,
+      prev_nmi: false,
+      irq: false,
+      prev_irq: false,
+    }
+  }
+
+  // NMI is edge triggered and stays pending until serviced, where IRQ is a
+  // level that the I flag masks.
+  pub fn sample(&mut self, nmi_line: bool, irq: bool) {
+    self.prev_nmi = self.nmi;
+    if nmi_line && !self.nmi_line {
+      self.nmi = true;
+    }
+    self.nmi_line = nmi_line;
+
+    self.prev_irq = self.irq;
+    self.irq = irq;
+  }
+
+  // A status read as vblank starts takes back an NMI already seen
+  pub fn cancel_nmi(&mut self) {
+    self.nmi = false;
+  }
+}
+
+impl Snapshot for Polling {
+  fn save(&self, state: &mut Writer) {
+    state.bool(self.nmi_line);
+    state.bool(self.nmi);
+    state.bool(self.prev_nmi);
+    state.bool(self.irq);
+    state.bool(self.prev_irq);
+  }
+
+  fn load(&mut self, state: &mut Reader) -> Result<(), StateError> {
+    self.nmi_line = state.bool()?;
+    self.nmi = state.bool()?;
+    self.prev_nmi = state.bool()?;
+    self.irq = state.bool()?;
+    self.prev_irq = state.bool()?;
+    Ok(())
+  }
+}
@@ -1,3 +1,5 @@
+use crate::savestate::{Reader, Snapshot, StateError, Writer};
+
 crate::utils::bitflag!(ProgramStatus,
   Carry,
   Zero,
@@ -18,9 +20,9 @@ pub struct Registers {
   stack_pointer: u8,
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Register {
-  A, X, Y, P, #[allow(unused) ]PC, SP,
+  A, X, Y, P, PC, SP,
 }
 
 impl Registers {
@@ -81,3 +83,24 @@ impl Registers {
     self.program_status.unset_flag(flag);
   }
 }
+
+impl Snapshot for Registers {
+  fn save(&self, state: &mut Writer) {
+    state.u8(self.accumulator);
+    state.u8(self.x_index);
+    state.u8(self.y_index);
+    state.u8(self.program_status.get());
+    state.u16(self.program_counter);
+    state.u8(self.stack_pointer);
+  }
+
+  fn load(&mut self, state: &mut Reader) -> Result<(), StateError> {
+    self.accumulator = state.u8()?;
+    self.x_index = state.u8()?;
+    self.y_index = state.u8()?;
+    self.program_status.set(state.u8()?);
+    self.program_counter = state.u16()?;
+    self.stack_pointer = state.u8()?;
+    Ok(())
+  }
+}
@@ -0,0 +1,125 @@
+use std::{io::{self, Write}, ops::RangeInclusive};
+
+// Logs every instruction the CPU is about to execute in the nestest.log format,
+// so that runs can be diffed against reference logs from other emulators.
+pub struct Tracer {
+  output: Box<dyn Write>,
+  range: RangeInclusive<u16>,
+  enabled: bool,
+}
+
+impl Tracer {
+  pub fn new(output: Box<dyn Write>) -> Self {
+    Tracer {
+      output,
+      range: 0x0000 ..= 0xFFFF,
+      enabled: true,
+    }
+  }
+
+  pub fn enabled(&self) -> bool {
+    self.enabled
+  }
+
+  pub fn set_enabled(&mut self, enabled: bool) {
+    self.enabled = enabled;
+  }
+
+  pub fn toggle(&mut self) {
+    self.enabled = !self.enabled;
+  }
+
+  // Only instructions starting inside the range are logged
+  pub fn set_range(&mut self, range: RangeInclusive<u16>) {
+    self.range = range;
+  }
+
+  pub fn flush(&mut self) -> io::Result<()> {
+    self.output.flush()
+  }
+
+  pub(super) fn traces(&self, pc: u16) -> bool {
+    self.enabled && self.range.contains(&pc)
+  }
+
+  pub(super) fn log(&mut self, line: &str) -> io::Result<()> {
+    writeln!(self.output, "{line}")
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{cell::RefCell, io::{self, Write}, rc::Rc};
+
+  use super::Tracer;
+  use crate::{cpu::CPU, system::{cartridge::Cartridge, System}};
+
+  // Runs from $C000 like nestest's automated mode, touching each addressing
+  // mode that gets annotated and a few unofficial opcodes, whose names have to
+  // match nestest.log's, before jumping into RAM onto an implied NOP.
+  const PROGRAM: [u8; 0x2F] = [
+    0xA2, 0x02, 0xA0, 0x03, 0xA9, 0x00, 0x85, 0x20, 0xA9, 0x03, 0x85, 0x21, 0xA9, 0x5A, 0x8D, 0x00,
+    0x03, 0xB5, 0x1E, 0xB6, 0x1E, 0xBD, 0xFD, 0x02, 0xB9, 0xFD, 0x02, 0xA1, 0x1D, 0xB1, 0x20, 0x4A,
+    0xB0, 0x00, 0xE7, 0x30, 0x04, 0x10, 0x0C, 0x00, 0x03, 0x20, 0x2C, 0xC0, 0x6C, 0x20, 0x00,
+  ];
+
+  const GOLDEN: [&str; 0x16] = [
+    "C000  A2 02     LDX #$02                        A:00 X:00 Y:00 P:24 SP:FD PPU:  0, 21 CYC:7",
+    "C002  A0 03     LDY #$03                        A:00 X:02 Y:00 P:24 SP:FD PPU:  0, 27 CYC:9",
+    "C004  A9 00     LDA #$00                        A:00 X:02 Y:03 P:24 SP:FD PPU:  0, 33 CYC:11",
+    "C006  85 20     STA $20 = 00                    A:00 X:02 Y:03 P:26 SP:FD PPU:  0, 39 CYC:13",
+    "C008  A9 03     LDA #$03                        A:00 X:02 Y:03 P:26 SP:FD PPU:  0, 48 CYC:16",
+    "C00A  85 21     STA $21 = 00                    A:03 X:02 Y:03 P:24 SP:FD PPU:  0, 54 CYC:18",
+    "C00C  A9 5A     LDA #$5A                        A:03 X:02 Y:03 P:24 SP:FD PPU:  0, 63 CYC:21",
+    "C00E  8D 00 03  STA $0300 = 00                  A:5A X:02 Y:03 P:24 SP:FD PPU:  0, 69 CYC:23",
+    "C011  B5 1E     LDA $1E,X @ 20 = 00             A:5A X:02 Y:03 P:24 SP:FD PPU:  0, 81 CYC:27",
+    "C013  B6 1E     LDX $1E,Y @ 21 = 03             A:00 X:02 Y:03 P:26 SP:FD PPU:  0, 93 CYC:31",
+    "C015  BD FD 02  LDA $02FD,X @ 0300 = 5A         A:00 X:03 Y:03 P:24 SP:FD PPU:  0,105 CYC:35",
+    "C018  B9 FD 02  LDA $02FD,Y @ 0300 = 5A         A:5A X:03 Y:03 P:24 SP:FD PPU:  0,120 CYC:40",
+    "C01B  A1 1D     LDA ($1D,X) @ 20 = 0300 = 5A    A:5A X:03 Y:03 P:24 SP:FD PPU:  0,135 CYC:45",
+    "C01D  B1 20     LDA ($20),Y = 0300 @ 0303 = 00  A:5A X:03 Y:03 P:24 SP:FD PPU:  0,153 CYC:51",
+    "C01F  4A        LSR A                           A:00 X:03 Y:03 P:26 SP:FD PPU:  0,168 CYC:56",
+    "C020  B0 00     BCS $C022                       A:00 X:03 Y:03 P:26 SP:FD PPU:  0,174 CYC:58",
+    "C022  E7 30    *ISB $30 = 00                    A:00 X:03 Y:03 P:26 SP:FD PPU:  0,180 CYC:60",
+    "C024  04 10    *NOP $10 = 00                    A:FE X:03 Y:03 P:A4 SP:FD PPU:  0,195 CYC:65",
+    "C026  0C 00 03 *NOP $0300 = 5A                  A:FE X:03 Y:03 P:A4 SP:FD PPU:  0,204 CYC:68",
+    "C029  20 2C C0  JSR $C02C                       A:FE X:03 Y:03 P:A4 SP:FD PPU:  0,216 CYC:72",
+    "C02C  6C 20 00  JMP ($0020) = 0300              A:FE X:03 Y:03 P:A4 SP:FB PPU:  0,234 CYC:78",
+    "0300  5A       *NOP                             A:FE X:03 Y:03 P:A4 SP:FB PPU:  0,249 CYC:83",
+  ];
+
+  struct Shared(Rc<RefCell<Vec<u8>>>);
+
+  impl Write for Shared {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+      self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+      Ok(())
+    }
+  }
+
+  fn nrom(program: &[u8]) -> Vec<u8> {
+    let mut prg = vec![0xEA; 0x4000];
+    prg[.. program.len()].copy_from_slice(program);
+    prg[0x3FFC .. 0x3FFE].copy_from_slice(&[0x00, 0xC0]);
+
+    let header = [0x4E, 0x45, 0x53, 0x1A, 0x01, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
+    [&header[..], &prg, &[0x00; 0x2000]].concat()
+  }
+
+  #[test]
+  fn traces_match_nestest_format() {
+    let output = Rc::new(RefCell::new(Vec::new()));
+    let mut cpu = Box::new(CPU::new(System::new(Cartridge::new(nrom(&PROGRAM)).unwrap(), None)));
+    cpu.set_tracer(Some(Tracer::new(Box::new(Shared(output.clone())))));
+
+    for _ in 0 .. GOLDEN.len() {
+      cpu.step().unwrap();
+    }
+
+    let output = String::from_utf8(output.take()).unwrap();
+    assert_eq!(output.lines().collect::<Vec<_>>(), GOLDEN);
+  }
+}
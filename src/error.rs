@@ -0,0 +1,50 @@
+use std::{fmt, io};
+
+use crate::savestate::StateError;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NeoNESError {
+  TruncatedRom { expected: usize, actual: usize },
+  BadMagic,
+  BadDisk,
+  BadBios(usize),
+  BadPatch,
+  BadNsf(&'static str),
+  BadUnif(&'static str),
+  UnsupportedMapper(u16),
+  UnsupportedBoard(String),
+  Jammed { pc: u16 },
+  TraceFailed(io::ErrorKind),
+  BadCondition(String),
+  State(StateError),
+}
+
+impl fmt::Display for NeoNESError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      NeoNESError::TruncatedRom { expected, actual } => {
+        write!(f, "ROM is truncated: expected {expected} bytes, found {actual}.")
+      }
+      NeoNESError::BadMagic => write!(f, "File not in iNES or UNIF format."),
+      NeoNESError::BadDisk => write!(f, "File not in FDS format."),
+      NeoNESError::BadBios(size) => write!(f, "FDS BIOS must be 8192 bytes, found {size}."),
+      NeoNESError::BadPatch => write!(f, "Patch is not a valid IPS file."),
+      NeoNESError::BadNsf(reason) => write!(f, "File not in NSF format: {reason}."),
+      NeoNESError::BadUnif(reason) => write!(f, "File not in UNIF format: {reason}."),
+      NeoNESError::UnsupportedMapper(mapper) => write!(f, "Unsupported mapper: {mapper}."),
+      NeoNESError::UnsupportedBoard(board) => write!(f, "Unsupported UNIF board: {board}."),
+      NeoNESError::Jammed { pc } => write!(f, "Console was jammed at {pc:#06X}, please reboot."),
+      NeoNESError::TraceFailed(kind) => write!(f, "Could not write trace: {kind}."),
+      NeoNESError::BadCondition(condition) => write!(f, "Could not parse condition: {condition}."),
+      NeoNESError::State(e) => e.fmt(f),
+    }
+  }
+}
+
+impl std::error::Error for NeoNESError { }
+
+impl From<StateError> for NeoNESError {
+  fn from(e: StateError) -> Self {
+    NeoNESError::State(e)
+  }
+}
@@ -0,0 +1,126 @@
+use crate::error::NeoNESError;
+
+const MAGIC: &[u8] = b"PATCH";
+const EOF: &[u8] = b"EOF";
+const MAX_RECORD: usize = 0xFFFF;
+
+// Records every run of bytes that differs between two images of the same size.
+pub fn diff(original: &[u8], modified: &[u8]) -> Vec<u8> {
+  let mut patch = MAGIC.to_vec();
+  let mut offset = 0;
+
+  while offset < modified.len() {
+    if original.get(offset) == Some(&modified[offset]) {
+      offset += 1;
+      continue;
+    }
+
+    let start = offset;
+    while offset < modified.len() && offset - start < MAX_RECORD && original.get(offset) != Some(&modified[offset]) {
+      offset += 1;
+    }
+
+    patch.extend_from_slice(&(start as u32).to_be_bytes()[1 ..]);
+    patch.extend_from_slice(&((offset - start) as u16).to_be_bytes());
+    patch.extend_from_slice(&modified[start .. offset]);
+  }
+
+  patch.extend_from_slice(EOF);
+  patch
+}
+
+pub fn apply(data: &mut [u8], patch: &[u8]) -> Result<(), NeoNESError> {
+  if !patch.starts_with(MAGIC) {
+    return Err(NeoNESError::BadPatch);
+  }
+
+  let mut position = MAGIC.len();
+
+  loop {
+    match patch.get(position .. position + 3) {
+      Some(EOF) => return Ok(()),
+      Some(_) => { },
+      None => return Err(NeoNESError::BadPatch),
+    }
+
+    let header = patch.get(position .. position + 5).ok_or(NeoNESError::BadPatch)?;
+    let offset = (header[0] as usize) << 16 | (header[1] as usize) << 8 | header[2] as usize;
+    let size = (header[3] as usize) << 8 | header[4] as usize;
+    position += 5;
+
+    // A zero size marks a run of one repeated byte
+    let (size, record) = if size == 0 {
+      let rle = patch.get(position .. position + 3).ok_or(NeoNESError::BadPatch)?;
+      position += 3;
+      ((rle[0] as usize) << 8 | rle[1] as usize, None)
+    } else {
+      let record = patch.get(position .. position + size).ok_or(NeoNESError::BadPatch)?;
+      position += size;
+      (size, Some(record))
+    };
+
+    let dest = data.get_mut(offset .. offset + size).ok_or(NeoNESError::BadPatch)?;
+
+    match record {
+      Some(record) => dest.copy_from_slice(record),
+      None => dest.fill(patch[position - 1]),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{apply, diff};
+  use crate::error::NeoNESError;
+
+  #[test]
+  fn identical_images_give_an_empty_patch() {
+    assert_eq!(diff(&[0x12; 0x100], &[0x12; 0x100]), b"PATCHEOF");
+  }
+
+  #[test]
+  fn diff_records_each_changed_run() {
+    let original = [0x00; 0x10];
+    let mut modified = original;
+    modified[0x02 .. 0x04].copy_from_slice(&[0xAA, 0xBB]);
+    modified[0x0F] = 0xCC;
+
+    let patch = diff(&original, &modified);
+    assert_eq!(patch, b"PATCH\x00\x00\x02\x00\x02\xAA\xBB\x00\x00\x0F\x00\x01\xCCEOF");
+
+    let mut image = original;
+    apply(&mut image, &patch).unwrap();
+    assert_eq!(image, modified);
+  }
+
+  #[test]
+  fn long_runs_are_split() {
+    let original = vec![0x00; 0x20000];
+    let modified = vec![0xFF; 0x20000];
+
+    let patch = diff(&original, &modified);
+    // The second record starts at $00FFFF, straight after the first one's data
+    assert_eq!(&patch[0x10009 .. 0x1000E], b"\x00\xFF\xFF\xFF\xFF");
+
+    let mut image = original;
+    apply(&mut image, &patch).unwrap();
+    assert_eq!(image, modified);
+  }
+
+  #[test]
+  fn apply_fills_rle_records() {
+    let mut image = [0x00; 8];
+    apply(&mut image, b"PATCH\x00\x00\x01\x00\x00\x00\x03\x77\x00\x00\x06\x00\x01\x11EOF").unwrap();
+    assert_eq!(image, [0x00, 0x77, 0x77, 0x77, 0x00, 0x00, 0x11, 0x00]);
+  }
+
+  #[test]
+  fn bad_patches_are_rejected() {
+    let mut image = [0x00; 8];
+
+    assert_eq!(apply(&mut image, b"PITCHEOF"), Err(NeoNESError::BadPatch));
+    assert_eq!(apply(&mut image, b"PATCH\x00\x00\x01\x00\x01\x11"), Err(NeoNESError::BadPatch));
+    assert_eq!(apply(&mut image, b"PATCH\x00\x00\x01\x00\x04\x11EOF"), Err(NeoNESError::BadPatch));
+    assert_eq!(apply(&mut image, b"PATCH\x00\x00\x07\x00\x02\x11\x22EOF"), Err(NeoNESError::BadPatch));
+  }
+}
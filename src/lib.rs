@@ -1,7 +1,14 @@
+// Chips, boards and formats keep the all-caps names the hardware docs use
+#![allow(clippy::upper_case_acronyms)]
+
 pub mod apu;
 pub mod cpu;
+pub mod error;
+pub mod ips;
 pub mod neones;
+pub mod nsfplayer;
 pub mod ppu;
 pub mod renderer;
+pub mod savestate;
 pub mod system;
 mod utils;
@@ -1,15 +1,140 @@
-use std::{cell::RefCell, rc::Rc};
+use std::{cell::RefCell, fs::File, io::BufWriter, path::{Path, PathBuf}, process, rc::Rc};
 
-use neones::{renderer::sdlrenderer::SDLRenderer, neones::NeoNES};
+mod repl;
+
+use neones::{cpu::tracer::Tracer, ips, renderer::sdlrenderer::SDLRenderer, neones::NeoNES, nsfplayer::NsfPlayer};
+
+const SAVE_INTERVAL: usize = 300;
 
 fn main() {
   let path = std::env::args().nth(1).unwrap_or(String::from("dev/Super_Mario.nes"));
-  let rom = std::fs::read(path).unwrap();
+  let rom = std::fs::read(&path).unwrap_or_else(|e| fail(format!("Could not read {path}: {e}")));
+  let extension = Path::new(&path).extension().map(|ext| ext.to_ascii_lowercase());
+
+  if extension.as_ref().is_some_and(|ext| ext == "nsf" || ext == "nsfe") {
+    return play(rom);
+  }
+
+  let disk = extension.is_some_and(|ext| ext == "fds");
+  let save = Path::new(&path).with_extension(if disk { "ips" } else { "sav" });
 
   let renderer = Rc::from(RefCell::from(SDLRenderer::new()));
-  let mut nes = NeoNES::new(rom, renderer.clone());
+  let mut nes = if disk {
+    let bios_path = std::env::args().nth(2).map(PathBuf::from)
+      .unwrap_or_else(|| Path::new(&path).with_file_name("disksys.rom"));
+    let bios = std::fs::read(&bios_path)
+      .unwrap_or_else(|e| fail(format!("Could not read {}: {e}", bios_path.display())));
+
+    NeoNES::fds(rom, bios, renderer.clone())
+  } else {
+    NeoNES::new(rom, renderer.clone())
+  }.unwrap_or_else(|e| fail(e));
+
+  // Disk writes are kept as a patch against the untouched image
+  let original = if disk { nes.battery_ram().map(|ram| ram.to_vec()) } else { None };
+
+  if let Ok(data) = std::fs::read(&save) {
+    match &original {
+      Some(image) => {
+        let mut image = image.clone();
+
+        match ips::apply(&mut image, &data) {
+          Ok(_) => nes.load_battery_ram(&image),
+          Err(e) => eprintln!("Could not apply {}: {e}", save.display()),
+        }
+      }
+      None => nes.load_battery_ram(&data),
+    }
+  }
 
   renderer.borrow_mut().use_callback(nes.audio());
 
-  nes.start();
+  let mut saved = nes.battery_ram().map(|ram| ram.to_vec());
+
+  while renderer.borrow().running() {
+    if let Err(e) = nes.step_frame() {
+      flush(&nes, &save, &mut saved, original.as_deref());
+      fail(e);
+    }
+
+    if renderer.borrow_mut().flip_disk() && nes.disk_sides() > 0 {
+      let side = nes.disk().map_or(0, |side| (side + 1) % nes.disk_sides());
+      nes.insert_disk(Some(side));
+    }
+
+    if renderer.borrow_mut().pause() {
+      repl::pause(&mut nes);
+    }
+
+    // Execution stays put until the debugger is told to resume
+    if nes.debugger().is_some_and(|debugger| debugger.stopped().is_some()) && !repl::run(&mut nes) {
+      break;
+    }
+
+    if renderer.borrow_mut().toggle_trace() {
+      trace(&mut nes, &Path::new(&path).with_extension("log"));
+    }
+
+    if nes.frame().number.is_multiple_of(SAVE_INTERVAL) {
+      flush(&nes, &save, &mut saved, original.as_deref());
+    }
+  }
+
+  flush(&nes, &save, &mut saved, original.as_deref());
+}
+
+fn play(file: Vec<u8>) {
+  let renderer = Rc::from(RefCell::from(SDLRenderer::new()));
+  let mut player = NsfPlayer::new(file, renderer.clone()).unwrap_or_else(|e| fail(e));
+
+  renderer.borrow_mut().use_callback(player.audio());
+
+  while renderer.borrow().running() {
+    player.step_frame().unwrap_or_else(|e| fail(e));
+
+    // Move on once a timed track has played out
+    let info = player.info();
+    if let Some(length) = info.length {
+      if player.elapsed() >= length + info.fade.unwrap_or_default() {
+        player.next();
+      }
+    }
+  }
+}
+
+// The log is only created the first time tracing is switched on
+fn trace(nes: &mut NeoNES, path: &Path) {
+  match nes.tracer() {
+    Some(tracer) => {
+      tracer.toggle();
+      if let Err(e) = tracer.flush() {
+        eprintln!("Could not write {}: {e}", path.display());
+      }
+    }
+    None => match File::create(path) {
+      Ok(file) => { nes.set_tracer(Some(Tracer::new(Box::new(BufWriter::new(file))))); },
+      Err(e) => eprintln!("Could not create {}: {e}", path.display()),
+    },
+  }
+}
+
+fn flush(nes: &NeoNES, path: &Path, saved: &mut Option<Vec<u8>>, original: Option<&[u8]>) {
+  if let (Some(ram), Some(last)) = (nes.battery_ram(), saved.as_mut()) {
+    if ram != last.as_slice() {
+      let data = match original {
+        Some(image) => ips::diff(image, ram),
+        None => ram.to_vec(),
+      };
+
+      match std::fs::write(path, data) {
+        Ok(_) => last.copy_from_slice(ram),
+        Err(e) => eprintln!("Could not write {}: {e}", path.display()),
+      }
+    }
+  }
+}
+
+fn fail(e: impl std::fmt::Display) -> ! {
+  eprintln!("{e}");
+  process::exit(1);
 }
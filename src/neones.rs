@@ -1,7 +1,13 @@
-use std::{cell::RefCell, rc::Rc};
+use std::{cell::RefCell, collections::BTreeMap, rc::Rc};
 
 use crate::{
-  apu::mixer::NESAudioCallback, cpu::CPU, renderer::Renderer, system::{cartridge::Cartridge, joypad::Flag as JoypadButton, System}
+  apu::{mixer::NESAudioCallback, Channel},
+  cpu::{debugger::Debugger, disassembler, tracer::Tracer, CPU},
+  error::NeoNESError,
+  ppu::frame::Frame,
+  renderer::Renderer,
+  savestate::{Reader, Snapshot, StateError, Writer},
+  system::{cartridge::{Cartridge, RomHeader}, joypad::Flag as JoypadButton, System},
 };
 
 pub struct NeoNES {
@@ -9,24 +15,64 @@ pub struct NeoNES {
 }
 
 impl NeoNES {
-  pub fn new(rom: Vec<u8>, renderer: Rc<RefCell<dyn Renderer>>) -> Self {
-    NeoNES {
-      cpu: CPU::new(System::new(Cartridge::new(rom).unwrap(), renderer)),
-    }
+  const STATE_MAGIC: [u8; 4] = *b"NNES";
+  const STATE_VERSION: u16 = 6;
+
+  pub fn new(rom: Vec<u8>, renderer: Rc<RefCell<dyn Renderer>>) -> Result<Self, NeoNESError> {
+    Ok(NeoNES {
+      cpu: CPU::new(System::new(Cartridge::new(rom)?, Some(renderer))),
+    })
+  }
+
+  pub fn headless(rom: Vec<u8>) -> Result<Self, NeoNESError> {
+    Ok(NeoNES {
+      cpu: CPU::new(System::new(Cartridge::new(rom)?, None)),
+    })
+  }
+
+  pub fn fds(image: Vec<u8>, bios: Vec<u8>, renderer: Rc<RefCell<dyn Renderer>>) -> Result<Self, NeoNESError> {
+    Ok(NeoNES {
+      cpu: CPU::new(System::new(Cartridge::fds(image, bios)?, Some(renderer))),
+    })
   }
 
-  pub fn start(&mut self) {
+  pub fn fds_headless(image: Vec<u8>, bios: Vec<u8>) -> Result<Self, NeoNESError> {
+    Ok(NeoNES {
+      cpu: CPU::new(System::new(Cartridge::fds(image, bios)?, None)),
+    })
+  }
+
+  pub fn start(&mut self) -> Result<(), NeoNESError> {
     self.cpu.start()
   }
 
-  pub fn step_frame(&mut self) {
-    self.cpu.step_frame();
+  pub fn step_frame(&mut self) -> Result<(), NeoNESError> {
+    self.cpu.step_frame()
   }
 
   pub fn audio(&mut self) -> NESAudioCallback {
     self.cpu.system.callback()
   }
 
+  pub fn header(&self) -> &RomHeader {
+    &self.cpu.system.header
+  }
+
+  pub fn frame(&self) -> &Frame {
+    &self.cpu.system.ppu.frame
+  }
+
+  pub fn samples(&mut self) -> Vec<f32> {
+    self.cpu.system.apu.mixer.drain()
+  }
+
+  pub fn mute(&mut self, channel: Channel, muted: bool) {
+    match channel {
+      Channel::Expansion(idx) => self.cpu.system.ppu.mapper.mute(idx, muted),
+      _ => self.cpu.system.apu.mute(channel, muted),
+    }
+  }
+
   pub fn push(&mut self, button: JoypadButton) {
     self.cpu.system.joypads.0.push(button);
   }
@@ -34,4 +80,174 @@ impl NeoNES {
   pub fn release(&mut self, button: JoypadButton) {
     self.cpu.system.joypads.0.release(button);
   }
+
+  pub fn battery_ram(&self) -> Option<&[u8]> {
+    self.cpu.system.ppu.mapper.battery_ram()
+  }
+
+  pub fn load_battery_ram(&mut self, data: &[u8]) {
+    self.cpu.system.ppu.mapper.load_battery_ram(data);
+  }
+
+  pub fn disk_sides(&self) -> usize {
+    self.cpu.system.ppu.mapper.disk_sides()
+  }
+
+  pub fn disk(&self) -> Option<usize> {
+    self.cpu.system.ppu.mapper.disk()
+  }
+
+  pub fn insert_disk(&mut self, side: Option<usize>) {
+    self.cpu.system.ppu.mapper.insert_disk(side);
+  }
+
+  // The instruction at addr, along with its length, using the current registers
+  // to resolve where its operand points
+  pub fn disassemble(&self, addr: u16) -> (String, u16) {
+    self.cpu.disassemble(addr)
+  }
+
+  pub fn pc(&self) -> u16 {
+    self.cpu.pc()
+  }
+
+  pub fn disassemble_next(&self) -> (String, u16) {
+    self.cpu.disassemble(self.cpu.pc())
+  }
+
+  pub fn disassemble_vectors(&self) -> BTreeMap<u16, (String, u16)> {
+    disassembler::disassemble_vectors(&self.cpu.system)
+  }
+
+  pub fn debugger(&mut self) -> Option<&mut Debugger> {
+    self.cpu.debugger()
+  }
+
+  // Installs a debugger, handing back the previous one. While it is stopped,
+  // step_frame returns without running anything.
+  pub fn set_debugger(&mut self, debugger: Option<Debugger>) -> Option<Debugger> {
+    self.cpu.set_debugger(debugger)
+  }
+
+  pub fn peek(&self, addr: u16) -> u8 {
+    self.cpu.system.peek(addr)
+  }
+
+  // The next instruction and the registers, as a line of the trace log
+  pub fn trace_line(&self) -> String {
+    self.cpu.trace()
+  }
+
+  pub fn tracer(&mut self) -> Option<&mut Tracer> {
+    self.cpu.tracer()
+  }
+
+  // Installs a tracer, handing back the previous one
+  pub fn set_tracer(&mut self, tracer: Option<Tracer>) -> Option<Tracer> {
+    self.cpu.set_tracer(tracer)
+  }
+
+  pub fn save_state(&self) -> Vec<u8> {
+    let mut state = Writer::new();
+
+    NeoNES::STATE_MAGIC.iter().for_each(|byte| state.u8(*byte));
+    state.u16(NeoNES::STATE_VERSION);
+    self.cpu.save(&mut state);
+
+    state.finish()
+  }
+
+  pub fn load_state(&mut self, data: &[u8]) -> Result<(), NeoNESError> {
+    // Keep a copy around so that a state which fails partway through loading
+    // does not leave the console half-restored.
+    let backup = self.save_state();
+
+    self.restore(data).inspect_err(|_| {
+      self.restore(&backup).expect("Failed to restore console state.");
+    })?;
+    Ok(())
+  }
+
+  fn restore(&mut self, data: &[u8]) -> Result<(), StateError> {
+    let mut state = Reader::new(data);
+
+    for byte in NeoNES::STATE_MAGIC {
+      if state.u8().map_err(|_| StateError::Magic)? != byte {
+        return Err(StateError::Magic);
+      }
+    }
+
+    let version = state.u16()?;
+    if version != NeoNES::STATE_VERSION {
+      return Err(StateError::Version { found: version, expected: NeoNES::STATE_VERSION });
+    }
+
+    self.cpu.load(&mut state)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::NeoNES;
+  use crate::{error::NeoNESError, savestate::StateError};
+
+  // NROM that increments $00 forever, so every frame leaves RAM different
+  fn nes() -> NeoNES {
+    let mut prg = [0x00; 0x4000];
+    prg[..5].copy_from_slice(&[0xE6, 0x00, 0x4C, 0x00, 0x80]);
+    prg[0x3FFC..].copy_from_slice(&[0x00, 0x80, 0x00, 0x80]);
+
+    let rom = [b"NES\x1A".as_slice(), &[0x01, 0x01], &[0x00; 0x0A], &prg, &[0x00; 0x2000]].concat();
+    NeoNES::headless(rom).unwrap()
+  }
+
+  #[test]
+  fn state_round_trips() {
+    let mut nes = nes();
+    nes.step_frame().unwrap();
+
+    let state = nes.save_state();
+    nes.step_frame().unwrap();
+    let later = nes.save_state();
+    assert_ne!(state, later);
+
+    nes.load_state(&state).unwrap();
+    assert_eq!(nes.save_state(), state);
+
+    nes.step_frame().unwrap();
+    assert_eq!(nes.save_state(), later);
+  }
+
+  #[test]
+  fn other_versions_are_rejected() {
+    let mut nes = nes();
+    let mut state = nes.save_state();
+    state[4..6].copy_from_slice(&(NeoNES::STATE_VERSION + 1).to_le_bytes());
+
+    nes.step_frame().unwrap();
+    let current = nes.save_state();
+
+    let expected = StateError::Version { found: NeoNES::STATE_VERSION + 1, expected: NeoNES::STATE_VERSION };
+    assert_eq!(nes.load_state(&state), Err(NeoNESError::State(expected)));
+    assert_eq!(nes.save_state(), current);
+  }
+
+  #[test]
+  fn other_data_is_rejected() {
+    let mut nes = nes();
+    assert_eq!(nes.load_state(b"NES\x1A"), Err(NeoNESError::State(StateError::Magic)));
+    assert_eq!(nes.load_state(b"NN"), Err(NeoNESError::State(StateError::Magic)));
+  }
+
+  #[test]
+  fn truncated_state_leaves_the_console_alone() {
+    let mut nes = nes();
+    let state = nes.save_state();
+
+    nes.step_frame().unwrap();
+    let current = nes.save_state();
+
+    assert_eq!(nes.load_state(&state[..state.len() - 1]), Err(NeoNESError::State(StateError::Truncated)));
+    assert_eq!(nes.save_state(), current);
+  }
 }
@@ -0,0 +1,172 @@
+use std::{cell::RefCell, rc::Rc, time::Duration};
+
+use crate::{
+  apu::{mixer::NESAudioCallback, Channel},
+  cpu::CPU,
+  error::NeoNESError,
+  ppu::{frame::Frame, palette::PALETTE},
+  renderer::{font, Renderer},
+  system::{cartridge::{Cartridge, NsfHeader, NsfTrack}, joypad::{Flag as JoypadButton, Joypad}, System},
+};
+
+// Plays NSF and NSFe rips. The console runs headless since tunes never draw
+// anything, and the player shows a track info screen in its place.
+pub struct NsfPlayer {
+  cpu: Box<CPU>,
+  header: NsfHeader,
+  track: u8,
+  started: usize,
+  renderer: Option<Rc<RefCell<dyn Renderer>>>,
+  screen: Box<Frame>,
+  joypad: Joypad,
+  held: u8,
+}
+
+impl NsfPlayer {
+  const LINE_LENGTH: usize = Frame::WIDTH / font::WIDTH - 2;
+
+  pub fn new(file: Vec<u8>, renderer: Rc<RefCell<dyn Renderer>>) -> Result<Self, NeoNESError> {
+    NsfPlayer::build(file, Some(renderer))
+  }
+
+  pub fn headless(file: Vec<u8>) -> Result<Self, NeoNESError> {
+    NsfPlayer::build(file, None)
+  }
+
+  fn build(file: Vec<u8>, renderer: Option<Rc<RefCell<dyn Renderer>>>) -> Result<Self, NeoNESError> {
+    let (header, data) = NsfHeader::new(&file)?;
+    let cartridge = Cartridge::nsf(&header, data);
+
+    Ok(NsfPlayer {
+      cpu: Box::new(CPU::new(System::new(cartridge, None))),
+      track: header.start,
+      header,
+      started: 0,
+      renderer,
+      screen: Box::new(Frame::new()),
+      joypad: Joypad::new(),
+      held: 0,
+    })
+  }
+
+  pub fn header(&self) -> &NsfHeader {
+    &self.header
+  }
+
+  pub fn tracks(&self) -> u8 {
+    self.header.songs
+  }
+
+  pub fn track(&self) -> u8 {
+    self.track
+  }
+
+  pub fn info(&self) -> &NsfTrack {
+    &self.header.tracks[self.track as usize]
+  }
+
+  pub fn select(&mut self, track: u8) {
+    self.track = track % self.header.songs;
+    self.cpu.system.ppu.mapper.select_track(self.track);
+    self.cpu.reset();
+    self.started = self.cpu.system.cycles;
+  }
+
+  pub fn next(&mut self) {
+    self.select((self.track + 1) % self.header.songs);
+  }
+
+  pub fn previous(&mut self) {
+    self.select(self.track.checked_sub(1).unwrap_or(self.header.songs - 1));
+  }
+
+  pub fn elapsed(&self) -> Duration {
+    let cycles = self.cpu.system.cycles.wrapping_sub(self.started);
+    Duration::from_secs_f64(cycles as f64 / CPU::CLOCK_RATE as f64)
+  }
+
+  pub fn step_frame(&mut self) -> Result<(), NeoNESError> {
+    self.cpu.step_frame()?;
+    self.draw();
+
+    if let Some(renderer) = &self.renderer {
+      renderer.borrow_mut().render(&self.screen.data, &mut self.joypad);
+    }
+
+    let buttons = self.joypad.buttons.get();
+    let pressed = buttons & !self.held;
+    self.held = buttons;
+
+    if pressed & (JoypadButton::Right as u8 | JoypadButton::Up as u8 | JoypadButton::A as u8) != 0 {
+      self.next();
+    } else if pressed & (JoypadButton::Left as u8 | JoypadButton::Down as u8 | JoypadButton::B as u8) != 0 {
+      self.previous();
+    }
+    Ok(())
+  }
+
+  pub fn screen(&self) -> &Frame {
+    &self.screen
+  }
+
+  pub fn audio(&mut self) -> NESAudioCallback {
+    self.cpu.system.callback()
+  }
+
+  pub fn samples(&mut self) -> Vec<f32> {
+    self.cpu.system.apu.mixer.drain()
+  }
+
+  pub fn mute(&mut self, channel: Channel, muted: bool) {
+    match channel {
+      Channel::Expansion(idx) => self.cpu.system.ppu.mapper.mute(idx, muted),
+      _ => self.cpu.system.apu.mute(channel, muted),
+    }
+  }
+
+  fn draw(&mut self) {
+    let background = PALETTE[0x0F];
+    let (white, grey, blue) = (PALETTE[0x30], PALETTE[0x10], PALETTE[0x21]);
+
+    for y in 0 .. Frame::HEIGHT {
+      for x in 0 .. Frame::WIDTH {
+        self.screen.set_pixel(x, y, background);
+      }
+    }
+
+    let track = self.info();
+    let position = format!("Track {} / {}", self.track + 1, self.header.songs);
+    let time = match track.length {
+      Some(length) => format!("{} / {}", NsfPlayer::clock(self.elapsed()), NsfPlayer::clock(length)),
+      None => NsfPlayer::clock(self.elapsed()),
+    };
+    let label = track.label.clone().unwrap_or_default();
+    let chips = [
+      (NsfHeader::VRC6, "VRC6"), (NsfHeader::VRC7, "VRC7"), (NsfHeader::FDS, "FDS"),
+      (NsfHeader::MMC5, "MMC5"), (NsfHeader::N163, "N163"), (NsfHeader::SUNSOFT5B, "5B"),
+    ].iter().filter(|(flag, _)| self.header.chips & flag != 0).map(|(_, name)| *name).collect::<Vec<_>>().join(" ");
+
+    let lines = [
+      (3, "NSF Player", blue),
+      (6, self.header.title.as_str(), white),
+      (8, self.header.artist.as_str(), grey),
+      (9, self.header.copyright.as_str(), grey),
+      (10, self.header.ripper.as_str(), grey),
+      (14, position.as_str(), white),
+      (16, label.as_str(), white),
+      (18, time.as_str(), grey),
+      (22, chips.as_str(), blue),
+      (26, "Left/Right: change track", blue),
+    ];
+
+    for (row, text, color) in lines {
+      let text: String = text.chars().take(NsfPlayer::LINE_LENGTH).collect();
+      font::draw(&mut self.screen, font::WIDTH, row * font::WIDTH, &text, color);
+    }
+  }
+
+  fn clock(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    format!("{:02}:{:02}", seconds / 60, seconds % 60)
+  }
+}
@@ -1,8 +1,9 @@
 pub mod frame;
-mod palette;
+pub(crate) mod palette;
 mod register;
 mod state;
 
+use crate::savestate::{Reader, Snapshot, StateError, Writer};
 use crate::system::mapper::{Mapper, MapperEvent};
 use crate::system::System;
 
@@ -21,50 +22,50 @@ pub struct PPU {
   pub mapper: Box<dyn Mapper>,
   pub frame: Frame,
   pub registers: Registers,
-  nmi: NMI,
+  latch: Latch,
   state: State,
   scan: RenderState,
   sprites: SpriteState,
+
+  // Left by a status read racing the start of vblank and used up within the
+  // same CPU cycle, so neither needs saving
+  vbl_suppressed: bool,
+  nmi_cancelled: bool,
 }
 
-pub(crate) struct NMI {
-  pending: bool,
-  delay: u8,
-  prev: bool,
+pub(crate) struct Latch {
+  value: u8,
+  refreshed: [usize; 8],
 }
 
-impl NMI {
+impl Latch {
+  // Each bit fades roughly 600ms after it was last driven high.
+  const DECAY_FRAMES: usize = 36;
+
   fn new() -> Self {
-    NMI {
-      pending: false,
-      delay: 0,
-      prev: false,
+    Latch {
+      value: 0,
+      refreshed: [0; 8],
     }
   }
 
-  // @fogleman hack
-  fn change(&mut self, occurred: bool, enabled: bool) {
-    let curr = enabled && occurred;
-    if curr && !self.prev {
-      self.delay = 15;
+  fn get(&mut self, frame: usize) -> u8 {
+    for bit in 0..8 {
+      if frame.wrapping_sub(self.refreshed[bit]) > Latch::DECAY_FRAMES {
+        self.value &= !(1 << bit);
+      }
     }
-    self.prev = curr;
+    self.value
   }
 
-  pub fn poll(&mut self) -> bool {
-    let res = self.pending;
-    self.pending = false;
-    res
-  }
+  fn set(&mut self, data: u8, mask: u8, frame: usize) {
+    self.value = (self.value & !mask) | (data & mask);
 
-  fn tick(&mut self) {
-    if self.delay > 0 {
-      self.delay -= 1;
-
-      if self.delay == 0 {
-          self.pending = true;
+    for bit in 0..8 {
+      if data & mask & (1 << bit) != 0 {
+        self.refreshed[bit] = frame;
       }
-  }
+    }
   }
 }
 
@@ -81,34 +82,44 @@ impl PPU {
       oam: [0; 0x100],
       frame: Frame::new(),
       registers: Registers::new(),
-      nmi: NMI::new(),
+      latch: Latch::new(),
       state: State::new(),
       scan: RenderState::new(),
       sprites: SpriteState::new(),
+      vbl_suppressed: false,
+      nmi_cancelled: false,
     }
   }
 
   pub fn read(&mut self, addr: u16) -> u8 {
-    match addr {
-      0x2000 | 0x2001 | 0x2003 | 0x2005 | 0x2006 | 0x4014 => {
-        panic!("Illegal access at write-only PPU register: {:#0X}", addr)
-      }
-      0x2002 => self.read_status(),
-      0x2004 => self.read_oam_data(),
-      0x2007 => self.read_data(),
-      0x2008..=System::PPU_END => self.read(addr & 0x2007),
-      _ => panic!("Illegal PPU read access: {:#0X}", addr),
-    }
+    // Bits not driven by the register come from the I/O latch
+    let (data, driven) = match addr {
+      0x2002 => (self.read_status(), 0xE0),
+      0x2004 => (self.read_oam_data(), 0xFF),
+      0x2007 => match self.registers.read_address() & 0x3FFF {
+        0x3F00..=0x3FFF => (self.read_data(), 0x3F),
+        _ => (self.read_data(), 0xFF),
+      },
+      0x2008..=System::PPU_END => return self.read(addr & 0x2007),
+      _ => (0, 0x00), // Write-only registers
+    };
+
+    self.latch.set(data, driven, self.frame.number);
+    self.latch.get(self.frame.number)
   }
 
   pub fn write(&mut self, addr: u16, data: u8) {
+    self.latch.set(data, 0xFF, self.frame.number);
+
     match addr {
       0x2000 => {
-        let occurred = self.registers.write_controller(data);
-        self.nmi(occurred);
+        self.registers.write_controller(data);
+        self.mapper.notify(MapperEvent::ControlChanged(data));
+      }
+      0x2001 => {
+        self.registers.mask.set(data);
+        self.mapper.notify(MapperEvent::MaskChanged(data));
       }
-      0x2001 => self.registers.mask.set(data),
-      0x2002 => panic!("Illegal write to PPU status register."),
       0x2003 => self.registers.write_oam_addr(data),
       0x2004 => self.write_oam_data(data),
       0x2005 => self.registers.write_scroll(data),
@@ -118,13 +129,11 @@ impl PPU {
       }
       0x2007 => self.write_data(data),
       0x2008..=System::PPU_END => self.write(addr & 0x2007, data),
-      _ => panic!("Illegal PPU write access: {:#0X}", addr),
+      _ => { } // Read-only status register
     }
   }
 
   fn clock_tick(&mut self) {
-    self.nmi.tick();
-
     if self.rendering_enabled()
       && self.state.odd && self.scan.line == PPU::TOTAL_SCANLINES - 1
       && self.scan.dot == PPU::SCANLINE_DURATION - 2 {
@@ -159,6 +168,11 @@ impl PPU {
     let fetch         = prefetch || render_cycle;
 
     if self.rendering_enabled() {
+      if render && self.scan.dot == 321 {
+        let next = if prerender { 0 } else { self.scan.line + 1 };
+        self.mapper.notify(MapperEvent::Scanline(next));
+      }
+
       if visible && render_cycle {
         self.render_pixel()
       }
@@ -204,9 +218,9 @@ impl PPU {
     }
 
     if self.scan.line == PPU::VISIBLE_SCANLINES && self.scan.dot == 1 {
-      self.registers.status.set_flag(StatusFlag::VBLankStarted);
-
-      self.nmi(true);
+      if !std::mem::take(&mut self.vbl_suppressed) {
+        self.registers.status.set_flag(StatusFlag::VBLankStarted);
+      }
       self.frame.number = self.frame.number.wrapping_add(1);
       return true;
     }
@@ -216,7 +230,6 @@ impl PPU {
     }
 
     if prerender && self.scan.dot == 1 {
-      self.nmi(false);
       self.registers.status.unset_flag(StatusFlag::SpriteZeroHit);
       self.registers.status.unset_flag(StatusFlag::SpriteOverflow);
       self.registers.status.unset_flag(StatusFlag::VBLankStarted);
@@ -226,7 +239,7 @@ impl PPU {
   }
 
   fn read_data(&mut self) -> u8 {
-    let addr = self.registers.read_address();
+    let addr = self.registers.read_address() & 0x3FFF;
     self
       .registers
       .increment_address(self.registers.controller.vram_increment());
@@ -235,13 +248,12 @@ impl PPU {
     match addr {
       0x0000..=0x1FFF => self.mapper_read(addr),
       0x2000..=0x3EFF => self.vram_read(addr),
-      0x3F00..=0x3FFF => self.palette_read(addr),
-      _ => panic!("Unexpected read at {:#0x}", addr),
+      _ => self.palette_read(addr),
     }
   }
 
   fn write_data(&mut self, data: u8) {
-    let addr = self.registers.read_address();
+    let addr = self.registers.read_address() & 0x3FFF;
     self
       .registers
       .increment_address(self.registers.controller.vram_increment());
@@ -250,25 +262,30 @@ impl PPU {
     match addr {
       0x0000..=0x1FFF => self.mapper.write(addr, data),
       0x2000..=0x3EFF => self.vram_write(addr, data),
-      0x3F00..=0x3FFF => self.palette_write(addr, data),
-      _ => panic!("Unexpected write at {:#0x}", addr),
+      _ => self.palette_write(addr, data),
     };
   }
 
   fn mapper_read(&mut self, addr: u16) -> u8 {
     let res = self.state.buffer;
-    self.state.buffer = self.mapper.read(addr);
+    self.state.buffer = self.pattern_read(addr);
+    res
+  }
+
+  fn pattern_read(&mut self, addr: u16) -> u8 {
+    let res = self.mapper.read(addr).unwrap_or(0);
+    self.mapper.notify(MapperEvent::PatternFetch(addr));
     res
   }
 
   fn vram_read(&mut self, addr: u16) -> u8 {
     let res = self.state.buffer;
-    self.state.buffer = self.vram[self.mirror_vram(addr) as usize];
+    self.state.buffer = self.mapper.read_nametable(addr, &self.vram);
     res
   }
 
   fn vram_write(&mut self, addr: u16, data: u8) {
-    self.vram[self.mirror_vram(addr) as usize] = data;
+    self.mapper.write_nametable(addr, data, &mut self.vram);
   }
 
   fn palette_read(&self, addr: u16) -> u8 {
@@ -289,18 +306,6 @@ impl PPU {
     self.palette[idx] = data;
   }
 
-  fn mirror_vram(&self, addr: u16) -> u16 {
-    let addr = (addr - 0x2000) % 0x1000;
-    let table = addr / 0x400;
-    let offset = addr % 0x400;
-
-    let index = 0x2000
-      + self.mapper.as_ref().mirroring().coeff()[table as usize] * 0x400
-      + offset;
-
-    index % 0x800
-  }
-
   fn read_oam_data(&self) -> u8 {
     self.oam[self.registers.oam_address as usize]
   }
@@ -313,9 +318,18 @@ impl PPU {
     self.registers.increment_oam_addr();
   }
 
+  // Reading a dot before vblank starts keeps the flag from being set for the
+  // frame, while reading as it starts sees the flag but cancels the NMI.
   fn read_status(&mut self) -> u8 {
+    if self.scan.line == PPU::VISIBLE_SCANLINES {
+      match self.scan.dot {
+        0 => self.vbl_suppressed = true,
+        1 | 2 => self.nmi_cancelled = true,
+        _ => { },
+      }
+    }
+
     let res = self.registers.status.get();
-    self.nmi(false);
     self.registers.status.unset_flag(StatusFlag::VBLankStarted);
     self.registers.reset_latch();
 
@@ -412,38 +426,40 @@ impl PPU {
     self.state.tile |= data as u64;
   }
 
-  fn fetch_nametable(&self) -> u8 {
+  fn fetch_nametable(&mut self) -> u8 {
     let v = self.registers.read_address();
     let address = 0x2000 | (v & 0x0FFF);
-    self.vram[self.mirror_vram(address) as usize]
+    self.mapper.read_nametable(address, &self.vram)
   }
 
-  fn fetch_attrtable(&self) -> u8 {
+  fn fetch_attrtable(&mut self) -> u8 {
     let v = self.registers.read_address();
     let address = 0x23C0 | (v & 0x0C00) | ((v >> 4) & 0x0038) | ((v >> 2) & 0x0007);
-    let byte = self.vram[self.mirror_vram(address) as usize];
+    let byte = self.mapper.read_nametable(address, &self.vram);
     let shift = ((v >> 4) & 0x04) | (v & 0x02);
     ((byte >> shift) & 0x03) << 2
   }
 
-  fn fetch_lotile(&self) -> u8 {
+  fn fetch_lotile(&mut self) -> u8 {
     let y = (self.registers.read_address() >> 12) & 0x07;
     let tile = self.state.nametable as u16;
     let address = self.registers.controller.background_pattern_table() + y + (16 * tile);
-    self.mapper.read(address)
+    self.pattern_read(address)
   }
 
-  fn fetch_hitile(&self) -> u8 {
+  fn fetch_hitile(&mut self) -> u8 {
     let y = (self.registers.read_address() >> 12) & 0x07;
     let tile = self.state.nametable as u16;
     let address = self.registers.controller.background_pattern_table() + y + (16 * tile) + 8;
-    self.mapper.read(address)
+    self.pattern_read(address)
   }
 
   fn evaluate_sprites(&mut self) {
     let size = self.registers.controller.sprite_size();
     let mut count = 0;
 
+    self.mapper.notify(MapperEvent::SpriteFetch(true));
+
     for sprite_idx in 0 .. 64 {
       let y = self.oam[(sprite_idx * 4 + 0) as usize % 0x100];
       let a = self.oam[(sprite_idx * 4 + 2) as usize % 0x100];
@@ -465,6 +481,8 @@ impl PPU {
       count += 1;
     }
 
+    self.mapper.notify(MapperEvent::SpriteFetch(false));
+
     if count > 8 {
       count = 8;
       self.registers.status.set_flag(StatusFlag::SpriteOverflow);
@@ -501,8 +519,8 @@ impl PPU {
     };
 
     let a = ((attrs & 0x03) << 2) as u32;
-    let mut lo = self.mapper.read(address) as u32;
-    let mut hi = self.mapper.read(address + 8) as u32;
+    let mut lo = self.pattern_read(address) as u32;
+    let mut hi = self.pattern_read(address + 8) as u32;
 
     (0 .. 8).fold(0, |acc, _| {
       let p1;
@@ -526,11 +544,127 @@ impl PPU {
     })
   }
 
-  fn nmi(&mut self, occurred: bool) {
-    self.nmi.change(occurred, self.registers.controller.get_flag(ControllerFlag::NMIGen));
+  // The NMI output stays low for as long as both vblank and NMI generation
+  // are set, leaving edge detection to the CPU.
+  pub fn nmi_line(&self) -> bool {
+    self.registers.status.get_flag(StatusFlag::VBLankStarted)
+      && self.registers.controller.get_flag(ControllerFlag::NMIGen)
+  }
+
+  pub fn nmi_cancelled(&mut self) -> bool {
+    std::mem::take(&mut self.nmi_cancelled)
+  }
+
+  // Where the next access through PPUDATA lands
+  pub fn data_address(&self) -> u16 {
+    self.registers.read_address() & 0x3FFF
+  }
+
+  // The scanline and dot about to be rendered, with the pre-render line last
+  pub fn position(&self) -> (u16, usize) {
+    (self.scan.line, self.scan.dot)
+  }
+}
+
+impl Snapshot for Latch {
+  fn save(&self, state: &mut Writer) {
+    state.u8(self.value);
+    self.refreshed.iter().for_each(|frame| state.usize(*frame));
+  }
+
+  fn load(&mut self, state: &mut Reader) -> Result<(), StateError> {
+    self.value = state.u8()?;
+    for frame in self.refreshed.iter_mut() {
+      *frame = state.usize()?;
+    }
+    Ok(())
+  }
+}
+
+impl Snapshot for PPU {
+  fn save(&self, state: &mut Writer) {
+    state.bytes(&self.palette);
+    state.bytes(&self.vram);
+    state.bytes(&self.oam);
+    state.usize(self.frame.number);
+    self.registers.save(state);
+    self.latch.save(state);
+    self.state.save(state);
+    self.scan.save(state);
+    self.sprites.save(state);
+    self.mapper.save(state);
+  }
+
+  fn load(&mut self, state: &mut Reader) -> Result<(), StateError> {
+    state.bytes_into(&mut self.palette, "palette size")?;
+    state.bytes_into(&mut self.vram, "VRAM size")?;
+    state.bytes_into(&mut self.oam, "OAM size")?;
+    self.frame.number = state.usize()?;
+    self.registers.load(state)?;
+    self.latch.load(state)?;
+    self.state.load(state)?;
+    self.scan.load(state)?;
+    self.sprites.load(state)?;
+    self.mapper.load(state)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::PPU;
+  use crate::system::{cartridge::Cartridge, System};
+
+  // An NROM board with NMIs turned on
+  fn ppu() -> PPU {
+    let rom = [b"NES\x1A".as_slice(), &[0x01, 0x01], &[0x00; 0x0A], &[0x00; 0x6000]].concat();
+    let mut ppu = System::new(Cartridge::new(rom).unwrap(), None).ppu;
+    ppu.write(0x2000, 0x80);
+    ppu
+  }
+
+  // Reads the status at a dot on the first line of vblank, returning the
+  // vblank bit read, whether the NMI line went up afterwards and whether an
+  // NMI already seen was taken back.
+  fn race(dot: usize) -> (bool, bool, bool) {
+    let mut ppu = ppu();
+
+    while (ppu.scan.line, ppu.scan.dot) != (PPU::VISIBLE_SCANLINES, dot) {
+      ppu.tick();
+    }
+
+    let status = ppu.read(0x2002);
+    let cancelled = ppu.nmi_cancelled();
+    let mut nmi = false;
+
+    while (ppu.scan.line, ppu.scan.dot) != (PPU::VISIBLE_SCANLINES + 1, 0) {
+      ppu.tick();
+      nmi |= ppu.nmi_line();
+    }
+
+    (status & 0x80 == 0x80, nmi, cancelled)
+  }
+
+  #[test]
+  fn vblank_raises_nmi() {
+    let mut ppu = ppu();
+    while !ppu.tick() { }
+    assert_eq!((ppu.scan.line, ppu.scan.dot), (PPU::VISIBLE_SCANLINES, 1));
+    assert!(ppu.nmi_line());
+  }
+
+  #[test]
+  fn status_read_before_vblank_suppresses_it() {
+    assert_eq!(race(0), (false, false, false));
+  }
+
+  #[test]
+  fn status_read_as_vblank_starts_cancels_nmi() {
+    assert_eq!(race(1), (true, false, true));
+    assert_eq!(race(2), (true, false, true));
   }
 
-  pub fn poll(&mut self) -> bool {
-    self.nmi.poll()
+  #[test]
+  fn status_read_later_leaves_nmi() {
+    assert_eq!(race(3), (true, false, false));
   }
 }
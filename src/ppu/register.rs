@@ -2,10 +2,11 @@ pub(in crate::ppu) mod controller;
 pub(in crate::ppu) mod mask;
 pub(in crate::ppu) mod status;
 
-use controller::{Controller, Flag as ControllerFlag};
+use controller::Controller;
 use mask::Mask;
-use status::{Status, Flag as StatusFlag};
+use status::Status;
 
+use crate::savestate::{Reader, Snapshot, StateError, Writer};
 use crate::system::System;
 
 pub struct Registers {
@@ -72,16 +73,11 @@ impl Registers {
     self.latch = !self.latch;
   }
 
-  pub fn write_controller(&mut self, data: u8) -> bool {
-    let nmi_gen = self.controller.get_flag(ControllerFlag::NMIGen);
+  pub fn write_controller(&mut self, data: u8) {
     self.controller.set(data);
 
     // t: ...GH.. ........ <- d: ......GH
     self.t = (self.t & 0xF3FF) | (((data as u16) & 0x03) << 10);
-
-    !nmi_gen
-      && self.controller.get_flag(ControllerFlag::NMIGen)
-      && self.status.get_flag(StatusFlag::VBLankStarted)
   }
 
   pub fn read_address(&self) -> u16 {
@@ -145,3 +141,28 @@ impl Registers {
     self.v = (self.v & 0xFBE0) | (self.t & 0x041F);
   }
 }
+
+impl Snapshot for Registers {
+  fn save(&self, state: &mut Writer) {
+    state.u8(self.controller.get());
+    state.u8(self.status.get());
+    state.u8(self.mask.get());
+    state.u8(self.oam_address);
+    state.u16(self.v);
+    state.u16(self.t);
+    state.u8(self.x);
+    state.bool(self.latch);
+  }
+
+  fn load(&mut self, state: &mut Reader) -> Result<(), StateError> {
+    self.controller.set(state.u8()?);
+    self.status.set(state.u8()?);
+    self.mask.set(state.u8()?);
+    self.oam_address = state.u8()?;
+    self.v = state.u16()?;
+    self.t = state.u16()?;
+    self.x = state.u8()?;
+    self.latch = state.bool()?;
+    Ok(())
+  }
+}
@@ -1,3 +1,5 @@
+use crate::savestate::{Reader, Snapshot, StateError, Writer};
+
 
 pub struct State {
   pub buffer: u8,
@@ -58,3 +60,64 @@ impl SpriteState {
     }
   }
 }
+
+impl Snapshot for State {
+  fn save(&self, state: &mut Writer) {
+    state.u8(self.buffer);
+    state.bool(self.odd);
+    state.u64(self.tile);
+    state.u8(self.attrtable);
+    state.u8(self.hitile);
+    state.u8(self.lotile);
+    state.u8(self.nametable);
+  }
+
+  fn load(&mut self, state: &mut Reader) -> Result<(), StateError> {
+    self.buffer = state.u8()?;
+    self.odd = state.bool()?;
+    self.tile = state.u64()?;
+    self.attrtable = state.u8()?;
+    self.hitile = state.u8()?;
+    self.lotile = state.u8()?;
+    self.nametable = state.u8()?;
+    Ok(())
+  }
+}
+
+impl Snapshot for RenderState {
+  fn save(&self, state: &mut Writer) {
+    state.u16(self.line);
+    state.usize(self.dot);
+  }
+
+  fn load(&mut self, state: &mut Reader) -> Result<(), StateError> {
+    self.line = state.u16()?;
+    self.dot = state.usize()?;
+    Ok(())
+  }
+}
+
+impl Snapshot for SpriteState {
+  fn save(&self, state: &mut Writer) {
+    state.usize(self.count);
+
+    for i in 0 .. 0x08 {
+      state.usize(self.indices[i]);
+      state.u32(self.patterns[i]);
+      state.u8(self.positions[i]);
+      state.u8(self.priorities[i]);
+    }
+  }
+
+  fn load(&mut self, state: &mut Reader) -> Result<(), StateError> {
+    self.count = state.usize()?.min(0x08);
+
+    for i in 0 .. 0x08 {
+      self.indices[i] = state.usize()?;
+      self.patterns[i] = state.u32()?;
+      self.positions[i] = state.u8()?;
+      self.priorities[i] = state.u8()?;
+    }
+    Ok(())
+  }
+}
@@ -1,3 +1,4 @@
+pub mod font;
 #[cfg(not(target_arch = "wasm32"))]
 pub mod sdlrenderer;
 
@@ -20,6 +20,10 @@ pub struct SDLRenderer {
   texture_creator: TextureCreator<WindowContext>,
   event_pump: EventPump,
   audio: AudioSubsystem,
+  running: bool,
+  flip: bool,
+  trace: bool,
+  pause: bool,
 }
 
 impl AudioCallback for NESAudioCallback {
@@ -42,10 +46,13 @@ impl Renderer for SDLRenderer {
 
     for event in self.event_pump.poll_iter() {
       match event {
-        Event::Quit { .. } => std::process::exit(0),
+        Event::Quit { .. } => self.running = false,
         Event::KeyDown {  keycode: Some(key), .. } => {
           match key {
-            Keycode::Escape => std::process::exit(0),
+            Keycode::Escape => self.running = false,
+            Keycode::Tab => self.flip = true,
+            Keycode::T => self.trace = true,
+            Keycode::Backquote => self.pause = true,
 
             Keycode::W => joypad.push(JoypadButton::Up),
             Keycode::A => joypad.push(JoypadButton::Left),
@@ -112,9 +119,29 @@ impl SDLRenderer {
       texture_creator,
       event_pump,
       audio,
+      running: true,
+      flip: false,
+      trace: false,
+      pause: false,
     }
   }
 
+  pub fn running(&self) -> bool {
+    self.running
+  }
+
+  pub fn flip_disk(&mut self) -> bool {
+    std::mem::take(&mut self.flip)
+  }
+
+  pub fn toggle_trace(&mut self) -> bool {
+    std::mem::take(&mut self.trace)
+  }
+
+  pub fn pause(&mut self) -> bool {
+    std::mem::take(&mut self.pause)
+  }
+
   pub fn use_callback(&mut self, callback: NESAudioCallback) {
     let audio = self.audio.open_playback(None, &AudioSpecDesired {
       freq: Some(Mixer::OUTPUT_FREQ as i32),
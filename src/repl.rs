@@ -0,0 +1,191 @@
+use std::io::{self, BufRead, Write};
+
+use neones::{
+  cpu::debugger::{parse_value, CallKind, Condition, Debugger, Event, Space, Stop, Watch},
+  neones::NeoNES,
+};
+
+const HELP: &str = "\
+c                          continue
+s / n / o                  step into / over / out
+l <line>                   run to scanline
+b <addr> [if <cond>]       break at addr, e.g. b $C000 if A == #$3F
+w <cpu|ppu> <r|w|rw> <addr>[-<end>]
+                           watch reads and/or writes
+on / off <nmi|irq|brk>     break when the interrupt is taken
+d <id> / dw <id>           delete a breakpoint / watchpoint
+i                          list breakpoints and watchpoints
+bt                         call stack
+x <addr> [count]           dump memory
+u [addr] [count]           disassemble
+q                          quit";
+
+// Reads commands from stdin while the debugger is stopped, until one of them
+// resumes execution. Returns false if the emulator should quit.
+pub fn run(nes: &mut NeoNES) -> bool {
+  if let Some(stop) = nes.debugger().and_then(|debugger| debugger.stopped()) {
+    println!("{}", describe(stop));
+  }
+  println!("{}", nes.trace_line());
+
+  let mut lines = io::stdin().lock().lines();
+
+  loop {
+    print!("> ");
+    io::stdout().flush().ok();
+
+    let Some(Ok(line)) = lines.next() else {
+      // Without a terminal to read from, let the game run on
+      nes.set_debugger(None);
+      return true;
+    };
+
+    let words = line.split_whitespace().collect::<Vec<_>>();
+    let Some(debugger) = nes.debugger() else { return true };
+
+    match words.as_slice() {
+      [] => { },
+      ["c"] => { debugger.resume(); return true; },
+      ["s"] => { debugger.step_into(); return true; },
+      ["n"] => { debugger.step_over(); return true; },
+      ["o"] => { debugger.step_out(); return true; },
+      ["l", line] => match parse_value(line) {
+        Some(line) => { debugger.run_to_scanline(line); return true; },
+        None => println!("Bad scanline: {line}"),
+      },
+      ["b", addr, rest @ ..] => {
+        let condition = match rest {
+          [] => Ok(None),
+          ["if", condition @ ..] => Condition::parse(&condition.join(" ")).map(Some),
+          _ => Condition::parse(&rest.join(" ")).map(Some),
+        };
+
+        match (parse_value(addr), condition) {
+          (Some(addr), Ok(condition)) => println!("Breakpoint {}", debugger.add_breakpoint(addr, condition)),
+          (None, _) => println!("Bad address: {addr}"),
+          (_, Err(e)) => println!("{e}"),
+        }
+      }
+      ["w", space, watch, range] => {
+        let space = match *space {
+          "cpu" => Some(Space::CPU),
+          "ppu" => Some(Space::PPU),
+          _ => None,
+        };
+        let watch = match *watch {
+          "r" => Some(Watch::Read),
+          "w" => Some(Watch::Write),
+          "rw" => Some(Watch::Access),
+          _ => None,
+        };
+        let range = match range.split_once('-') {
+          Some((start, end)) => parse_value(start).zip(parse_value(end)),
+          None => parse_value(range).map(|addr| (addr, addr)),
+        };
+
+        match (space, watch, range) {
+          (Some(space), Some(watch), Some((start, end))) => {
+            println!("Watchpoint {}", debugger.add_watchpoint(space, start ..= end, watch));
+          }
+          _ => println!("Usage: w <cpu|ppu> <r|w|rw> <addr>[-<end>]"),
+        }
+      }
+      [toggle @ ("on" | "off"), event] => {
+        let event = match *event {
+          "nmi" => Some(Event::NMI),
+          "irq" => Some(Event::IRQ),
+          "brk" => Some(Event::BRK),
+          _ => None,
+        };
+
+        match event {
+          Some(event) => debugger.break_on(event, *toggle == "on"),
+          None => println!("Usage: {toggle} <nmi|irq|brk>"),
+        }
+      }
+      ["d", id] => {
+        if id.parse().ok().and_then(|id| debugger.remove_breakpoint(id)).is_none() {
+          println!("No breakpoint {id}");
+        }
+      }
+      ["dw", id] => {
+        if id.parse().ok().and_then(|id| debugger.remove_watchpoint(id)).is_none() {
+          println!("No watchpoint {id}");
+        }
+      }
+      ["i"] => {
+        for (id, bp) in debugger.breakpoints() {
+          match &bp.condition {
+            Some(c) => println!("b{id}: ${:04X} if {:?} {:?} ${:X}", bp.addr, c.register, c.comparison, c.value),
+            None => println!("b{id}: ${:04X}", bp.addr),
+          }
+        }
+        for (id, wp) in debugger.watchpoints() {
+          println!("w{id}: {:?} {:?} ${:04X}-${:04X}", wp.space, wp.watch, wp.range.start(), wp.range.end());
+        }
+      }
+      ["bt"] => {
+        for call in debugger.calls().iter().rev() {
+          match call.kind {
+            CallKind::Subroutine => println!("${:04X} from ${:04X}", call.to, call.from),
+            CallKind::Interrupt(event) => println!("${:04X} {event:?} at ${:04X}", call.to, call.from),
+          }
+        }
+      }
+      ["x", addr, rest @ ..] => match (parse_value(addr), rest.first().map_or(Some(0x10), |count| parse_value(count))) {
+        (Some(addr), Some(count)) => {
+          for row in (0 .. count).step_by(0x10) {
+            let start = addr.wrapping_add(row);
+            let bytes = (0 .. (count - row).min(0x10))
+              .map(|offset| format!("{:02X}", nes.peek(start.wrapping_add(offset))))
+              .collect::<Vec<_>>();
+            println!("{start:04X}  {}", bytes.join(" "));
+          }
+        }
+        _ => println!("Usage: x <addr> [count]"),
+      },
+      ["u", rest @ ..] => {
+        let addr = rest.first().map_or(Some(nes.pc()), |addr| parse_value(addr));
+        let count = rest.get(1).map_or(Some(10), |count| parse_value(count));
+
+        match addr.zip(count) {
+          Some((mut addr, count)) => {
+            for _ in 0 .. count {
+              let (text, len) = nes.disassemble(addr);
+              println!("{addr:04X}  {text}");
+              addr = addr.wrapping_add(len);
+            }
+          }
+          None => println!("Usage: u [addr] [count]"),
+        }
+      }
+      ["q"] => return false,
+      _ => println!("{HELP}"),
+    }
+  }
+}
+
+// Stops the game where it is, bringing up a debugger the first time
+pub fn pause(nes: &mut NeoNES) {
+  if nes.debugger().is_none() {
+    nes.set_debugger(Some(Debugger::new()));
+  }
+
+  if let Some(debugger) = nes.debugger() {
+    debugger.pause();
+  }
+}
+
+fn describe(stop: Stop) -> String {
+  match stop {
+    Stop::Pause => String::from("Paused"),
+    Stop::Step => String::from("Stepped"),
+    Stop::Scanline(line) => format!("Reached scanline {line}"),
+    Stop::Breakpoint(id) => format!("Hit breakpoint {id}"),
+    Stop::Watchpoint { id, addr, data, write } => {
+      let access = if write { "Write of" } else { "Read of" };
+      format!("Hit watchpoint {id}: {access} ${data:02X} at ${addr:04X}")
+    }
+    Stop::Interrupt(event) => format!("Took {event:?}"),
+  }
+}
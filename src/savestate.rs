@@ -0,0 +1,207 @@
+use std::fmt;
+
+pub trait Snapshot {
+  fn save(&self, state: &mut Writer);
+
+  fn load(&mut self, state: &mut Reader) -> Result<(), StateError>;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StateError {
+  Magic,
+  Version { found: u16, expected: u16 },
+  Truncated,
+  Mismatch(&'static str),
+}
+
+impl fmt::Display for StateError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      StateError::Magic => write!(f, "Data is not a NeoNES save state."),
+      StateError::Version { found, expected } => {
+        write!(f, "Save state version {found} is not supported (expected {expected}).")
+      }
+      StateError::Truncated => write!(f, "Save state ended unexpectedly."),
+      StateError::Mismatch(what) => write!(f, "Save state does not match the loaded cartridge ({what})."),
+    }
+  }
+}
+
+impl std::error::Error for StateError { }
+
+#[derive(Default)]
+pub struct Writer {
+  data: Vec<u8>,
+}
+
+pub struct Reader<'a> {
+  data: &'a [u8],
+  position: usize,
+}
+
+impl Writer {
+  pub fn new() -> Self {
+    Writer {
+      data: vec![],
+    }
+  }
+
+  pub fn u8(&mut self, val: u8) {
+    self.data.push(val);
+  }
+
+  pub fn bool(&mut self, val: bool) {
+    self.u8(val as u8);
+  }
+
+  pub fn u16(&mut self, val: u16) {
+    self.data.extend_from_slice(&val.to_le_bytes());
+  }
+
+  pub fn u32(&mut self, val: u32) {
+    self.data.extend_from_slice(&val.to_le_bytes());
+  }
+
+  pub fn u64(&mut self, val: u64) {
+    self.data.extend_from_slice(&val.to_le_bytes());
+  }
+
+  pub fn usize(&mut self, val: usize) {
+    self.u64(val as u64);
+  }
+
+  pub fn f32(&mut self, val: f32) {
+    self.u32(val.to_bits());
+  }
+
+  pub fn bytes(&mut self, val: &[u8]) {
+    self.usize(val.len());
+    self.data.extend_from_slice(val);
+  }
+
+  pub fn finish(self) -> Vec<u8> {
+    self.data
+  }
+}
+
+impl<'a> Reader<'a> {
+  pub fn new(data: &'a [u8]) -> Self {
+    Reader {
+      data,
+      position: 0,
+    }
+  }
+
+  fn take<const N: usize>(&mut self) -> Result<[u8; N], StateError> {
+    let end = self.position + N;
+    let bytes = self.data.get(self.position..end).ok_or(StateError::Truncated)?;
+    self.position = end;
+
+    let mut res = [0; N];
+    res.copy_from_slice(bytes);
+    Ok(res)
+  }
+
+  pub fn u8(&mut self) -> Result<u8, StateError> {
+    Ok(self.take::<1>()?[0])
+  }
+
+  pub fn bool(&mut self) -> Result<bool, StateError> {
+    Ok(self.u8()? != 0)
+  }
+
+  pub fn u16(&mut self) -> Result<u16, StateError> {
+    Ok(u16::from_le_bytes(self.take()?))
+  }
+
+  pub fn u32(&mut self) -> Result<u32, StateError> {
+    Ok(u32::from_le_bytes(self.take()?))
+  }
+
+  pub fn u64(&mut self) -> Result<u64, StateError> {
+    Ok(u64::from_le_bytes(self.take()?))
+  }
+
+  pub fn usize(&mut self) -> Result<usize, StateError> {
+    usize::try_from(self.u64()?).map_err(|_| StateError::Mismatch("address width"))
+  }
+
+  pub fn f32(&mut self) -> Result<f32, StateError> {
+    Ok(f32::from_bits(self.u32()?))
+  }
+
+  pub fn bytes(&mut self) -> Result<&'a [u8], StateError> {
+    let len = self.usize()?;
+    let end = self.position.checked_add(len).ok_or(StateError::Truncated)?;
+    let bytes = self.data.get(self.position..end).ok_or(StateError::Truncated)?;
+    self.position = end;
+    Ok(bytes)
+  }
+
+  pub fn bytes_into(&mut self, dest: &mut [u8], what: &'static str) -> Result<(), StateError> {
+    let bytes = self.bytes()?;
+
+    if bytes.len() != dest.len() {
+      return Err(StateError::Mismatch(what));
+    }
+
+    dest.copy_from_slice(bytes);
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{Reader, StateError, Writer};
+
+  #[test]
+  fn values_round_trip() {
+    let mut state = Writer::new();
+    state.u8(0xA5);
+    state.bool(true);
+    state.u16(0xBEEF);
+    state.u32(0xDEADBEEF);
+    state.u64(u64::MAX - 1);
+    state.usize(0x1234);
+    state.f32(-0.25);
+    state.bytes(&[1, 2, 3]);
+
+    let data = state.finish();
+    let mut state = Reader::new(&data);
+    assert_eq!(state.u8(), Ok(0xA5));
+    assert_eq!(state.bool(), Ok(true));
+    assert_eq!(state.u16(), Ok(0xBEEF));
+    assert_eq!(state.u32(), Ok(0xDEADBEEF));
+    assert_eq!(state.u64(), Ok(u64::MAX - 1));
+    assert_eq!(state.usize(), Ok(0x1234));
+    assert_eq!(state.f32(), Ok(-0.25));
+    assert_eq!(state.bytes(), Ok([1, 2, 3].as_slice()));
+    assert_eq!(state.u8(), Err(StateError::Truncated));
+  }
+
+  #[test]
+  fn short_data_is_truncated() {
+    let mut state = Reader::new(&[0x01, 0x02, 0x03]);
+    assert_eq!(state.u32(), Err(StateError::Truncated));
+
+    let mut state = Writer::new();
+    state.u64(u64::MAX);
+    let data = state.finish();
+    assert_eq!(Reader::new(&data).bytes(), Err(StateError::Truncated));
+  }
+
+  #[test]
+  fn bytes_into_checks_the_length() {
+    let mut state = Writer::new();
+    state.bytes(&[0xFF; 4]);
+    let data = state.finish();
+
+    let mut ram = [0x00; 8];
+    assert_eq!(Reader::new(&data).bytes_into(&mut ram, "RAM"), Err(StateError::Mismatch("RAM")));
+    assert_eq!(ram, [0x00; 8]);
+
+    let mut ram = [0x00; 4];
+    assert_eq!(Reader::new(&data).bytes_into(&mut ram, "RAM"), Ok(()));
+    assert_eq!(ram, [0xFF; 4]);
+  }
+}
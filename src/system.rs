@@ -1,6 +1,6 @@
-pub(crate) mod cartridge;
+pub mod cartridge;
 pub mod joypad;
-pub(crate) mod mapper;
+pub mod mapper;
 mod memory;
 
 use std::cell::RefCell;
@@ -10,7 +10,8 @@ use crate::apu::mixer::NESAudioCallback;
 use crate::apu::APU;
 use crate::ppu::PPU;
 use crate::renderer::Renderer;
-use cartridge::Cartridge;
+use crate::savestate::{Reader, Snapshot, StateError, Writer};
+use cartridge::{Cartridge, RomHeader};
 use joypad::Joypad;
 use memory::Memory;
 
@@ -18,8 +19,10 @@ pub struct System {
   pub apu: APU,
   pub ppu: PPU,
   pub joypads: (Joypad, Joypad),
-  pub renderer: Rc<RefCell<dyn Renderer>>,
-  cycles: usize,
+  pub header: RomHeader,
+  pub renderer: Option<Rc<RefCell<dyn Renderer>>>,
+  pub(crate) cycles: usize,
+  bus: u8,
   memory: Memory,
 }
 
@@ -38,13 +41,15 @@ impl System {
   pub const JOYPAD1: u16 = 0x4016;
   pub const JOYPAD2: u16 = 0x4017;
 
-  pub fn new(cartridge: Cartridge, renderer: Rc<RefCell<dyn Renderer>>) -> Self {
+  pub fn new(cartridge: Cartridge, renderer: Option<Rc<RefCell<dyn Renderer>>>) -> Self {
     System {
       apu: APU::new(),
       ppu: PPU::new(cartridge.mapper),
       joypads: (Joypad::new(), Joypad::new()),
+      header: cartridge.header,
       renderer,
       cycles: 0,
+      bus: 0,
       memory: Memory::new(),
     }
   }
@@ -54,27 +59,38 @@ impl System {
   }
 
   pub fn read(&mut self, addr: u16) -> u8 {
-    match addr {
+    let data = match addr {
       System::RAM..=System::RAM_END => self.memory.read(addr),
       System::PPU..=System::PPU_END => self.ppu.read(addr),
-      System::EROM..=System::EROM_END => 0,
-      System::SRAM..=System::SRAM_END => self.ppu.mapper.read(addr),
-      System::ROM..=System::ROM_END => self.ppu.mapper.read(addr),
-      System::JOYPAD1 => self.joypads.0.read(),
-      System::JOYPAD2 => self.joypads.1.read(),
-      0x4000..=0x4013 | 0x4015 => self.apu.read(addr),
-      _ => {
-        println!("Ignoring read: {:#0X}", addr);
-        0
-      }
+      System::EROM..=System::EROM_END => self.ppu.mapper.read_expansion(addr).unwrap_or(self.bus),
+      System::SRAM..=System::ROM_END => self.ppu.mapper.read(addr).unwrap_or(self.bus),
+      System::JOYPAD1 => (self.bus & 0xE0) | self.joypads.0.read(),
+      System::JOYPAD2 => (self.bus & 0xE0) | self.joypads.1.read(),
+      // The status register is internal to the CPU and does not drive the bus
+      0x4015 => return (self.bus & 0x20) | self.apu.read(addr),
+      _ => self.bus,
+    };
+
+    self.bus = data;
+    data
+  }
+
+  // Reads without side effects, leaving registers that react to reads alone
+  pub fn peek(&self, addr: u16) -> u8 {
+    match addr {
+      System::RAM..=System::RAM_END => self.memory.read(addr),
+      System::SRAM..=System::ROM_END => self.ppu.mapper.read(addr).unwrap_or(self.bus),
+      _ => self.bus,
     }
   }
 
   pub fn write(&mut self, addr: u16, data: u8) {
+    self.bus = data;
+
     match addr {
       System::RAM..=System::RAM_END => self.memory.write(addr, data),
       System::PPU..=System::PPU_END => self.ppu.write(addr, data),
-      System::EROM..=System::EROM_END => { },
+      System::EROM..=System::EROM_END => self.ppu.mapper.write(addr, data),
       System::SRAM..=System::SRAM_END => self.ppu.mapper.write(addr, data),
       System::ROM..=System::ROM_END => self.ppu.mapper.write(addr, data),
       System::OAM_REQ => self.oamdma(data),
@@ -105,7 +121,8 @@ impl System {
     }
 
     for _ in 0 .. cycles {
-      self.apu.tick();
+      self.ppu.mapper.tick();
+      self.apu.tick(self.ppu.mapper.signal());
 
       if self.apu.dma() {
         self.dmcdma();
@@ -114,30 +131,69 @@ impl System {
 
     if render {
       self.apu.mix();
-      self.renderer.borrow_mut().render(&self.ppu.frame.data, &mut self.joypads.0)
+
+      if let Some(renderer) = &self.renderer {
+        renderer.borrow_mut().render(&self.ppu.frame.data, &mut self.joypads.0)
+      }
     }
   }
 
+  // The CPU halts for a cycle, plus one more to line up with a read cycle,
+  // before the DMA alternates between reading a byte and writing it to OAM.
   fn oamdma(&mut self, data: u8) {
     let hi: u16 = (data as u16) << 8;
+    self.tick(if self.cycles % 2 == 1 { 1 } else { 2 });
+
     for lo in 0x0..0x100 {
       let val = self.read(hi | lo);
+      self.tick(1);
       self.ppu.write(0x2004, val);
+      self.tick(1);
     }
-    self.tick(if self.cycles % 2 == 0 { 513 } else { 514 })
   }
 
+  // Fetching a sample stalls the CPU for four cycles, the last of which is
+  // the read itself.
   fn dmcdma(&mut self) {
     let addr = self.apu.dma_addr();
+    self.tick(3);
+
     let val = self.read(addr);
     self.apu.dmcdma(val);
+    self.tick(1);
   }
 
-  pub fn poll_nmi(&mut self) -> bool {
-    self.ppu.poll()
+  pub fn nmi_line(&self) -> bool {
+    self.ppu.nmi_line()
   }
 
-  pub fn poll_irq(&mut self) -> bool {
+  pub fn nmi_cancelled(&mut self) -> bool {
+    self.ppu.nmi_cancelled()
+  }
+
+  pub fn irq_line(&self) -> bool {
     self.apu.poll() || self.ppu.mapper.poll()
   }
 }
+
+impl Snapshot for System {
+  fn save(&self, state: &mut Writer) {
+    state.usize(self.cycles);
+    state.u8(self.bus);
+    self.memory.save(state);
+    self.joypads.0.save(state);
+    self.joypads.1.save(state);
+    self.ppu.save(state);
+    self.apu.save(state);
+  }
+
+  fn load(&mut self, state: &mut Reader) -> Result<(), StateError> {
+    self.cycles = state.usize()?;
+    self.bus = state.u8()?;
+    self.memory.load(state)?;
+    self.joypads.0.load(state)?;
+    self.joypads.1.load(state)?;
+    self.ppu.load(state)?;
+    self.apu.load(state)
+  }
+}
@@ -1,43 +1,842 @@
-use super::mapper::{Mapper, Mirroring, from};
+use std::time::Duration;
+
+use crate::error::NeoNESError;
+
+use super::mapper::{Mapper, Mirroring, fds, from, nsf};
 
 
 pub struct Cartridge {
+  pub header: RomHeader,
   pub mapper: Box<dyn Mapper>,
 }
 
-impl Cartridge {
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+  INES, NES2, UNIF,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Timing {
+  NTSC, PAL, Multi, Dendy,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Console {
+  NES,
+  VsSystem { ppu: u8, hardware: u8 },
+  Playchoice,
+  Extended(u8),
+}
+
+#[derive(Clone, Debug)]
+pub struct RomHeader {
+  pub format: Format,
+  pub mapper: u16,
+  pub submapper: u8,
+  pub mirroring: Mirroring,
+  pub battery: bool,
+  pub trainer: bool,
+  pub prg_rom_size: usize,
+  pub chr_rom_size: usize,
+  pub prg_ram_size: usize,
+  pub prg_nvram_size: usize,
+  pub chr_ram_size: usize,
+  pub chr_nvram_size: usize,
+  pub timing: Timing,
+  pub console: Console,
+  pub misc_roms: u8,
+  pub expansion: u8,
+}
+
+#[derive(Clone, Debug)]
+pub struct NsfHeader {
+  pub title: String,
+  pub artist: String,
+  pub copyright: String,
+  pub ripper: String,
+  pub songs: u8,
+  pub start: u8,
+  pub load: u16,
+  pub init: u16,
+  pub play: u16,
+  pub banks: [u8; 8],
+  pub ntsc_speed: u16,
+  pub pal_speed: u16,
+  pub timing: Timing,
+  pub chips: u8,
+  pub tracks: Vec<NsfTrack>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct NsfTrack {
+  pub label: Option<String>,
+  pub length: Option<Duration>,
+  pub fade: Option<Duration>,
+}
+
+impl RomHeader {
   const NES_TAG: [u8; 4] = [0x4E, 0x45, 0x53, 0x1A];
+  const UNIF_TAG: [u8; 4] = [0x55, 0x4E, 0x49, 0x46];
+  pub const SIZE: usize = 16;
+  const UNIF_SIZE: usize = 32;
+
+  pub fn new(header: &[u8]) -> Result<RomHeader, NeoNESError> {
+    if header.len() < RomHeader::SIZE {
+      return Err(NeoNESError::TruncatedRom { expected: RomHeader::SIZE, actual: header.len() });
+    }
+
+    if header[0..4] != RomHeader::NES_TAG {
+      return Err(NeoNESError::BadMagic);
+    }
+
+    match header[7] & 0x0C {
+      0x08 => Ok(RomHeader::nes2(header)),
+      _ => Ok(RomHeader::ines(header)),
+    }
+  }
+
+  // The RAM adapter has no header of its own, so describe it the way an NES 2.0
+  // header would: the BIOS as PRG ROM alongside 32K of PRG RAM and 8K of CHR RAM.
+  fn fds(bios_size: usize) -> RomHeader {
+    RomHeader {
+      format: Format::INES,
+      mapper: 20,
+      submapper: 0,
+      mirroring: Mirroring::Horizontal,
+      battery: true,
+      trainer: false,
+      prg_rom_size: bios_size,
+      chr_rom_size: 0,
+      prg_ram_size: 0x8000,
+      prg_nvram_size: 0,
+      chr_ram_size: 0x2000,
+      chr_nvram_size: 0,
+      timing: Timing::NTSC,
+      console: Console::NES,
+      misc_roms: 0,
+      expansion: 0,
+    }
+  }
+
+  // NSF rips run on mapper 31's layout of 4K PRG banks, with 8K of PRG RAM
+  // and CHR RAM that nothing ever draws from.
+  fn nsf(tune: &NsfHeader, prg_rom_size: usize) -> RomHeader {
+    RomHeader {
+      format: Format::INES,
+      mapper: 31,
+      submapper: 0,
+      mirroring: Mirroring::Vertical,
+      battery: false,
+      trainer: false,
+      prg_rom_size,
+      chr_rom_size: 0,
+      prg_ram_size: 0x2000,
+      prg_nvram_size: 0,
+      chr_ram_size: 0x2000,
+      chr_nvram_size: 0,
+      timing: tune.timing,
+      console: Console::NES,
+      misc_roms: 0,
+      expansion: 0,
+    }
+  }
+
+  // UNIF files are a list of chunks, naming the board rather than numbering
+  // the mapper. ROM is split across PRG0-PRGF and CHR0-CHRF, which are joined
+  // in numeric order whatever order they appear in.
+  fn unif(file: &[u8]) -> Result<(RomHeader, Vec<u8>, Vec<u8>), NeoNESError> {
+    if file.len() < RomHeader::UNIF_SIZE {
+      return Err(NeoNESError::TruncatedRom { expected: RomHeader::UNIF_SIZE, actual: file.len() });
+    }
+
+    let mut board = None;
+    let mut prg: [Vec<u8>; 16] = Default::default();
+    let mut chr: [Vec<u8>; 16] = Default::default();
+    let mut mirroring = Mirroring::Horizontal;
+    let mut battery = false;
+    let mut timing = Timing::NTSC;
+    let mut position = RomHeader::UNIF_SIZE;
+
+    while let Some(chunk) = file.get(position .. position + 8) {
+      let id = &chunk[0 .. 4];
+      let size = u32::from_le_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]) as usize;
+      let end = position.checked_add(8).and_then(|start| start.checked_add(size));
+      let body = end.and_then(|end| file.get(position + 8 .. end)).ok_or(NeoNESError::BadUnif("truncated chunk"))?;
+      position += 8 + size;
+
+      match id {
+        b"MAPR" => {
+          let end = body.iter().position(|byte| *byte == 0).unwrap_or(body.len());
+          board = Some(String::from_utf8_lossy(&body[.. end]).trim().to_string());
+        }
+        b"MIRR" => {
+          mirroring = match body.first() {
+            Some(0x01) => Mirroring::Vertical,
+            Some(0x02) => Mirroring::Single0,
+            Some(0x03) => Mirroring::Single1,
+            Some(0x04) => Mirroring::FourScreen,
+            _ => Mirroring::Horizontal,
+          };
+        }
+        b"BATR" => battery = body.first().is_none_or(|flag| *flag != 0),
+        b"TVCI" => {
+          timing = match body.first() {
+            Some(0x01) => Timing::PAL,
+            Some(0x02) => Timing::Multi,
+            _ => Timing::NTSC,
+          };
+        }
+        _ => match (&id[0 .. 3], (id[3] as char).to_digit(16)) {
+          (b"PRG", Some(index)) => prg[index as usize] = body.to_vec(),
+          (b"CHR", Some(index)) => chr[index as usize] = body.to_vec(),
+          _ => { },
+        },
+      }
+    }
+
+    let board = board.ok_or(NeoNESError::BadUnif("missing MAPR chunk"))?;
+    let (mapper, submapper, prg_ram) = RomHeader::board(&board).ok_or(NeoNESError::UnsupportedBoard(board))?;
+
+    let prg_rom = prg.concat();
+    let chr_rom = chr.concat();
+
+    if prg_rom.is_empty() {
+      return Err(NeoNESError::BadUnif("missing PRG chunks"));
+    }
+
+    let header = RomHeader {
+      format: Format::UNIF,
+      mapper,
+      submapper,
+      mirroring,
+      battery,
+      trainer: false,
+      prg_rom_size: prg_rom.len(),
+      chr_rom_size: chr_rom.len(),
+      prg_ram_size: if battery { 0 } else { prg_ram },
+      prg_nvram_size: if battery { prg_ram } else { 0 },
+      chr_ram_size: if chr_rom.is_empty() { 0x2000 } else { 0 },
+      chr_nvram_size: 0,
+      timing,
+      console: Console::NES,
+      misc_roms: 0,
+      expansion: 0,
+    };
+
+    Ok((header, prg_rom, chr_rom))
+  }
+
+  // Maps a UNIF board name onto its mapper, submapper and PRG RAM size. The
+  // NES- and HVC- prefixes only tell the NES and Famicom releases apart.
+  fn board(name: &str) -> Option<(u16, u8, usize)> {
+    let name = name.strip_prefix("NES-").or_else(|| name.strip_prefix("HVC-")).unwrap_or(name);
+
+    Some(match name {
+      "NROM" | "NROM-128" | "NROM-256" | "RROM" | "RROM-128" => (0, 0, 0x2000),
+      "SEROM" | "SHROM" | "SH1ROM" => (1, 5, 0),
+      "SAROM" | "SBROM" | "SCROM" | "SC1ROM" | "SFROM" | "SGROM" | "SJROM" | "SKROM"
+        | "SLROM" | "SL1ROM" | "SL2ROM" | "SL3ROM" | "SLRROM" | "SNROM" | "SUROM" => (1, 0, 0x2000),
+      "SOROM" => (1, 0, 0x4000),
+      "SXROM" => (1, 0, 0x8000),
+      "UNROM" | "UOROM" => (2, 0, 0),
+      "CNROM" => (3, 0, 0),
+      "TBROM" | "TEROM" | "TFROM" | "TGROM" | "TKROM" | "TLROM" | "TL1ROM" | "TLBROM"
+        | "TNROM" | "TR1ROM" | "TSROM" | "TVROM" | "B4" => (4, 0, 0x2000),
+      "HKROM" => (4, 1, 0x400),
+      "TKSROM" | "TLSROM" => (118, 0, 0x2000),
+      "TQROM" => (119, 0, 0),
+      "ELROM" => (5, 0, 0),
+      "EKROM" => (5, 0, 0x2000),
+      "ETROM" => (5, 0, 0x4000),
+      "EWROM" => (5, 0, 0x8000),
+      "AMROM" | "ANROM" | "AN1ROM" | "AOROM" => (7, 0, 0),
+      "PNROM" | "PEEOROM" => (9, 0, 0),
+      "FJROM" | "FKROM" => (10, 0, 0x2000),
+      "GNROM" | "MHROM" => (66, 0, 0),
+      "JLROM" => (69, 0, 0),
+      "JSROM" => (69, 0, 0x2000),
+      _ => return None,
+    })
+  }
+
+  fn ines(header: &[u8]) -> RomHeader {
+    let flags_6 = header[6];
+    let battery = flags_6 & 0x02 == 0x02;
+
+    // Old dumping tools left their signature in bytes 7-15, in which case the
+    // upper mapper nibble and the PRG-RAM size are garbage.
+    let archaic = header[12..16].iter().any(|byte| *byte != 0);
+    let flags_7 = if archaic { 0x0 } else { header[7] };
+    let prg_ram_banks = if archaic { 0x0 } else { header[8] };
+
+    let prg_ram = std::cmp::max(1, prg_ram_banks as usize) * 0x2000;
+    let chr_rom_size = header[5] as usize * 0x2000;
+
+    RomHeader {
+      format: Format::INES,
+      mapper: ((flags_7 & 0xF0) | (flags_6 >> 4)) as u16,
+      submapper: 0,
+      mirroring: RomHeader::mirroring(flags_6),
+      battery,
+      trainer: flags_6 & 0x04 == 0x04,
+      prg_rom_size: header[4] as usize * 0x4000,
+      chr_rom_size,
+      prg_ram_size: if battery { 0 } else { prg_ram },
+      prg_nvram_size: if battery { prg_ram } else { 0 },
+      chr_ram_size: if chr_rom_size == 0 { 0x2000 } else { 0 },
+      chr_nvram_size: 0,
+      timing: if !archaic && header[9] & 0x01 == 0x01 { Timing::PAL } else { Timing::NTSC },
+      console: match flags_7 & 0x03 {
+        0x01 => Console::VsSystem { ppu: 0, hardware: 0 },
+        0x02 => Console::Playchoice,
+        _ => Console::NES,
+      },
+      misc_roms: 0,
+      expansion: 0,
+    }
+  }
 
-  pub fn new(rom: Vec<u8>) -> Result<Cartridge, &'static str> {
-    let header = &rom[0..16];
+  fn nes2(header: &[u8]) -> RomHeader {
     let flags_6 = header[6];
     let flags_7 = header[7];
 
-    if header[0..4] != Cartridge::NES_TAG {
-      return Err("File not in iNES format.");
-    } else if flags_7 & 0x0C == 0x08 {
-      println!("NES 2.0 format not supported (yet).");
+    RomHeader {
+      format: Format::NES2,
+      mapper: (((header[8] & 0x0F) as u16) << 8) | (flags_7 & 0xF0) as u16 | (flags_6 >> 4) as u16,
+      submapper: header[8] >> 4,
+      mirroring: RomHeader::mirroring(flags_6),
+      battery: flags_6 & 0x02 == 0x02,
+      trainer: flags_6 & 0x04 == 0x04,
+      prg_rom_size: RomHeader::rom_size(header[4], header[9] & 0x0F, 0x4000),
+      chr_rom_size: RomHeader::rom_size(header[5], header[9] >> 4, 0x2000),
+      prg_ram_size: RomHeader::ram_size(header[10] & 0x0F),
+      prg_nvram_size: RomHeader::ram_size(header[10] >> 4),
+      chr_ram_size: RomHeader::ram_size(header[11] & 0x0F),
+      chr_nvram_size: RomHeader::ram_size(header[11] >> 4),
+      timing: match header[12] & 0x03 {
+        0 => Timing::NTSC,
+        1 => Timing::PAL,
+        2 => Timing::Multi,
+        _ => Timing::Dendy,
+      },
+      console: match flags_7 & 0x03 {
+        0 => Console::NES,
+        1 => Console::VsSystem { ppu: header[13] & 0x0F, hardware: header[13] >> 4 },
+        2 => Console::Playchoice,
+        _ => Console::Extended(header[13] & 0x0F),
+      },
+      misc_roms: header[14] & 0x03,
+      expansion: header[15] & 0x3F,
+    }
+  }
+
+  fn mirroring(flags_6: u8) -> Mirroring {
+    match (flags_6 & 0x08 == 0x08, flags_6 & 0x01 == 0x01) {
+      (true, _) => Mirroring::FourScreen,
+      (false, true) => Mirroring::Vertical,
+      (false, false) => Mirroring::Horizontal,
+    }
+  }
+
+  fn rom_size(lsb: u8, msb: u8, unit: usize) -> usize {
+    if msb == 0x0F {
+      // Exponent-multiplier notation: EEEEEEMM -> 2^E * (MM * 2 + 1)
+      let exponent = (lsb >> 2) as u32;
+      let multiplier = ((lsb & 0x03) as usize) * 2 + 1;
+      // Sizes past what can be addressed could never be satisfied by the file
+      1usize.checked_shl(exponent).and_then(|size| size.checked_mul(multiplier)).unwrap_or(usize::MAX)
+    } else {
+      (((msb as usize) << 8) | lsb as usize) * unit
     }
+  }
 
-    let header_bytes = 16;
-    let trainer_bytes = if flags_6 & 0x04 == 0x04 { 512 } else { 0 };
-    let prg_bytes = header[4] as usize * 0x4000;
-    let chr_bytes = header[5] as usize * 0x2000;
+  fn ram_size(shift: u8) -> usize {
+    if shift == 0 { 0 } else { 64 << shift }
+  }
 
-    let prg_start = header_bytes + trainer_bytes;
-    let chr_start = header_bytes + trainer_bytes + prg_bytes;
+  pub fn prg_ram(&self) -> usize {
+    self.prg_ram_size + self.prg_nvram_size
+  }
 
-    Ok(Cartridge {
-      mapper: from(
-        (flags_7 & 0xF0) | (flags_6 >> 4),
-        rom[chr_start..(chr_start + chr_bytes)].to_vec(),
-        rom[prg_start..(prg_start + prg_bytes)].to_vec(),
-        match (flags_6 & 0x08 == 0x08, flags_6 & 0x01 == 0x01) {
-          (true, _) => Mirroring::FourScreen,
-          (false, true) => Mirroring::Vertical,
-          (false, false) => Mirroring::Horizontal,
+  pub fn chr_ram(&self) -> usize {
+    match self.chr_ram_size + self.chr_nvram_size {
+      0 if self.chr_rom_size == 0 => 0x2000,
+      size => size,
+    }
+  }
+}
+
+impl NsfHeader {
+  const NSF_TAG: [u8; 5] = [0x4E, 0x45, 0x53, 0x4D, 0x1A];
+  const NSFE_TAG: [u8; 4] = [0x4E, 0x53, 0x46, 0x45];
+  pub const SIZE: usize = 0x80;
+
+  pub const VRC6: u8 = 0x01;
+  pub const VRC7: u8 = 0x02;
+  pub const FDS: u8 = 0x04;
+  pub const MMC5: u8 = 0x08;
+  pub const N163: u8 = 0x10;
+  pub const SUNSOFT5B: u8 = 0x20;
+
+  const NTSC_SPEED: u16 = 16639;
+  const PAL_SPEED: u16 = 19997;
+
+  // Returns the header along with the program data it describes
+  pub fn new(file: &[u8]) -> Result<(NsfHeader, Vec<u8>), NeoNESError> {
+    if file.starts_with(&NsfHeader::NSF_TAG) {
+      NsfHeader::nsf(file)
+    } else if file.starts_with(&NsfHeader::NSFE_TAG) {
+      NsfHeader::nsfe(file)
+    } else {
+      Err(NeoNESError::BadNsf("missing NESM or NSFE tag"))
+    }
+  }
+
+  pub fn bankswitched(&self) -> bool {
+    self.banks.iter().any(|bank| *bank != 0)
+  }
+
+  fn blank() -> NsfHeader {
+    NsfHeader {
+      title: String::new(),
+      artist: String::new(),
+      copyright: String::new(),
+      ripper: String::new(),
+      songs: 1,
+      start: 0,
+      load: 0x8000,
+      init: 0x8000,
+      play: 0x8000,
+      banks: [0; 8],
+      ntsc_speed: NsfHeader::NTSC_SPEED,
+      pal_speed: NsfHeader::PAL_SPEED,
+      timing: Timing::NTSC,
+      chips: 0,
+      tracks: Vec::new(),
+    }
+  }
+
+  fn nsf(file: &[u8]) -> Result<(NsfHeader, Vec<u8>), NeoNESError> {
+    if file.len() < NsfHeader::SIZE {
+      return Err(NeoNESError::TruncatedRom { expected: NsfHeader::SIZE, actual: file.len() });
+    }
+
+    let word = |at: usize| u16::from_le_bytes([file[at], file[at + 1]]);
+
+    let mut header = NsfHeader {
+      title: NsfHeader::text(&file[0x0E .. 0x2E]),
+      artist: NsfHeader::text(&file[0x2E .. 0x4E]),
+      copyright: NsfHeader::text(&file[0x4E .. 0x6E]),
+      songs: std::cmp::max(1, file[0x06]),
+      start: file[0x07].saturating_sub(1),
+      load: word(0x08),
+      init: word(0x0A),
+      play: word(0x0C),
+      ntsc_speed: NsfHeader::speed(word(0x6E), NsfHeader::NTSC_SPEED),
+      pal_speed: NsfHeader::speed(word(0x78), NsfHeader::PAL_SPEED),
+      timing: NsfHeader::timing(file[0x7A]),
+      chips: file[0x7B],
+      ..NsfHeader::blank()
+    };
+    header.banks.copy_from_slice(&file[0x70 .. 0x78]);
+    header.start = std::cmp::min(header.start, header.songs - 1);
+    header.tracks = vec![NsfTrack::default(); header.songs as usize];
+
+    // NSF2 can append metadata after the program, in which case its length is
+    // given up front.
+    let length = file[0x7D] as usize | (file[0x7E] as usize) << 8 | (file[0x7F] as usize) << 16;
+    let end = if file[0x05] >= 2 && length != 0 { std::cmp::min(file.len(), NsfHeader::SIZE + length) } else { file.len() };
+
+    Ok((header, file[NsfHeader::SIZE .. end].to_vec()))
+  }
+
+  fn nsfe(file: &[u8]) -> Result<(NsfHeader, Vec<u8>), NeoNESError> {
+    let mut header = NsfHeader::blank();
+    let mut data = None;
+    let mut info = false;
+    let mut labels = Vec::new();
+    let mut lengths = Vec::new();
+    let mut fades = Vec::new();
+    let mut position = NsfHeader::NSFE_TAG.len();
+
+    while let Some(chunk) = file.get(position .. position + 8) {
+      let size = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]) as usize;
+      let id = &chunk[4 .. 8];
+      let end = position.checked_add(8).and_then(|start| start.checked_add(size));
+      let body = end.and_then(|end| file.get(position + 8 .. end)).ok_or(NeoNESError::BadNsf("truncated chunk"))?;
+      let word = |at: usize| body.get(at .. at + 2).map(|word| u16::from_le_bytes([word[0], word[1]]));
+      position += 8 + size;
+
+      match id {
+        b"INFO" => {
+          if body.len() < 9 {
+            return Err(NeoNESError::BadNsf("INFO chunk too short"));
+          }
+
+          header.load = word(0).unwrap_or(0x8000);
+          header.init = word(2).unwrap_or(0x8000);
+          header.play = word(4).unwrap_or(0x8000);
+          header.timing = NsfHeader::timing(body[6]);
+          header.chips = body[7];
+          header.songs = std::cmp::max(1, body[8]);
+          header.start = body.get(9).copied().unwrap_or(0);
+          info = true;
+        }
+        b"DATA" => data = Some(body.to_vec()),
+        b"BANK" => {
+          let len = std::cmp::min(body.len(), 8);
+          header.banks[.. len].copy_from_slice(&body[.. len]);
+        }
+        b"RATE" => {
+          header.ntsc_speed = NsfHeader::speed(word(0).unwrap_or(0), NsfHeader::NTSC_SPEED);
+          header.pal_speed = NsfHeader::speed(word(2).unwrap_or(0), NsfHeader::PAL_SPEED);
         }
-      ),
+        b"auth" => {
+          let mut fields = body.split(|byte| *byte == 0).map(NsfHeader::text);
+          header.title = fields.next().unwrap_or_default();
+          header.artist = fields.next().unwrap_or_default();
+          header.copyright = fields.next().unwrap_or_default();
+          header.ripper = fields.next().unwrap_or_default();
+        }
+        b"tlbl" => labels = body.split(|byte| *byte == 0).map(NsfHeader::text).collect(),
+        b"time" => lengths = NsfHeader::times(body),
+        b"fade" => fades = NsfHeader::times(body),
+        b"NEND" => break,
+        // Chunks starting with a capital letter must be understood to play the file
+        _ if id[0].is_ascii_uppercase() => return Err(NeoNESError::BadNsf("unsupported NSFe chunk")),
+        _ => { },
+      }
+    }
+
+    let data = match (info, data) {
+      (true, Some(data)) => data,
+      _ => return Err(NeoNESError::BadNsf("missing INFO or DATA chunk")),
+    };
+
+    header.start = std::cmp::min(header.start, header.songs - 1);
+    header.tracks = (0 .. header.songs as usize).map(|track| NsfTrack {
+      label: labels.get(track).filter(|label| !label.is_empty()).cloned(),
+      length: lengths.get(track).copied().flatten(),
+      fade: fades.get(track).copied().flatten(),
+    }).collect();
+
+    Ok((header, data))
+  }
+
+  fn timing(flags: u8) -> Timing {
+    match flags & 0x03 {
+      0 => Timing::NTSC,
+      1 => Timing::PAL,
+      _ => Timing::Multi,
+    }
+  }
+
+  fn speed(speed: u16, default: u16) -> u16 {
+    if speed == 0 { default } else { speed }
+  }
+
+  // Text fields are NUL terminated, with "<?>" standing in for unknown values
+  fn text(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|byte| *byte == 0).unwrap_or(bytes.len());
+    let text = String::from_utf8_lossy(&bytes[.. end]).trim().to_string();
+
+    if text == "<?>" { String::new() } else { text }
+  }
+
+  // Negative times mark tracks of unknown length
+  fn times(body: &[u8]) -> Vec<Option<Duration>> {
+    body.chunks_exact(4).map(|time| {
+      let ms = i32::from_le_bytes([time[0], time[1], time[2], time[3]]);
+      (ms >= 0).then(|| Duration::from_millis(ms as u64))
+    }).collect()
+  }
+}
+
+impl Cartridge {
+  pub fn new(rom: Vec<u8>) -> Result<Cartridge, NeoNESError> {
+    if rom.starts_with(&RomHeader::UNIF_TAG) {
+      return Cartridge::unif(&rom);
+    }
+
+    let header = RomHeader::new(&rom)?;
+
+    let trainer_bytes = if header.trainer { 512 } else { 0 };
+
+    let prg_start = RomHeader::SIZE + trainer_bytes;
+    let chr_start = prg_start.checked_add(header.prg_rom_size);
+    let chr_end = chr_start.and_then(|start| start.checked_add(header.chr_rom_size));
+
+    let (Some(chr_start), Some(chr_end)) = (chr_start, chr_end) else {
+      return Err(NeoNESError::TruncatedRom { expected: usize::MAX, actual: rom.len() });
+    };
+
+    if rom.len() < chr_end {
+      return Err(NeoNESError::TruncatedRom { expected: chr_end, actual: rom.len() });
+    }
+
+    let mapper = from(
+      &header,
+      rom[chr_start..chr_end].to_vec(),
+      rom[prg_start..chr_start].to_vec(),
+    )?;
+
+    Ok(Cartridge {
+      header,
+      mapper,
+    })
+  }
+
+  fn unif(rom: &[u8]) -> Result<Cartridge, NeoNESError> {
+    let (header, prg_rom, chr_rom) = RomHeader::unif(rom)?;
+    let mapper = from(&header, chr_rom, prg_rom)?;
+
+    Ok(Cartridge {
+      header,
+      mapper,
     })
   }
+
+  pub fn fds(image: Vec<u8>, bios: Vec<u8>) -> Result<Cartridge, NeoNESError> {
+    let header = RomHeader::fds(bios.len());
+    let mapper = fds(&header, bios, &image)?;
+
+    Ok(Cartridge {
+      header,
+      mapper,
+    })
+  }
+
+  pub fn nsf(tune: &NsfHeader, data: Vec<u8>) -> Cartridge {
+    let header = RomHeader::nsf(tune, data.len());
+    let mapper = nsf(&header, tune, data);
+
+    Cartridge {
+      header,
+      mapper,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::time::Duration;
+
+  use super::{Console, Format, NsfHeader, RomHeader, Timing};
+  use crate::{error::NeoNESError, system::mapper::Mirroring};
+
+  fn nsfe(chunks: &[(&[u8; 4], &[u8])]) -> Vec<u8> {
+    let mut file = b"NSFE".to_vec();
+
+    for (id, body) in chunks {
+      file.extend_from_slice(&(body.len() as u32).to_le_bytes());
+      file.extend_from_slice(*id);
+      file.extend_from_slice(body);
+    }
+
+    file
+  }
+
+  fn unif(chunks: &[(&[u8; 4], &[u8])]) -> Vec<u8> {
+    let mut file = [b"UNIF".as_slice(), &7u32.to_le_bytes(), &[0x00; 0x18]].concat();
+
+    for (id, body) in chunks {
+      file.extend_from_slice(*id);
+      file.extend_from_slice(&(body.len() as u32).to_le_bytes());
+      file.extend_from_slice(body);
+    }
+
+    file
+  }
+
+  #[test]
+  fn ines_header() {
+    let header = RomHeader::new(b"NES\x1A\x08\x10\x43\x41\x02\x01\x00\x00\x00\x00\x00\x00").unwrap();
+
+    assert_eq!(header.format, Format::INES);
+    assert_eq!(header.mapper, 0x44);
+    assert_eq!(header.mirroring, Mirroring::Vertical);
+    assert!(header.battery);
+    assert!(!header.trainer);
+    assert_eq!(header.prg_rom_size, 0x20000);
+    assert_eq!(header.chr_rom_size, 0x20000);
+    assert_eq!((header.prg_ram_size, header.prg_nvram_size), (0, 0x4000));
+    assert_eq!(header.chr_ram(), 0);
+    assert_eq!(header.timing, Timing::PAL);
+    assert_eq!(header.console, Console::VsSystem { ppu: 0, hardware: 0 });
+  }
+
+  #[test]
+  fn ines_header_without_chr_rom() {
+    let header = RomHeader::new(b"NES\x1A\x02\x00\x0C\x00\x00\x00\x00\x00\x00\x00\x00\x00").unwrap();
+
+    assert_eq!(header.mapper, 0);
+    assert_eq!(header.mirroring, Mirroring::FourScreen);
+    assert!(header.trainer);
+    assert_eq!((header.prg_ram_size, header.prg_nvram_size), (0x2000, 0));
+    assert_eq!(header.chr_ram(), 0x2000);
+  }
+
+  #[test]
+  fn archaic_ines_header() {
+    let header = RomHeader::new(b"NES\x1A\x02\x01\x10DiskDude!").unwrap();
+
+    assert_eq!(header.format, Format::INES);
+    assert_eq!(header.mapper, 1);
+    assert_eq!(header.prg_ram(), 0x2000);
+    assert_eq!(header.timing, Timing::NTSC);
+    assert_eq!(header.console, Console::NES);
+  }
+
+  #[test]
+  fn nes2_header() {
+    let header = RomHeader::new(b"NES\x1A\x02\x01\x52\x19\x31\x21\x70\x07\x01\x00\x01\x02").unwrap();
+
+    assert_eq!(header.format, Format::NES2);
+    assert_eq!((header.mapper, header.submapper), (0x115, 3));
+    assert_eq!(header.mirroring, Mirroring::Horizontal);
+    assert!(header.battery);
+    assert_eq!(header.prg_rom_size, 0x102 * 0x4000);
+    assert_eq!(header.chr_rom_size, 0x201 * 0x2000);
+    assert_eq!((header.prg_ram_size, header.prg_nvram_size), (0, 0x2000));
+    assert_eq!((header.chr_ram_size, header.chr_nvram_size), (0x2000, 0));
+    assert_eq!(header.timing, Timing::PAL);
+    assert_eq!(header.console, Console::VsSystem { ppu: 0, hardware: 0 });
+    assert_eq!((header.misc_roms, header.expansion), (1, 2));
+  }
+
+  #[test]
+  fn nes2_exponent_sizes() {
+    // 2^4 * 3 bytes of PRG ROM and 2^2 * 1 of CHR ROM
+    let header = RomHeader::new(b"NES\x1A\x11\x08\x00\x08\x00\xFF\x00\x00\x00\x00\x00\x00").unwrap();
+
+    assert_eq!(header.prg_rom_size, 48);
+    assert_eq!(header.chr_rom_size, 4);
+  }
+
+  #[test]
+  fn other_files_are_rejected() {
+    assert!(RomHeader::new(b"NES\x1A\x02\x01").is_err());
+    assert!(RomHeader::new(b"NESM\x1A\x01\x01\x01\x00\x80\x00\x80\x00\x80\x00\x00").is_err());
+  }
+
+  #[test]
+  fn nsf_header() {
+    let mut file = vec![0x00; NsfHeader::SIZE];
+    file[.. 0x10].copy_from_slice(b"NESM\x1A\x01\x03\x02\x00\x80\x03\x80\x06\x80Tu");
+    file[0x10 .. 0x13].copy_from_slice(b"ne\0");
+    file[0x2E .. 0x31].copy_from_slice(b"<?>");
+    file[0x70 .. 0x78].copy_from_slice(&[0, 1, 2, 3, 4, 5, 6, 7]);
+    file[0x7A] = 0x01;
+    file.extend_from_slice(&[0xEA; 0x10]);
+
+    let (header, data) = NsfHeader::new(&file).unwrap();
+
+    assert_eq!((header.title.as_str(), header.artist.as_str()), ("Tune", ""));
+    assert_eq!((header.songs, header.start), (3, 1));
+    assert_eq!((header.load, header.init, header.play), (0x8000, 0x8003, 0x8006));
+    assert!(header.bankswitched());
+    assert_eq!((header.ntsc_speed, header.pal_speed), (NsfHeader::NTSC_SPEED, NsfHeader::PAL_SPEED));
+    assert_eq!(header.timing, Timing::PAL);
+    assert_eq!(header.tracks.len(), 3);
+    assert_eq!(data, [0xEA; 0x10]);
+  }
+
+  #[test]
+  fn nsfe_chunks() {
+    let file = nsfe(&[
+      (b"INFO", &[0x00, 0x80, 0x03, 0x80, 0x06, 0x80, 0x00, 0x01, 0x02, 0x01]),
+      (b"DATA", &[0xEA; 0x10]),
+      (b"auth", b"Tune\0Artist\0<?>\0Ripper\0"),
+      (b"tlbl", b"Intro\0\0"),
+      (b"time", &[0x10, 0x27, 0x00, 0x00, 0xFF, 0xFF, 0xFF, 0xFF]),
+      (b"NEND", &[]),
+      (b"JUNK", &[]),
+    ]);
+    let (header, data) = NsfHeader::new(&file).unwrap();
+
+    assert_eq!((header.title.as_str(), header.artist.as_str()), ("Tune", "Artist"));
+    assert_eq!((header.copyright.as_str(), header.ripper.as_str()), ("", "Ripper"));
+    assert_eq!((header.songs, header.start, header.chips), (2, 1, 0x01));
+    assert_eq!((header.load, header.init, header.play), (0x8000, 0x8003, 0x8006));
+    assert!(!header.bankswitched());
+    assert_eq!(header.tracks[0].label.as_deref(), Some("Intro"));
+    assert_eq!(header.tracks[0].length, Some(Duration::from_secs(10)));
+    assert_eq!((header.tracks[1].label.as_deref(), header.tracks[1].length), (None, None));
+    assert_eq!(data, [0xEA; 0x10]);
+  }
+
+  #[test]
+  fn bad_nsfe_files() {
+    let info = [0x00, 0x80, 0x03, 0x80, 0x06, 0x80, 0x00, 0x00, 0x01].as_slice();
+
+    let missing_data = nsfe(&[(b"INFO", info)]);
+    let short_info = nsfe(&[(b"INFO", &info[.. 8]), (b"DATA", &[0xEA])]);
+    let unsupported = nsfe(&[(b"INFO", info), (b"DATA", &[0xEA]), (b"VRC7", &[])]);
+
+    let mut truncated = nsfe(&[(b"INFO", info), (b"DATA", &[0xEA])]);
+    truncated.pop();
+
+    let mut overflowing = nsfe(&[(b"INFO", info), (b"DATA", &[])]);
+    let size = overflowing.len() - 8;
+    overflowing[size .. size + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+
+    assert_eq!(NsfHeader::new(&missing_data).err(), Some(NeoNESError::BadNsf("missing INFO or DATA chunk")));
+    assert_eq!(NsfHeader::new(&short_info).err(), Some(NeoNESError::BadNsf("INFO chunk too short")));
+    assert_eq!(NsfHeader::new(&unsupported).err(), Some(NeoNESError::BadNsf("unsupported NSFe chunk")));
+    assert_eq!(NsfHeader::new(&truncated).err(), Some(NeoNESError::BadNsf("truncated chunk")));
+    assert_eq!(NsfHeader::new(&overflowing).err(), Some(NeoNESError::BadNsf("truncated chunk")));
+    assert!(NsfHeader::new(b"NESM\x1A").is_err());
+  }
+
+  #[test]
+  fn unif_board() {
+    let file = unif(&[
+      (b"MAPR", b"NES-SNROM\0"),
+      (b"PRG1", &[0x11; 0x4000]),
+      (b"PRG0", &[0x00; 0x4000]),
+      (b"MIRR", &[0x01]),
+      (b"BATR", &[0x01]),
+      (b"READ", b"Chunks the loader does not use are skipped\0"),
+    ]);
+    let (header, prg_rom, chr_rom) = RomHeader::unif(&file).unwrap();
+
+    assert_eq!(header.format, Format::UNIF);
+    assert_eq!((header.mapper, header.submapper), (1, 0));
+    assert_eq!(header.mirroring, Mirroring::Vertical);
+    assert!(header.battery);
+    assert_eq!((header.prg_ram_size, header.prg_nvram_size), (0, 0x2000));
+    assert_eq!(header.chr_ram(), 0x2000);
+    assert_eq!(header.timing, Timing::NTSC);
+    assert_eq!((prg_rom[0x3FFF], prg_rom[0x4000]), (0x00, 0x11));
+    assert!(chr_rom.is_empty());
+  }
+
+  #[test]
+  fn unif_board_names() {
+    assert_eq!(RomHeader::board("HVC-TLROM"), Some((4, 0, 0x2000)));
+    assert_eq!(RomHeader::board("UNROM"), Some((2, 0, 0)));
+    assert_eq!(RomHeader::board("NES-SOROM"), Some((1, 0, 0x4000)));
+    assert_eq!(RomHeader::board("BMC-Unknown"), None);
+  }
+
+  #[test]
+  fn bad_unif_files() {
+    let missing_board = unif(&[(b"PRG0", &[0x00; 0x4000])]);
+    let unknown_board = unif(&[(b"MAPR", b"BMC-Unknown\0"), (b"PRG0", &[0x00; 0x4000])]);
+    let missing_prg = unif(&[(b"MAPR", b"NES-NROM\0"), (b"CHR0", &[0x00; 0x2000])]);
+
+    let mut truncated = unif(&[(b"MAPR", b"NES-NROM\0"), (b"PRG0", &[0x00; 0x4000])]);
+    truncated.pop();
+
+    let mut overflowing = unif(&[(b"MAPR", b"NES-NROM\0"), (b"PRG0", &[])]);
+    let size = overflowing.len() - 4;
+    overflowing[size ..].copy_from_slice(&u32::MAX.to_le_bytes());
+
+    assert_eq!(RomHeader::unif(&missing_board).err(), Some(NeoNESError::BadUnif("missing MAPR chunk")));
+    assert_eq!(RomHeader::unif(&unknown_board).err(), Some(NeoNESError::UnsupportedBoard(String::from("BMC-Unknown"))));
+    assert_eq!(RomHeader::unif(&missing_prg).err(), Some(NeoNESError::BadUnif("missing PRG chunks")));
+    assert_eq!(RomHeader::unif(&truncated).err(), Some(NeoNESError::BadUnif("truncated chunk")));
+    assert_eq!(RomHeader::unif(&overflowing).err(), Some(NeoNESError::BadUnif("truncated chunk")));
+    assert!(RomHeader::unif(b"UNIF").is_err());
+  }
 }
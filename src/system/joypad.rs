@@ -1,3 +1,5 @@
+use crate::savestate::{Reader, Snapshot, StateError, Writer};
+
 crate::utils::bitflag!(pub JoypadStatus,
   A,
   B,
@@ -32,7 +34,7 @@ impl Joypad {
     let res = (self.buttons.get() & (1 << self.index)) >> self.index;
     self.index += !self.strobe as u8;
 
-    res | 0x40
+    res
   }
 
   pub fn write(&mut self, data: u8) {
@@ -51,3 +53,18 @@ impl Joypad {
     self.buttons.unset_flag(button);
   }
 }
+
+impl Snapshot for Joypad {
+  fn save(&self, state: &mut Writer) {
+    state.bool(self.strobe);
+    state.u8(self.index);
+    state.u8(self.buttons.get());
+  }
+
+  fn load(&mut self, state: &mut Reader) -> Result<(), StateError> {
+    self.strobe = state.bool()?;
+    self.index = state.u8()?;
+    self.buttons.set(state.u8()?);
+    Ok(())
+  }
+}
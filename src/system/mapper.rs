@@ -1,47 +1,150 @@
 mod banks;
+mod fds;
+mod fdsaudio;
+mod fdsdisk;
 mod mapper0;
 mod mapper1;
 mod mapper2;
 mod mapper3;
 mod mapper4;
+mod mapper5;
+mod mapper7;
+mod mapper9;
+mod mapper11;
+mod mapper19;
+mod mapper21;
+mod mapper24;
+mod mapper66;
+mod mapper69;
+mod mapper71;
+mod mapper79;
+mod mapper85;
+mod mapper140;
+mod mapper180;
+mod mapper232;
+mod namco163audio;
+mod nsf;
+mod sunsoft5b;
+mod vrc6audio;
+mod vrc7audio;
+mod vrcirq;
 
+use crate::error::NeoNESError;
+use crate::savestate::{Reader, Snapshot, StateError, Writer};
+
+use super::cartridge::{NsfHeader, RomHeader};
+
+use fds::FDS;
 use mapper0::Mapper0;
 use mapper1::Mapper1;
 use mapper2::Mapper2;
 use mapper3::Mapper3;
 use mapper4::Mapper4;
+use mapper5::Mapper5;
+use mapper7::Mapper7;
+use mapper9::Mapper9;
+use mapper11::Mapper11;
+use mapper19::Mapper19;
+use mapper21::Mapper21;
+use mapper24::Mapper24;
+use mapper66::Mapper66;
+use mapper69::Mapper69;
+use mapper71::Mapper71;
+use mapper79::Mapper79;
+use mapper85::Mapper85;
+use mapper140::Mapper140;
+use mapper180::Mapper180;
+use mapper232::Mapper232;
+use nsf::NSF;
 
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Mirroring {
   Vertical, Horizontal, FourScreen, Single0, Single1,
 }
 
 pub enum MapperEvent {
-  HBlank, VRAMAddressChanged(u16),
+  HBlank,
+  VRAMAddressChanged(u16),
+  PatternFetch(u16),
+  ControlChanged(u8),
+  MaskChanged(u8),
+  SpriteFetch(bool),
+  Scanline(u16),
 }
 
-pub fn from(mapper: u8, chr_rom: Vec<u8>, prg_rom: Vec<u8>, mirroring: Mirroring) -> Box<dyn Mapper> {
-  match mapper {
-    0 => Box::from(Mapper0::new(chr_rom, prg_rom, mirroring)),
-    1 => Box::from(Mapper1::new(chr_rom, prg_rom, mirroring)),
-    2 => Box::from(Mapper2::new(chr_rom, prg_rom, mirroring)),
-    3 => Box::from(Mapper3::new(chr_rom, prg_rom, mirroring)),
-    4 => Box::from(Mapper4::new(chr_rom, prg_rom, mirroring)),
-    _ => panic!("Unsupported mapper: {}", mapper),
-  }
+pub fn from(header: &RomHeader, chr_rom: Vec<u8>, prg_rom: Vec<u8>) -> Result<Box<dyn Mapper>, NeoNESError> {
+  Ok(match header.mapper {
+    0 => Box::from(Mapper0::new(header, chr_rom, prg_rom)),
+    1 => Box::from(Mapper1::new(header, chr_rom, prg_rom)),
+    2 => Box::from(Mapper2::new(header, chr_rom, prg_rom)),
+    3 => Box::from(Mapper3::new(header, chr_rom, prg_rom)),
+    4 | 118 | 119 => Box::from(Mapper4::new(header, chr_rom, prg_rom)),
+    5 => Box::from(Mapper5::new(header, chr_rom, prg_rom)),
+    7 => Box::from(Mapper7::new(header, chr_rom, prg_rom)),
+    9 | 10 => Box::from(Mapper9::new(header, chr_rom, prg_rom)),
+    11 => Box::from(Mapper11::new(header, chr_rom, prg_rom)),
+    19 => Box::from(Mapper19::new(header, chr_rom, prg_rom)),
+    21 | 22 | 23 | 25 => Box::from(Mapper21::new(header, chr_rom, prg_rom)),
+    24 | 26 => Box::from(Mapper24::new(header, chr_rom, prg_rom)),
+    66 => Box::from(Mapper66::new(header, chr_rom, prg_rom)),
+    69 => Box::from(Mapper69::new(header, chr_rom, prg_rom)),
+    71 => Box::from(Mapper71::new(header, chr_rom, prg_rom)),
+    79 => Box::from(Mapper79::new(header, chr_rom, prg_rom)),
+    85 => Box::from(Mapper85::new(header, chr_rom, prg_rom)),
+    140 => Box::from(Mapper140::new(header, chr_rom, prg_rom)),
+    180 => Box::from(Mapper180::new(header, chr_rom, prg_rom)),
+    232 => Box::from(Mapper232::new(header, chr_rom, prg_rom)),
+    mapper => return Err(NeoNESError::UnsupportedMapper(mapper)),
+  })
+}
+
+pub fn fds(header: &RomHeader, bios: Vec<u8>, image: &[u8]) -> Result<Box<dyn Mapper>, NeoNESError> {
+  Ok(Box::from(FDS::new(header, bios, image)?))
+}
+
+pub fn nsf(header: &RomHeader, tune: &NsfHeader, data: Vec<u8>) -> Box<dyn Mapper> {
+  Box::from(NSF::new(header, tune, data))
 }
 
-pub trait Mapper {
-  fn read(&self, addr: u16) -> u8;
+pub trait Mapper: Snapshot {
+  fn read(&self, addr: u16) -> Option<u8>;
 
   fn write(&mut self, addr: u16, val: u8);
 
   fn mirroring(&self) -> Mirroring;
 
+  fn read_expansion(&mut self, _: u16) -> Option<u8> { None }
+
+  fn read_nametable(&mut self, addr: u16, vram: &[u8]) -> u8 {
+    vram[self.mirroring().index(addr)]
+  }
+
+  fn write_nametable(&mut self, addr: u16, val: u8, vram: &mut [u8]) {
+    vram[self.mirroring().index(addr)] = val;
+  }
+
   fn notify(&mut self, _: MapperEvent) { }
 
   fn poll(&self) -> bool { false }
+
+  fn tick(&mut self) { }
+
+  fn signal(&self) -> f32 { 0.0 }
+
+  fn mute(&mut self, _: u8, _: bool) { }
+
+  fn battery_ram(&self) -> Option<&[u8]> { None }
+
+  fn load_battery_ram(&mut self, _: &[u8]) { }
+
+  fn disk_sides(&self) -> usize { 0 }
+
+  fn disk(&self) -> Option<usize> { None }
+
+  fn insert_disk(&mut self, _: Option<usize>) { }
+
+  fn select_track(&mut self, _: u8) { }
 }
 
 impl Mirroring {
@@ -54,4 +157,36 @@ impl Mirroring {
       Mirroring::Single1    => [1, 1, 1, 1],
     }
   }
+
+  pub fn index(&self, addr: u16) -> usize {
+    let addr = (addr - 0x2000) % 0x1000;
+    let table = addr / 0x400;
+    let offset = addr % 0x400;
+
+    ((self.coeff()[table as usize] * 0x400 + offset) % 0x800) as usize
+  }
+}
+
+impl Snapshot for Mirroring {
+  fn save(&self, state: &mut Writer) {
+    state.u8(match self {
+      Mirroring::Vertical   => 0,
+      Mirroring::Horizontal => 1,
+      Mirroring::FourScreen => 2,
+      Mirroring::Single0    => 3,
+      Mirroring::Single1    => 4,
+    });
+  }
+
+  fn load(&mut self, state: &mut Reader) -> Result<(), StateError> {
+    *self = match state.u8()? {
+      0 => Mirroring::Vertical,
+      1 => Mirroring::Horizontal,
+      2 => Mirroring::FourScreen,
+      3 => Mirroring::Single0,
+      4 => Mirroring::Single1,
+      _ => return Err(StateError::Mismatch("mirroring mode")),
+    };
+    Ok(())
+  }
 }
@@ -1,3 +1,5 @@
+use crate::savestate::{Reader, Snapshot, StateError, Writer};
+
 pub struct Banks {
   size: usize,
   window: usize,
@@ -10,27 +12,26 @@ pub struct Banks {
 impl Banks {
   pub fn new(start: usize, end: usize, window: usize, memory: Vec<u8>, writeable: bool) -> Self {
     let size = end - start;
+    let pages = std::cmp::max(1, memory.len() / window);
     let mut banks = vec![0; (size + 1) / window];
 
     for i in 0 .. banks.len() {
-      banks[i] = i * window;
+      banks[i] = (i % pages) * window;
     }
 
     Banks {
       size,
       window,
       banks,
-      pages: std::cmp::max(1, memory.len() / window),
+      pages,
       memory,
       writeable,
     }
   }
 
   pub fn set_range(&mut self, start: usize, end: usize, bank: usize) {
-    let mut addr = (bank % self.pages) * self.window;
-    for slot in start ..= end {
-      self.banks[slot] = addr;
-      addr += self.window;
+    for (offset, slot) in (start ..= end).enumerate() {
+      self.banks[slot] = ((bank + offset) % self.pages) * self.window;
     }
   }
 
@@ -51,13 +52,17 @@ impl Banks {
     page | ((addr as usize) % self.window)
   }
 
-  pub fn read(&self, addr: u16) -> u8 {
-    self.memory[self.translate(addr) % self.memory.len()]
+  pub fn read(&self, addr: u16) -> Option<u8> {
+    if self.memory.is_empty() {
+      return None;
+    }
+
+    Some(self.memory[self.translate(addr) % self.memory.len()])
   }
 
   pub fn write(&mut self, addr: u16, val: u8) {
-    if self.writeable {
-      let index = self.translate(addr);
+    if self.writeable && !self.memory.is_empty() {
+      let index = self.translate(addr) % self.memory.len();
       self.memory[index] = val;
     }
   }
@@ -65,4 +70,45 @@ impl Banks {
   pub fn capacity(&self) -> usize {
     self.memory.len()
   }
+
+  pub fn memory(&self) -> &[u8] {
+    &self.memory
+  }
+
+  pub fn restore(&mut self, data: &[u8]) {
+    let len = std::cmp::min(data.len(), self.memory.len());
+    self.memory[..len].copy_from_slice(&data[..len]);
+  }
+}
+
+impl Snapshot for Banks {
+  fn save(&self, state: &mut Writer) {
+    state.usize(self.banks.len());
+    for bank in &self.banks {
+      state.usize(*bank);
+    }
+
+    if self.writeable {
+      state.bytes(&self.memory);
+    }
+  }
+
+  fn load(&mut self, state: &mut Reader) -> Result<(), StateError> {
+    if state.usize()? != self.banks.len() {
+      return Err(StateError::Mismatch("bank layout"));
+    }
+
+    for bank in self.banks.iter_mut() {
+      let offset = state.usize()?;
+      if offset % self.window != 0 || offset >= self.pages * self.window {
+        return Err(StateError::Mismatch("bank offset"));
+      }
+      *bank = offset;
+    }
+
+    if self.writeable {
+      state.bytes_into(&mut self.memory, "RAM size")?;
+    }
+    Ok(())
+  }
 }
@@ -0,0 +1,478 @@
+use crate::error::NeoNESError;
+use crate::savestate::{Reader, Snapshot, StateError, Writer};
+
+use super::{fdsaudio::FDSAudio, fdsdisk::{crc, Disk}, Mapper, Mirroring, RomHeader};
+
+const BIOS_SIZE: usize = 0x2000;
+
+// CPU cycles for the head to return to the start of the disk, per byte
+// transferred, and with the drive empty while the disk is flipped.
+const REWIND_DELAY: u32 = 50000;
+const BYTE_DELAY: u32 = 150;
+const SWAP_DELAY: u32 = 1000000;
+
+// Famicom Disk System RAM adapter. Disk writes only ever touch the expanded
+// image held in memory, which is handed out as the battery RAM.
+pub struct FDS {
+  mirroring: Mirroring,
+
+  bios: Vec<u8>,
+  chr: Vec<u8>,
+  prg_ram: Vec<u8>,
+
+  disk: Disk,
+  side: Option<usize>,
+  swap: u32,
+
+  disk_enabled: bool,
+  motor: bool,
+  reset: bool,
+  reading: bool,
+  ready: bool,
+  crc_control: bool,
+  transfer_irq: bool,
+
+  scanning: bool,
+  end_of_head: bool,
+  gap_ended: bool,
+  transferred: bool,
+  dirty: bool,
+  position: usize,
+  delay: u32,
+  crc: u16,
+  last_crc_control: bool,
+  read_data: u8,
+  write_data: u8,
+  external: u8,
+
+  irq: IRQ,
+  audio: FDSAudio,
+}
+
+struct IRQ {
+  enabled: bool,
+  repeat: bool,
+  reload: u16,
+  counter: u16,
+  timer: bool,
+  transfer: bool,
+}
+
+impl IRQ {
+  fn new() -> Self {
+    IRQ {
+      enabled: false,
+      repeat: false,
+      reload: 0x0,
+      counter: 0x0,
+      timer: false,
+      transfer: false,
+    }
+  }
+
+  fn tick(&mut self) {
+    if !self.enabled {
+      return;
+    }
+
+    if self.counter == 0 {
+      self.timer = true;
+      self.counter = self.reload;
+      self.enabled = self.repeat;
+    } else {
+      self.counter -= 1;
+    }
+  }
+}
+
+impl Mapper for FDS {
+  fn mirroring(&self) -> Mirroring {
+    self.mirroring
+  }
+
+  fn read(&self, addr: u16) -> Option<u8> {
+    match addr {
+      0x0000 ..= 0x1FFF => Some(self.chr[addr as usize]),
+      0x6000 ..= 0xDFFF => Some(self.prg_ram[addr as usize - 0x6000]),
+      0xE000 ..= 0xFFFF => Some(self.bios[addr as usize - 0xE000]),
+      _ => None,
+    }
+  }
+
+  fn write(&mut self, addr: u16, val: u8) {
+    match addr {
+      0x0000 ..= 0x1FFF => self.chr[addr as usize] = val,
+      0x4020 ..= 0x4026 => self.write_registers(addr, val),
+      0x4040 ..= 0x408A => self.audio.write(addr, val),
+      0x6000 ..= 0xDFFF => self.prg_ram[addr as usize - 0x6000] = val,
+      _ => { },
+    }
+  }
+
+  fn read_expansion(&mut self, addr: u16) -> Option<u8> {
+    match addr {
+      // Expanded images always carry valid CRCs, so the CRC error bit stays clear
+      0x4030 if self.disk_enabled => {
+        let status = (self.irq.timer as u8)
+          | (self.transferred as u8) << 1
+          | (self.end_of_head as u8) << 6;
+
+        self.transferred = false;
+        self.irq.timer = false;
+        self.irq.transfer = false;
+        Some(status)
+      }
+      0x4031 if self.disk_enabled => {
+        self.transferred = false;
+        self.irq.transfer = false;
+        Some(self.read_data)
+      }
+      0x4032 if self.disk_enabled => {
+        let inserted = self.inserted();
+
+        Some(0x40
+          | (!inserted as u8)
+          | ((!inserted || !self.scanning) as u8) << 1
+          | (!inserted as u8) << 2)
+      }
+      0x4033 if self.disk_enabled => Some(0x80),
+      0x4040 ..= 0x4092 => self.audio.read(addr),
+      _ => None,
+    }
+  }
+
+  fn tick(&mut self) {
+    self.irq.tick();
+    self.audio.tick();
+
+    if self.swap > 0 {
+      self.swap -= 1;
+    }
+
+    self.tick_drive();
+  }
+
+  fn signal(&self) -> f32 {
+    self.audio.signal()
+  }
+
+  fn mute(&mut self, channel: u8, muted: bool) {
+    if channel == 0 {
+      self.audio.mute(muted);
+    }
+  }
+
+  fn poll(&self) -> bool {
+    self.irq.timer || self.irq.transfer
+  }
+
+  fn battery_ram(&self) -> Option<&[u8]> {
+    Some(self.disk.image())
+  }
+
+  fn load_battery_ram(&mut self, data: &[u8]) {
+    self.disk.restore(data);
+  }
+
+  fn disk_sides(&self) -> usize {
+    self.disk.sides()
+  }
+
+  fn disk(&self) -> Option<usize> {
+    self.side
+  }
+
+  // Swapping sides ejects the current one first, so the BIOS notices the drive
+  // being empty before the new side shows up.
+  fn insert_disk(&mut self, side: Option<usize>) {
+    self.flush();
+    self.side = side.filter(|side| *side < self.disk.sides());
+    self.swap = SWAP_DELAY;
+  }
+}
+
+impl FDS {
+  pub fn new(header: &RomHeader, bios: Vec<u8>, image: &[u8]) -> Result<Self, NeoNESError> {
+    if bios.len() != BIOS_SIZE {
+      return Err(NeoNESError::BadBios(bios.len()));
+    }
+
+    Ok(FDS {
+      mirroring: header.mirroring,
+      bios,
+      chr: vec![0; header.chr_ram()],
+      prg_ram: vec![0; header.prg_ram()],
+      disk: Disk::new(image)?,
+      side: Some(0),
+      swap: 0,
+      disk_enabled: false,
+      motor: false,
+      reset: false,
+      reading: true,
+      ready: false,
+      crc_control: false,
+      transfer_irq: false,
+      scanning: false,
+      end_of_head: true,
+      gap_ended: false,
+      transferred: false,
+      dirty: false,
+      position: 0,
+      delay: 0,
+      crc: 0,
+      last_crc_control: false,
+      read_data: 0x0,
+      write_data: 0x0,
+      external: 0x0,
+      irq: IRQ::new(),
+      audio: FDSAudio::new(),
+    })
+  }
+
+  fn inserted(&self) -> bool {
+    self.side.is_some() && self.swap == 0
+  }
+
+  fn flush(&mut self) {
+    if let (true, Some(side)) = (self.dirty, self.side) {
+      self.disk.flush(side);
+    }
+    self.dirty = false;
+  }
+
+  fn write_registers(&mut self, addr: u16, val: u8) {
+    match addr {
+      0x4020 => self.irq.reload = (self.irq.reload & 0xFF00) | val as u16,
+      0x4021 => self.irq.reload = (self.irq.reload & 0x00FF) | (val as u16) << 8,
+      0x4022 if self.disk_enabled => {
+        self.irq.repeat = val & 0x01 == 0x01;
+        self.irq.enabled = val & 0x02 == 0x02;
+
+        if self.irq.enabled {
+          self.irq.counter = self.irq.reload;
+        } else {
+          self.irq.timer = false;
+        }
+      }
+      0x4023 => {
+        self.disk_enabled = val & 0x01 == 0x01;
+        self.audio.enable(val & 0x02 == 0x02);
+
+        if !self.disk_enabled {
+          self.irq.enabled = false;
+          self.irq.timer = false;
+          self.irq.transfer = false;
+        }
+      }
+      0x4024 if self.disk_enabled => {
+        self.write_data = val;
+        self.transferred = false;
+        self.irq.transfer = false;
+      }
+      0x4025 if self.disk_enabled => {
+        self.irq.transfer = false;
+        self.motor = val & 0x01 == 0x01;
+        self.reset = val & 0x02 == 0x02;
+        self.reading = val & 0x04 == 0x04;
+        self.mirroring = if val & 0x08 == 0x08 { Mirroring::Horizontal } else { Mirroring::Vertical };
+        self.crc_control = val & 0x10 == 0x10;
+        self.ready = val & 0x40 == 0x40;
+        self.transfer_irq = val & 0x80 == 0x80;
+
+        if self.reading {
+          self.flush();
+        }
+      }
+      0x4026 if self.disk_enabled => self.external = val,
+      _ => { },
+    }
+  }
+
+  fn tick_drive(&mut self) {
+    let side = match self.side {
+      Some(side) if self.inserted() && self.motor => side,
+      _ => {
+        self.end_of_head = true;
+        self.scanning = false;
+        return;
+      }
+    };
+
+    if self.reset && !self.scanning {
+      return;
+    }
+
+    if self.end_of_head {
+      self.delay = REWIND_DELAY;
+      self.end_of_head = false;
+      self.position = 0;
+      self.gap_ended = false;
+      return;
+    }
+
+    if self.delay > 0 {
+      self.delay -= 1;
+      return;
+    }
+
+    self.scanning = true;
+
+    if self.reading {
+      self.read_byte(side);
+    } else {
+      self.write_byte(side);
+    }
+
+    self.last_crc_control = self.crc_control;
+    self.position += 1;
+
+    if self.position >= self.disk.len(side) {
+      self.motor = false;
+      self.end_of_head = true;
+      self.flush();
+    } else {
+      self.delay = BYTE_DELAY;
+    }
+  }
+
+  // Bytes only start coming through once the BIOS has asked for them and the
+  // gap before a block has passed, so the start mark itself is swallowed.
+  fn read_byte(&mut self, side: usize) {
+    let val = self.disk.read(side, self.position);
+
+    if !self.last_crc_control {
+      self.crc = crc(self.crc, val);
+    }
+
+    if !self.ready {
+      self.gap_ended = false;
+      self.crc = 0;
+    } else if val != 0 && !self.gap_ended {
+      self.gap_ended = true;
+      return;
+    }
+
+    if self.gap_ended {
+      self.transferred = true;
+      self.read_data = val;
+      self.irq.transfer |= self.transfer_irq;
+    }
+  }
+
+  // While the CRC control bit is set the drive writes out the accumulated
+  // CRC instead of the data register.
+  fn write_byte(&mut self, side: usize) {
+    let val = if !self.crc_control {
+      self.transferred = true;
+      self.irq.transfer |= self.transfer_irq;
+
+      if !self.ready {
+        self.crc = 0;
+      }
+
+      let val = if self.ready { self.write_data } else { 0x00 };
+      self.crc = crc(self.crc, val);
+      val
+    } else {
+      if !self.last_crc_control {
+        self.crc = crc(crc(self.crc, 0x00), 0x00);
+      }
+
+      let val = self.crc as u8;
+      self.crc >>= 8;
+      val
+    };
+
+    self.disk.write(side, self.position, val);
+    self.gap_ended = false;
+    self.dirty = true;
+  }
+}
+
+impl Snapshot for FDS {
+  fn save(&self, state: &mut Writer) {
+    self.mirroring.save(state);
+    state.bytes(&self.chr);
+    state.bytes(&self.prg_ram);
+
+    self.disk.save(state);
+    state.bool(self.side.is_some());
+    state.usize(self.side.unwrap_or(0));
+    state.u32(self.swap);
+
+    state.bool(self.disk_enabled);
+    state.bool(self.motor);
+    state.bool(self.reset);
+    state.bool(self.reading);
+    state.bool(self.ready);
+    state.bool(self.crc_control);
+    state.bool(self.transfer_irq);
+
+    state.bool(self.scanning);
+    state.bool(self.end_of_head);
+    state.bool(self.gap_ended);
+    state.bool(self.transferred);
+    state.bool(self.dirty);
+    state.usize(self.position);
+    state.u32(self.delay);
+    state.u16(self.crc);
+    state.bool(self.last_crc_control);
+    state.u8(self.read_data);
+    state.u8(self.write_data);
+    state.u8(self.external);
+
+    state.bool(self.irq.enabled);
+    state.bool(self.irq.repeat);
+    state.u16(self.irq.reload);
+    state.u16(self.irq.counter);
+    state.bool(self.irq.timer);
+    state.bool(self.irq.transfer);
+    self.audio.save(state);
+  }
+
+  fn load(&mut self, state: &mut Reader) -> Result<(), StateError> {
+    self.mirroring.load(state)?;
+    state.bytes_into(&mut self.chr, "FDS CHR RAM size")?;
+    state.bytes_into(&mut self.prg_ram, "FDS PRG RAM size")?;
+
+    self.disk.load(state)?;
+    let inserted = state.bool()?;
+    let side = state.usize()?;
+    self.side = inserted.then_some(side).filter(|side| *side < self.disk.sides());
+    self.swap = state.u32()?;
+
+    self.disk_enabled = state.bool()?;
+    self.motor = state.bool()?;
+    self.reset = state.bool()?;
+    self.reading = state.bool()?;
+    self.ready = state.bool()?;
+    self.crc_control = state.bool()?;
+    self.transfer_irq = state.bool()?;
+
+    self.scanning = state.bool()?;
+    self.end_of_head = state.bool()?;
+    self.gap_ended = state.bool()?;
+    self.transferred = state.bool()?;
+    self.dirty = state.bool()?;
+    self.position = state.usize()?;
+
+    if self.side.is_some_and(|side| self.position >= self.disk.len(side)) {
+      return Err(StateError::Mismatch("FDS head position"));
+    }
+
+    self.delay = state.u32()?;
+    self.crc = state.u16()?;
+    self.last_crc_control = state.bool()?;
+    self.read_data = state.u8()?;
+    self.write_data = state.u8()?;
+    self.external = state.u8()?;
+
+    self.irq.enabled = state.bool()?;
+    self.irq.repeat = state.bool()?;
+    self.irq.reload = state.u16()?;
+    self.irq.counter = state.u16()?;
+    self.irq.timer = state.bool()?;
+    self.irq.transfer = state.bool()?;
+    self.audio.load(state)
+  }
+}
@@ -0,0 +1,368 @@
+use crate::savestate::{Reader, Snapshot, StateError, Writer};
+
+pub struct FDSAudio {
+  enabled: bool,
+  muted: bool,
+
+  wave: [u8; 0x40],
+  position: u8,
+  accumulator: u16,
+  halted: bool,
+  writeable: bool,
+  envelopes_halted: bool,
+  master_volume: u8,
+  master_speed: u8,
+  output: u8,
+
+  volume: Envelope,
+  modulator: Modulator,
+}
+
+struct Envelope {
+  disabled: bool,
+  increase: bool,
+  speed: u8,
+  gain: u8,
+  timer: u32,
+  frequency: u16,
+}
+
+struct Modulator {
+  envelope: Envelope,
+  halted: bool,
+  table: [u8; 0x40],
+  position: u8,
+  counter: i8,
+  accumulator: u16,
+  output: i32,
+}
+
+impl Envelope {
+  fn new() -> Self {
+    Envelope {
+      disabled: true,
+      increase: false,
+      speed: 0x0,
+      gain: 0x0,
+      timer: 0x0,
+      frequency: 0x0,
+    }
+  }
+
+  fn write(&mut self, reg: u16, val: u8, master_speed: u8) {
+    match reg {
+      0 => {
+        self.disabled = val & 0x80 == 0x80;
+        self.increase = val & 0x40 == 0x40;
+        self.speed = val & 0x3F;
+        self.reset(master_speed);
+
+        if self.disabled {
+          self.gain = self.speed;
+        }
+      }
+      2 => self.frequency = (self.frequency & 0xF00) | val as u16,
+      3 => self.frequency = (self.frequency & 0x0FF) | ((val as u16 & 0x0F) << 8),
+      _ => { },
+    }
+  }
+
+  fn reset(&mut self, master_speed: u8) {
+    self.timer = 8 * (self.speed as u32 + 1) * master_speed as u32;
+  }
+
+  fn tick(&mut self, master_speed: u8) -> bool {
+    if self.disabled || master_speed == 0 {
+      return false;
+    }
+
+    if self.timer > 1 {
+      self.timer -= 1;
+      return false;
+    }
+
+    self.reset(master_speed);
+
+    if self.increase && self.gain < 32 {
+      self.gain += 1;
+    } else if !self.increase && self.gain > 0 {
+      self.gain -= 1;
+    }
+    true
+  }
+}
+
+impl Modulator {
+  const STEPS: [i8; 0x08] = [0, 1, 2, 4, 0, -4, -2, -1];
+
+  fn new() -> Self {
+    Modulator {
+      envelope: Envelope::new(),
+      halted: true,
+      table: [0x0; 0x40],
+      position: 0x0,
+      counter: 0x0,
+      accumulator: 0x0,
+      output: 0x0,
+    }
+  }
+
+  fn enabled(&self) -> bool {
+    !self.halted && self.envelope.frequency > 0
+  }
+
+  // The counter is a signed 7-bit value
+  fn set_counter(&mut self, val: i16) {
+    self.counter = (((val as u8) << 1) as i8) >> 1;
+  }
+
+  // The table is written two entries at a time, and only while halted
+  fn write_table(&mut self, val: u8) {
+    if self.halted {
+      self.table[self.position as usize] = val & 0x07;
+      self.table[(self.position as usize + 1) & 0x3F] = val & 0x07;
+      self.position = (self.position + 2) & 0x3F;
+    }
+  }
+
+  fn tick(&mut self) -> bool {
+    if !self.enabled() {
+      return false;
+    }
+
+    let (accumulator, overflow) = self.accumulator.overflowing_add(self.envelope.frequency);
+    self.accumulator = accumulator;
+
+    if overflow {
+      let step = self.table[self.position as usize];
+      let counter = if step == 4 { 0 } else { self.counter as i16 + Modulator::STEPS[step as usize] as i16 };
+
+      self.set_counter(counter);
+      self.position = (self.position + 1) & 0x3F;
+    }
+    overflow
+  }
+
+  // The pitch offset works out to counter * gain / 16 with the hardware's
+  // peculiar rounding, then scaled by the wave frequency.
+  fn update(&mut self, frequency: u16) {
+    let mut offset = self.counter as i32 * self.envelope.gain as i32;
+    let remainder = offset & 0x0F;
+    offset >>= 4;
+
+    if remainder > 0 && offset & 0x80 == 0 {
+      offset += if self.counter < 0 { -1 } else { 2 };
+    }
+
+    if offset >= 192 {
+      offset -= 256;
+    } else if offset < -64 {
+      offset += 256;
+    }
+
+    offset *= frequency as i32;
+    let remainder = offset & 0x3F;
+    offset >>= 6;
+
+    if remainder >= 32 {
+      offset += 1;
+    }
+    self.output = offset;
+  }
+
+  fn signal(&self) -> i32 {
+    if self.enabled() { self.output } else { 0 }
+  }
+}
+
+impl FDSAudio {
+  // Scaled so a full volume wave is about 2.4 times a full volume 2A03 pulse
+  const LEVEL: f32 = 0.0057;
+  const VOLUMES: [u32; 0x04] = [36, 24, 17, 14];
+
+  pub fn new() -> Self {
+    FDSAudio {
+      enabled: false,
+      muted: false,
+      wave: [0x0; 0x40],
+      position: 0x0,
+      accumulator: 0x0,
+      halted: true,
+      writeable: false,
+      envelopes_halted: false,
+      master_volume: 0x0,
+      master_speed: 0xE8,
+      output: 0x0,
+      volume: Envelope::new(),
+      modulator: Modulator::new(),
+    }
+  }
+
+  pub fn enable(&mut self, enabled: bool) {
+    self.enabled = enabled;
+  }
+
+  pub fn mute(&mut self, muted: bool) {
+    self.muted = muted;
+  }
+
+  pub fn read(&self, addr: u16) -> Option<u8> {
+    match addr {
+      0x4040 ..= 0x407F => Some(self.wave[addr as usize - 0x4040] | 0x40),
+      0x4090 => Some(self.volume.gain | 0x40),
+      0x4092 => Some(self.modulator.envelope.gain | 0x40),
+      _ => None,
+    }
+  }
+
+  pub fn write(&mut self, addr: u16, val: u8) {
+    if !self.enabled {
+      return;
+    }
+
+    match addr {
+      0x4040 ..= 0x407F if self.writeable => self.wave[addr as usize - 0x4040] = val & 0x3F,
+      0x4080 | 0x4082 => self.volume.write(addr & 0x03, val, self.master_speed),
+      0x4083 => {
+        self.halted = val & 0x80 == 0x80;
+        self.envelopes_halted = val & 0x40 == 0x40;
+
+        if self.halted {
+          self.position = 0;
+          self.accumulator = 0;
+        }
+
+        if self.envelopes_halted {
+          self.volume.reset(self.master_speed);
+          self.modulator.envelope.reset(self.master_speed);
+        }
+        self.volume.write(3, val, self.master_speed);
+      }
+      0x4084 | 0x4086 => self.modulator.envelope.write(addr & 0x03, val, self.master_speed),
+      0x4085 => {
+        self.modulator.set_counter(val as i16 & 0x7F);
+        self.modulator.update(self.volume.frequency);
+      }
+      0x4087 => {
+        self.modulator.halted = val & 0x80 == 0x80;
+        self.modulator.envelope.write(3, val, self.master_speed);
+
+        if self.modulator.halted {
+          self.modulator.accumulator = 0;
+        }
+      }
+      0x4088 => self.modulator.write_table(val),
+      0x4089 => {
+        self.writeable = val & 0x80 == 0x80;
+        self.master_volume = val & 0x03;
+      }
+      0x408A => self.master_speed = val,
+      _ => { },
+    }
+  }
+
+  pub fn tick(&mut self) {
+    let frequency = self.volume.frequency;
+
+    if !self.halted && !self.envelopes_halted {
+      self.volume.tick(self.master_speed);
+
+      if self.modulator.envelope.tick(self.master_speed) {
+        self.modulator.update(frequency);
+      }
+    }
+
+    if self.modulator.tick() {
+      self.modulator.update(frequency);
+    }
+
+    // The output holds its last value while the wave RAM is being written
+    if !self.writeable {
+      let gain = std::cmp::min(self.volume.gain, 32) as u32;
+      self.output = (self.wave[self.position as usize] as u32 * gain * FDSAudio::VOLUMES[self.master_volume as usize] / 1152) as u8;
+    }
+
+    let pitch = frequency as i32 + self.modulator.signal();
+
+    if !self.halted && !self.writeable && pitch > 0 {
+      let (accumulator, overflow) = self.accumulator.overflowing_add(pitch as u16);
+      self.accumulator = accumulator;
+
+      if overflow {
+        self.position = (self.position + 1) & 0x3F;
+      }
+    }
+  }
+
+  pub fn signal(&self) -> f32 {
+    if self.muted { 0.0 } else { self.output as f32 * FDSAudio::LEVEL }
+  }
+}
+
+impl Snapshot for Envelope {
+  fn save(&self, state: &mut Writer) {
+    state.bool(self.disabled);
+    state.bool(self.increase);
+    state.u8(self.speed);
+    state.u8(self.gain);
+    state.u32(self.timer);
+    state.u16(self.frequency);
+  }
+
+  fn load(&mut self, state: &mut Reader) -> Result<(), StateError> {
+    self.disabled = state.bool()?;
+    self.increase = state.bool()?;
+    self.speed = state.u8()? & 0x3F;
+    self.gain = state.u8()? & 0x3F;
+    self.timer = state.u32()?;
+    self.frequency = state.u16()? & 0xFFF;
+    Ok(())
+  }
+}
+
+impl Snapshot for FDSAudio {
+  fn save(&self, state: &mut Writer) {
+    state.bool(self.enabled);
+    state.bytes(&self.wave);
+    state.u8(self.position);
+    state.u16(self.accumulator);
+    state.bool(self.halted);
+    state.bool(self.writeable);
+    state.bool(self.envelopes_halted);
+    state.u8(self.master_volume);
+    state.u8(self.master_speed);
+    state.u8(self.output);
+
+    self.volume.save(state);
+    self.modulator.envelope.save(state);
+    state.bool(self.modulator.halted);
+    state.bytes(&self.modulator.table);
+    state.u8(self.modulator.position);
+    state.u8(self.modulator.counter as u8);
+    state.u16(self.modulator.accumulator);
+    state.u32(self.modulator.output as u32);
+  }
+
+  fn load(&mut self, state: &mut Reader) -> Result<(), StateError> {
+    self.enabled = state.bool()?;
+    state.bytes_into(&mut self.wave, "FDS wave table")?;
+    self.position = state.u8()? & 0x3F;
+    self.accumulator = state.u16()?;
+    self.halted = state.bool()?;
+    self.writeable = state.bool()?;
+    self.envelopes_halted = state.bool()?;
+    self.master_volume = state.u8()? & 0x03;
+    self.master_speed = state.u8()?;
+    self.output = state.u8()?;
+
+    self.volume.load(state)?;
+    self.modulator.envelope.load(state)?;
+    self.modulator.halted = state.bool()?;
+    state.bytes_into(&mut self.modulator.table, "FDS modulation table")?;
+    self.modulator.position = state.u8()? & 0x3F;
+    self.modulator.set_counter(state.u8()? as i16);
+    self.modulator.accumulator = state.u16()?;
+    self.modulator.output = state.u32()? as i32;
+    Ok(())
+  }
+}
@@ -0,0 +1,163 @@
+use crate::error::NeoNESError;
+use crate::savestate::{Reader, Snapshot, StateError, Writer};
+
+const HEADER: [u8; 4] = [0x46, 0x44, 0x53, 0x1A];
+const HEADER_SIZE: usize = 16;
+const SIDE_SIZE: usize = 65500;
+const RAW_SIDE_SIZE: usize = 68000;
+
+const LEAD_IN: usize = 28300 / 8;
+const GAP: usize = 976 / 8;
+
+// A .fds image only keeps the contents of each block. The drive sees those
+// blocks surrounded by gaps, start marks and CRCs, so every side is expanded
+// into that raw stream on load and folded back into the image after writes.
+pub struct Disk {
+  image: Vec<u8>,
+  raw: Vec<Vec<u8>>,
+}
+
+impl Disk {
+  pub fn new(file: &[u8]) -> Result<Self, NeoNESError> {
+    let image = if file.starts_with(&HEADER) { &file[HEADER_SIZE.min(file.len()) ..] } else { file };
+    let sides = image.len() / SIDE_SIZE;
+
+    if sides == 0 || image.chunks_exact(SIDE_SIZE).any(|side| side[0] != 0x01 || &side[1 .. 15] != b"*NINTENDO-HVC*") {
+      return Err(NeoNESError::BadDisk);
+    }
+
+    let image = image[.. sides * SIDE_SIZE].to_vec();
+    let raw = image.chunks_exact(SIDE_SIZE).map(Disk::expand).collect();
+
+    Ok(Disk {
+      image,
+      raw,
+    })
+  }
+
+  pub fn sides(&self) -> usize {
+    self.raw.len()
+  }
+
+  pub fn image(&self) -> &[u8] {
+    &self.image
+  }
+
+  pub fn restore(&mut self, data: &[u8]) {
+    let len = std::cmp::min(data.len(), self.image.len());
+    self.image[.. len].copy_from_slice(&data[.. len]);
+    self.raw = self.image.chunks_exact(SIDE_SIZE).map(Disk::expand).collect();
+  }
+
+  pub fn len(&self, side: usize) -> usize {
+    self.raw[side].len()
+  }
+
+  pub fn read(&self, side: usize, position: usize) -> u8 {
+    self.raw[side][position]
+  }
+
+  pub fn write(&mut self, side: usize, position: usize, val: u8) {
+    self.raw[side][position] = val;
+  }
+
+  // Folds a side's raw stream back into the image, stopping at the first
+  // block that does not parse.
+  pub fn flush(&mut self, side: usize) {
+    let raw = &self.raw[side];
+    let image = &mut self.image[side * SIDE_SIZE .. (side + 1) * SIDE_SIZE];
+
+    let mut position = 0;
+    let mut offset = 0;
+    let mut file_size = 0;
+
+    loop {
+      while position < raw.len() && raw[position] == 0x00 {
+        position += 1;
+      }
+
+      if position + 1 >= raw.len() || raw[position] != 0x80 {
+        break;
+      }
+      position += 1;
+
+      let length = match raw[position] {
+        0x01 => 56,
+        0x02 => 2,
+        0x03 => 16,
+        0x04 => 1 + file_size,
+        _ => break,
+      };
+
+      if position + length > raw.len() || offset + length > SIDE_SIZE {
+        break;
+      }
+
+      if raw[position] == 0x03 {
+        file_size = raw[position + 13] as usize | (raw[position + 14] as usize) << 8;
+      }
+
+      image[offset .. offset + length].copy_from_slice(&raw[position .. position + length]);
+      offset += length;
+      position += length + 2;
+    }
+  }
+
+  fn expand(side: &[u8]) -> Vec<u8> {
+    let mut raw = vec![0x00; LEAD_IN];
+    let mut position = 0;
+    let mut file_size = 0;
+
+    while position < side.len() {
+      let length = match side[position] {
+        0x01 => 56,
+        0x02 => 2,
+        0x03 => 16,
+        0x04 => 1 + file_size,
+        _ => break,
+      };
+
+      if position + length > side.len() {
+        break;
+      }
+
+      if side[position] == 0x03 {
+        file_size = side[position + 13] as usize | (side[position + 14] as usize) << 8;
+      }
+
+      let block = &side[position .. position + length];
+      let crc = block.iter().chain(&[0x00, 0x00]).fold(crc(0, 0x80), |acc, byte| crc(acc, *byte));
+
+      raw.push(0x80);
+      raw.extend_from_slice(block);
+      raw.extend_from_slice(&crc.to_le_bytes());
+      raw.resize(raw.len() + GAP, 0x00);
+      position += length;
+    }
+
+    raw.resize(std::cmp::max(raw.len(), RAW_SIDE_SIZE), 0x00);
+    raw
+  }
+}
+
+pub fn crc(acc: u16, val: u8) -> u16 {
+  (0 .. 8).fold(acc, |acc, bit| {
+    let acc = if acc & 0x01 == 0x01 { (acc >> 1) ^ 0x8408 } else { acc >> 1 };
+    if val & (1 << bit) != 0 { acc ^ 0x8000 } else { acc }
+  })
+}
+
+impl Snapshot for Disk {
+  fn save(&self, state: &mut Writer) {
+    self.raw.iter().for_each(|side| state.bytes(side));
+  }
+
+  fn load(&mut self, state: &mut Reader) -> Result<(), StateError> {
+    for side in self.raw.iter_mut() {
+      state.bytes_into(side, "FDS disk size")?;
+    }
+
+    (0 .. self.sides()).for_each(|side| self.flush(side));
+    Ok(())
+  }
+}
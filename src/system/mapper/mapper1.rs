@@ -1,11 +1,20 @@
-use super::{banks::Banks, Mapper, Mirroring};
+use crate::savestate::{Reader, Snapshot, StateError, Writer};
+
+use super::{banks::Banks, Mapper, Mirroring, RomHeader};
 
 const PRG_RAM_BANK_SIZE: usize = 0x2000;
 const PRG_ROM_BANK_SIZE: usize = 0x4000;
 const CHR_BANK_SIZE: usize = 0x1000;
 
+// The SxROM boards reuse CHR bank bits: SNROM's bit 4 disables PRG RAM,
+// SUROM's bit 4 selects the upper 256K of PRG ROM, and SOROM/SXROM select a
+// PRG RAM bank with bit 3 or bits 2-3.
 pub struct Mapper1 {
   mirroring: Mirroring,
+  battery: bool,
+  snrom: bool,
+  mmc1a: bool,
+  fixed_prg: bool,
 
   chr: Banks,
   prg_ram: Banks,
@@ -17,6 +26,7 @@ pub struct Mapper1 {
   chr1: u8,
   prg: u8,
   shift: u8,
+  idle: u8,
 }
 
 impl Mapper for Mapper1 {
@@ -24,41 +34,54 @@ impl Mapper for Mapper1 {
     self.mirroring
   }
 
-  fn read(&self, addr: u16) -> u8 {
+  fn read(&self, addr: u16) -> Option<u8> {
     match addr {
-        0x0000 ..= 0x1FFF => {
-          let val = self.chr.read(addr);
-          // println!("Reading {val:#0x} from {addr:#0x} (actually {:#0x})", self.chr.translate(addr));
-          val
-        }
-        0x6000 ..= 0x7FFF if self.prg & 0x10 == 0x0 => self.prg_ram.read(addr),
+        0x0000 ..= 0x1FFF => self.chr.read(addr),
+        0x6000 ..= 0x7FFF if self.ram_enabled() => self.prg_ram.read(addr),
         0x8000 ..= 0xFFFF => self.prg_rom.read(addr),
-        _ => 0,
+        _ => None,
     }
   }
 
   fn write(&mut self, addr: u16, val: u8) {
     match addr {
-        0x0000 ..= 0x1FFF => {
-          // println!("Writing {val:#0x} to {addr:#0x} (actually {:#0x})", self.chr.translate(addr));
-          self.chr.write(addr, val)
-        }
-        0x6000 ..= 0x7FFF if self.prg & 0x10 == 0x0 => self.prg_ram.write(addr, val),
+        0x0000 ..= 0x1FFF => self.chr.write(addr, val),
+        0x6000 ..= 0x7FFF if self.ram_enabled() => self.prg_ram.write(addr, val),
         0x8000 ..= 0xFFFF => self.load(addr, val),
         _ => { },
     }
   }
+
+  fn tick(&mut self) {
+    self.idle = self.idle.saturating_add(1);
+  }
+
+  fn battery_ram(&self) -> Option<&[u8]> {
+    self.battery.then(|| self.prg_ram.memory())
+  }
+
+  fn load_battery_ram(&mut self, data: &[u8]) {
+    if self.battery {
+      self.prg_ram.restore(data);
+    }
+  }
 }
 
 impl Mapper1 {
-  pub fn new(chr_rom: Vec<u8>, prg_rom: Vec<u8>, mirroring: Mirroring) -> Self {
+  pub fn new(header: &RomHeader, chr_rom: Vec<u8>, prg_rom: Vec<u8>) -> Self {
     let chr = !chr_rom.is_empty();
 
     let mut mapper = Mapper1 {
-      mirroring,
-
-      chr: Banks::new(0x0000, 0x1FFF, CHR_BANK_SIZE, if chr { chr_rom } else { vec![0; 0x2000] }, !chr),
-      prg_ram: Banks::new(0x6000, 0x7FFF, PRG_RAM_BANK_SIZE, vec![0; 0x8000], true),
+      mirroring: header.mirroring,
+      battery: header.battery,
+      // SOROM shares SNROM's layout but spends CHR bit 3 on its second RAM bank
+      snrom: !chr && prg_rom.len() <= 0x40000 && header.prg_ram() == 0x2000,
+      // NES 2.0 submapper 3 marks the MMC1A, and 5 the fixed 32K PRG of SEROM/SHROM
+      mmc1a: header.submapper == 3,
+      fixed_prg: header.submapper == 5,
+
+      chr: Banks::new(0x0000, 0x1FFF, CHR_BANK_SIZE, if chr { chr_rom } else { vec![0; header.chr_ram()] }, !chr),
+      prg_ram: Banks::new(0x6000, 0x7FFF, PRG_RAM_BANK_SIZE, vec![0; header.prg_ram()], true),
       prg_rom: Banks::new(0x8000, 0xFFFF, PRG_ROM_BANK_SIZE, prg_rom, false),
 
       control: 0xC,
@@ -66,13 +89,14 @@ impl Mapper1 {
       chr1: 0,
       prg: 0,
       shift: 0x10,
+      idle: u8::MAX,
     };
 
-    mapper.update_banks(0x0000);
+    mapper.update_banks();
     mapper
   }
 
-  fn update_banks(&mut self, addr: u16) {
+  fn update_banks(&mut self) {
     self.mirroring = match self.control & 0x03 {
       0 => Mirroring::Single0,
       1 => Mirroring::Single1,
@@ -89,16 +113,21 @@ impl Mapper1 {
       }
     }
 
-    let extra = match addr {
-      0xC000 ..= 0xDFFF if self.chr_mode() != 0x0 => self.chr1,
-      _ => self.chr0,
-    } as usize;
+    let chr0 = self.chr0 as usize;
 
-    let selector = if self.prg_rom.capacity() == 0x80000 { extra & 0x10 } else { 0x0 };
+    let ram_bank = match self.prg_ram.capacity() {
+      0x4000 => (chr0 >> 3) & 0x01,
+      0x8000 => (chr0 >> 2) & 0x03,
+      _ => 0,
+    };
+    self.prg_ram.set(0, ram_bank);
+
+    let selector = if self.prg_rom.capacity() == 0x80000 { chr0 & 0x10 } else { 0x0 };
 
     let prg = self.prg as usize & 0xF;
 
     match self.prg_mode() {
+      _ if self.fixed_prg => self.prg_rom.set_range(0, 1, 0),
       0 | 1 => self.prg_rom.set_range(0, 1, selector | (prg & 0xFE)),
       2 => {
         self.prg_rom.set(0, selector);
@@ -106,12 +135,19 @@ impl Mapper1 {
       }
       3 => {
         self.prg_rom.set(0, selector | prg);
-        self.prg_rom.set(1, selector | self.prg_rom.last());
+        self.prg_rom.set(1, selector | (self.prg_rom.last() & 0x0F));
       }
       mode => unreachable!("Impossible prg_mode: {mode}"),
     }
   }
 
+  // The MMC1A has no PRG RAM disable bit
+  fn ram_enabled(&self) -> bool {
+    let disabled = self.prg & 0x10 == 0x10 && !self.mmc1a;
+    let snrom_disabled = self.snrom && self.chr0 & 0x10 == 0x10;
+    !disabled && !snrom_disabled
+  }
+
   fn prg_mode(&self) -> u8 {
     (self.control >> 2) & 0x03
   }
@@ -121,6 +157,15 @@ impl Mapper1 {
   }
 
   fn load(&mut self, addr: u16, val: u8) {
+    // Writes on consecutive cycles, like the two from a read-modify-write
+    // instruction, only see the first
+    let ignored = self.idle < 2;
+    self.idle = 0;
+
+    if ignored {
+      return;
+    }
+
     if (val >> 7) == 0x01 {
       self.shift = 0x10;
       self.control |= 0xC;
@@ -140,8 +185,39 @@ impl Mapper1 {
         }
 
         self.shift = 0x10;
-        self.update_banks(addr);
+        self.update_banks();
       }
     }
   }
 }
+
+impl Snapshot for Mapper1 {
+  fn save(&self, state: &mut Writer) {
+    self.mirroring.save(state);
+    self.chr.save(state);
+    self.prg_ram.save(state);
+    self.prg_rom.save(state);
+
+    state.u8(self.control);
+    state.u8(self.chr0);
+    state.u8(self.chr1);
+    state.u8(self.prg);
+    state.u8(self.shift);
+    state.u8(self.idle);
+  }
+
+  fn load(&mut self, state: &mut Reader) -> Result<(), StateError> {
+    self.mirroring.load(state)?;
+    self.chr.load(state)?;
+    self.prg_ram.load(state)?;
+    self.prg_rom.load(state)?;
+
+    self.control = state.u8()?;
+    self.chr0 = state.u8()?;
+    self.chr1 = state.u8()?;
+    self.prg = state.u8()?;
+    self.shift = state.u8()?;
+    self.idle = state.u8()?;
+    Ok(())
+  }
+}
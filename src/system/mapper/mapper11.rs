@@ -0,0 +1,66 @@
+use crate::savestate::{Reader, Snapshot, StateError, Writer};
+
+use super::{banks::Banks, Mapper, Mirroring, RomHeader};
+
+const PRG_ROM_BANK_SIZE: usize = 0x8000;
+const CHR_BANK_SIZE: usize = 0x2000;
+
+// Color Dreams
+
+pub struct Mapper11 {
+  mirroring: Mirroring,
+
+  chr: Banks,
+  prg_rom: Banks,
+}
+
+impl Mapper for Mapper11 {
+  fn mirroring(&self) -> Mirroring {
+    self.mirroring
+  }
+
+  fn read(&self, addr: u16) -> Option<u8> {
+    match addr {
+      0x0000 ..= 0x1FFF => self.chr.read(addr),
+      0x8000 ..= 0xFFFF => self.prg_rom.read(addr),
+      _ => None,
+    }
+  }
+
+  fn write(&mut self, addr: u16, val: u8) {
+    match addr {
+      0x0000 ..= 0x1FFF => self.chr.write(addr, val),
+      0x8000 ..= 0xFFFF => {
+        self.prg_rom.set(0, val as usize & 0x03);
+        self.chr.set(0, (val >> 4) as usize);
+      }
+      _ => { },
+    }
+  }
+}
+
+impl Mapper11 {
+  pub fn new(header: &RomHeader, chr_rom: Vec<u8>, prg_rom: Vec<u8>) -> Self {
+    let chr = !chr_rom.is_empty();
+
+    Mapper11 {
+      mirroring: header.mirroring,
+      chr: Banks::new(0x0000, 0x1FFF, CHR_BANK_SIZE, if chr { chr_rom } else { vec![0; header.chr_ram()] }, !chr),
+      prg_rom: Banks::new(0x8000, 0xFFFF, PRG_ROM_BANK_SIZE, prg_rom, false),
+    }
+  }
+}
+
+impl Snapshot for Mapper11 {
+  fn save(&self, state: &mut Writer) {
+    self.mirroring.save(state);
+    self.chr.save(state);
+    self.prg_rom.save(state);
+  }
+
+  fn load(&mut self, state: &mut Reader) -> Result<(), StateError> {
+    self.mirroring.load(state)?;
+    self.chr.load(state)?;
+    self.prg_rom.load(state)
+  }
+}
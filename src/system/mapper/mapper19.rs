@@ -0,0 +1,254 @@
+use crate::savestate::{Reader, Snapshot, StateError, Writer};
+
+use super::{banks::Banks, namco163audio::Namco163Audio, Mapper, Mirroring, RomHeader};
+
+const PRG_BANK_SIZE: usize = 0x2000;
+const CHR_BANK_SIZE: usize = 0x400;
+const SOUND_RAM: usize = 0x80;
+
+// Namco 163. Boards that keep their saves in the sound RAM are marked in NES
+// 2.0 headers with 128 bytes of PRG NVRAM, which is then the sound RAM itself.
+pub struct Mapper19 {
+  mirroring: Mirroring,
+  battery: bool,
+
+  chr: Banks,
+  prg_rom: Banks,
+  ram: Vec<u8>,
+  sound: [u8; SOUND_RAM],
+
+  nametables: [u8; 0x04],
+  protect: u8,
+  address: u8,
+  increment: bool,
+
+  irq: IRQ,
+  audio: Namco163Audio,
+}
+
+struct IRQ {
+  enabled: bool,
+  pending: bool,
+  counter: u16,
+}
+
+impl IRQ {
+  fn new() -> Self {
+    IRQ {
+      enabled: false,
+      pending: false,
+      counter: 0x0,
+    }
+  }
+}
+
+impl Mapper for Mapper19 {
+  fn mirroring(&self) -> Mirroring {
+    self.mirroring
+  }
+
+  fn read(&self, addr: u16) -> Option<u8> {
+    match addr {
+      0x0000 ..= 0x1FFF => self.chr.read(addr),
+      0x6000 ..= 0x7FFF => self.ram.get(addr as usize - 0x6000).copied(),
+      0x8000 ..= 0xFFFF => self.prg_rom.read(addr),
+      _ => None,
+    }
+  }
+
+  fn write(&mut self, addr: u16, val: u8) {
+    match addr {
+      0x0000 ..= 0x1FFF => self.chr.write(addr, val),
+      0x4800 ..= 0x4FFF => {
+        let index = self.sound_index();
+        self.sound[index] = val;
+      }
+      0x5000 ..= 0x57FF => {
+        self.irq.counter = (self.irq.counter & 0x7F00) | val as u16;
+        self.irq.pending = false;
+      }
+      0x5800 ..= 0x5FFF => {
+        self.irq.counter = (self.irq.counter & 0x00FF) | ((val as u16 & 0x7F) << 8);
+        self.irq.enabled = val & 0x80 == 0x80;
+        self.irq.pending = false;
+      }
+      0x6000 ..= 0x7FFF if self.writeable(addr) => {
+        if let Some(byte) = self.ram.get_mut(addr as usize - 0x6000) {
+          *byte = val;
+        }
+      }
+      0x8000 ..= 0xBFFF => self.chr.set((addr as usize - 0x8000) / 0x800, val as usize),
+      0xC000 ..= 0xDFFF => self.nametables[(addr as usize - 0xC000) / 0x800] = val,
+      0xE000 ..= 0xE7FF => {
+        self.prg_rom.set(0, val as usize & 0x3F);
+        self.audio.disable(val & 0x40 == 0x40);
+      }
+      0xE800 ..= 0xEFFF => self.prg_rom.set(1, val as usize & 0x3F),
+      0xF000 ..= 0xF7FF => self.prg_rom.set(2, val as usize & 0x3F),
+      0xF800 ..= 0xFFFF => {
+        self.protect = val;
+        self.address = val & 0x7F;
+        self.increment = val & 0x80 == 0x80;
+      }
+      _ => { },
+    }
+  }
+
+  fn read_expansion(&mut self, addr: u16) -> Option<u8> {
+    match addr {
+      0x4800 ..= 0x4FFF => {
+        let index = self.sound_index();
+        Some(self.sound[index])
+      }
+      0x5000 ..= 0x57FF => Some(self.irq.counter as u8),
+      0x5800 ..= 0x5FFF => Some((self.irq.counter >> 8) as u8 | if self.irq.enabled { 0x80 } else { 0x0 }),
+      _ => None,
+    }
+  }
+
+  // Each nametable quadrant is either a page of CIRAM ($E0 and up) or a bank
+  // of CHR ROM.
+  fn read_nametable(&mut self, addr: u16, vram: &[u8]) -> u8 {
+    let (select, offset) = self.nametable(addr);
+
+    match select {
+      0xE0 ..= 0xFF => vram[(select as usize & 0x01) * 0x400 + offset],
+      _ if self.chr.capacity() == 0 => 0,
+      _ => {
+        let chr = self.chr.memory();
+        chr[(select as usize * CHR_BANK_SIZE + offset) % chr.len()]
+      }
+    }
+  }
+
+  fn write_nametable(&mut self, addr: u16, val: u8, vram: &mut [u8]) {
+    let (select, offset) = self.nametable(addr);
+
+    if select >= 0xE0 {
+      vram[(select as usize & 0x01) * 0x400 + offset] = val;
+    }
+  }
+
+  fn tick(&mut self) {
+    if self.irq.enabled && self.irq.counter < 0x7FFF {
+      self.irq.counter += 1;
+
+      if self.irq.counter == 0x7FFF {
+        self.irq.pending = true;
+      }
+    }
+
+    self.audio.tick(&mut self.sound);
+  }
+
+  fn signal(&self) -> f32 {
+    self.audio.signal()
+  }
+
+  fn mute(&mut self, channel: u8, muted: bool) {
+    self.audio.mute(channel, muted);
+  }
+
+  fn poll(&self) -> bool {
+    self.irq.pending
+  }
+
+  fn battery_ram(&self) -> Option<&[u8]> {
+    self.battery.then_some(if self.ram.is_empty() { &self.sound } else { &self.ram })
+  }
+
+  fn load_battery_ram(&mut self, data: &[u8]) {
+    if self.battery {
+      let ram = if self.ram.is_empty() { &mut self.sound[..] } else { &mut self.ram[..] };
+      let len = std::cmp::min(data.len(), ram.len());
+      ram[.. len].copy_from_slice(&data[.. len]);
+    }
+  }
+}
+
+impl Mapper19 {
+  pub fn new(header: &RomHeader, chr_rom: Vec<u8>, prg_rom: Vec<u8>) -> Self {
+    let chr = !chr_rom.is_empty();
+
+    let mut mapper = Mapper19 {
+      mirroring: header.mirroring,
+      battery: header.battery,
+      chr: Banks::new(0x0000, 0x1FFF, CHR_BANK_SIZE, if chr { chr_rom } else { vec![0; header.chr_ram()] }, !chr),
+      prg_rom: Banks::new(0x8000, 0xFFFF, PRG_BANK_SIZE, prg_rom, false),
+      ram: vec![0; if header.prg_ram() == SOUND_RAM { 0 } else { header.prg_ram() }],
+      sound: [0; SOUND_RAM],
+      nametables: match header.mirroring {
+        Mirroring::Horizontal => [0xE0, 0xE0, 0xE1, 0xE1],
+        _ => [0xE0, 0xE1, 0xE0, 0xE1],
+      },
+      protect: 0x0,
+      address: 0x0,
+      increment: false,
+      irq: IRQ::new(),
+      audio: Namco163Audio::new(),
+    };
+
+    mapper.prg_rom.set(3, mapper.prg_rom.last());
+    mapper
+  }
+
+  fn nametable(&self, addr: u16) -> (u8, usize) {
+    let addr = (addr as usize - 0x2000) % 0x1000;
+    (self.nametables[addr / 0x400], addr % 0x400)
+  }
+
+  fn sound_index(&mut self) -> usize {
+    let index = self.address as usize;
+
+    if self.increment {
+      self.address = (self.address + 1) & 0x7F;
+    }
+
+    index
+  }
+
+  // PRG RAM is only writeable with $4x in the protect register, and each of
+  // the low bits protects one 2K window.
+  fn writeable(&self, addr: u16) -> bool {
+    let window = (addr - 0x6000) / 0x800;
+    self.protect & 0xF0 == 0x40 && self.protect & (1 << window) == 0
+  }
+}
+
+impl Snapshot for Mapper19 {
+  fn save(&self, state: &mut Writer) {
+    self.mirroring.save(state);
+    self.chr.save(state);
+    self.prg_rom.save(state);
+    state.bytes(&self.ram);
+    state.bytes(&self.sound);
+
+    state.bytes(&self.nametables);
+    state.u8(self.protect);
+    state.u8(self.address);
+    state.bool(self.increment);
+
+    state.bool(self.irq.enabled);
+    state.bool(self.irq.pending);
+    state.u16(self.irq.counter);
+    self.audio.save(state);
+  }
+
+  fn load(&mut self, state: &mut Reader) -> Result<(), StateError> {
+    self.mirroring.load(state)?;
+    self.chr.load(state)?;
+    self.prg_rom.load(state)?;
+    state.bytes_into(&mut self.ram, "Namco 163 RAM size")?;
+    state.bytes_into(&mut self.sound, "Namco 163 sound RAM")?;
+
+    state.bytes_into(&mut self.nametables, "Namco 163 nametables")?;
+    self.protect = state.u8()?;
+    self.address = state.u8()?;
+    self.increment = state.bool()?;
+
+    self.irq.enabled = state.bool()?;
+    self.irq.pending = state.bool()?;
+    self.irq.counter = state.u16()?;
+    self.audio.load(state)
+  }
+}
@@ -1,9 +1,12 @@
-use super::{banks::Banks, Mapper, Mirroring};
+use crate::savestate::{Reader, Snapshot, StateError, Writer};
+
+use super::{banks::Banks, Mapper, Mirroring, RomHeader};
 
 const PRG_ROM_BANK_SIZE: usize = 0x4000;
 
 pub struct Mapper2 {
   mirroring: Mirroring,
+  battery: bool,
 
   chr: Banks,
   prg_ram: Banks,
@@ -15,12 +18,12 @@ impl Mapper for Mapper2 {
     self.mirroring
   }
 
-  fn read(&self, addr: u16) -> u8 {
+  fn read(&self, addr: u16) -> Option<u8> {
     match addr {
       0x0000 ..= 0x1FFF => self.chr.read(addr),
       0x6000 ..= 0x7FFF => self.prg_ram.read(addr),
       0x8000 ..= 0xFFFF => self.prg_rom.read(addr),
-      _ => 0,
+      _ => None,
     }
   }
 
@@ -32,16 +35,27 @@ impl Mapper for Mapper2 {
       _ => { },
     }
   }
+
+  fn battery_ram(&self) -> Option<&[u8]> {
+    self.battery.then(|| self.prg_ram.memory())
+  }
+
+  fn load_battery_ram(&mut self, data: &[u8]) {
+    if self.battery {
+      self.prg_ram.restore(data);
+    }
+  }
 }
 
 impl Mapper2 {
-  pub fn new(chr_rom: Vec<u8>, prg_rom: Vec<u8>, mirroring: Mirroring) -> Self {
+  pub fn new(header: &RomHeader, chr_rom: Vec<u8>, prg_rom: Vec<u8>) -> Self {
     let chr = !chr_rom.is_empty();
 
     let mut mapper = Mapper2 {
-      mirroring,
-      chr: Banks::new(0x0000, 0x1FFF, 0x2000, if chr { chr_rom } else { vec![0; 0x2000] }, !chr),
-      prg_ram: Banks::new(0x6000, 0x7FFF, 0x2000, vec![0; 0x2000], true),
+      mirroring: header.mirroring,
+      battery: header.battery,
+      chr: Banks::new(0x0000, 0x1FFF, 0x2000, if chr { chr_rom } else { vec![0; header.chr_ram()] }, !chr),
+      prg_ram: Banks::new(0x6000, 0x7FFF, 0x2000, vec![0; header.prg_ram()], true),
       prg_rom: Banks::new(0x8000, 0xFFFF, PRG_ROM_BANK_SIZE, prg_rom, false),
     };
 
@@ -49,3 +63,19 @@ impl Mapper2 {
     mapper
   }
 }
+
+impl Snapshot for Mapper2 {
+  fn save(&self, state: &mut Writer) {
+    self.mirroring.save(state);
+    self.chr.save(state);
+    self.prg_ram.save(state);
+    self.prg_rom.save(state);
+  }
+
+  fn load(&mut self, state: &mut Reader) -> Result<(), StateError> {
+    self.mirroring.load(state)?;
+    self.chr.load(state)?;
+    self.prg_ram.load(state)?;
+    self.prg_rom.load(state)
+  }
+}
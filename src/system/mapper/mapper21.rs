@@ -0,0 +1,328 @@
+use crate::savestate::{Reader, Snapshot, StateError, Writer};
+
+use super::{banks::Banks, vrcirq::VRCIRQ, Mapper, Mirroring, RomHeader};
+
+const PRG_BANK_SIZE: usize = 0x2000;
+const CHR_BANK_SIZE: usize = 0x400;
+
+// VRC2 and VRC4 boards (mappers 21, 22, 23 and 25) connect the two register
+// select lines to different CPU address lines. Each wiring is stored as the
+// pair of address bits that drive the low and high select lines.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Wiring {
+  lo: u16,
+  hi: u16,
+  vrc2: bool,
+}
+
+const VRC2A: Wiring = Wiring { lo: 0x02, hi: 0x01, vrc2: true };
+const VRC2B: Wiring = Wiring { lo: 0x01, hi: 0x02, vrc2: true };
+const VRC2C: Wiring = Wiring { lo: 0x02, hi: 0x01, vrc2: true };
+const VRC4A: Wiring = Wiring { lo: 0x02, hi: 0x04, vrc2: false };
+const VRC4B: Wiring = Wiring { lo: 0x02, hi: 0x01, vrc2: false };
+const VRC4C: Wiring = Wiring { lo: 0x40, hi: 0x80, vrc2: false };
+const VRC4D: Wiring = Wiring { lo: 0x08, hi: 0x04, vrc2: false };
+const VRC4E: Wiring = Wiring { lo: 0x04, hi: 0x08, vrc2: false };
+const VRC4F: Wiring = Wiring { lo: 0x01, hi: 0x02, vrc2: false };
+
+pub struct Mapper21 {
+  mirroring: Mirroring,
+  battery: bool,
+  wiring: Wiring,
+  chr_shift: u8,
+
+  chr: Banks,
+  prg_ram: Banks,
+  prg_rom: Banks,
+
+  prg_banks: [u8; 0x02],
+  chr_banks: [u16; 0x08],
+  swap_mode: bool,
+  latch: u8,
+
+  irq: VRCIRQ,
+}
+
+impl Mapper for Mapper21 {
+  fn mirroring(&self) -> Mirroring {
+    self.mirroring
+  }
+
+  fn read(&self, addr: u16) -> Option<u8> {
+    match addr {
+      0x0000 ..= 0x1FFF => self.chr.read(addr),
+      // VRC2 boards without WRAM still have a one bit latch at $6000
+      0x6000 ..= 0x6FFF if self.prg_ram.capacity() == 0 && self.wiring.vrc2 => Some(self.latch),
+      0x6000 ..= 0x7FFF => self.prg_ram.read(addr),
+      0x8000 ..= 0xFFFF => self.prg_rom.read(addr),
+      _ => None,
+    }
+  }
+
+  fn write(&mut self, addr: u16, val: u8) {
+    match addr {
+      0x0000 ..= 0x1FFF => self.chr.write(addr, val),
+      0x6000 ..= 0x6FFF if self.prg_ram.capacity() == 0 && self.wiring.vrc2 => self.latch = val & 0x01,
+      0x6000 ..= 0x7FFF => self.prg_ram.write(addr, val),
+      0x8000 ..= 0xFFFF => self.write_registers(self.translate(addr), val),
+      _ => { },
+    }
+  }
+
+  fn tick(&mut self) {
+    if !self.wiring.vrc2 {
+      self.irq.tick();
+    }
+  }
+
+  fn poll(&self) -> bool {
+    self.irq.poll()
+  }
+
+  fn battery_ram(&self) -> Option<&[u8]> {
+    self.battery.then(|| self.prg_ram.memory())
+  }
+
+  fn load_battery_ram(&mut self, data: &[u8]) {
+    if self.battery {
+      self.prg_ram.restore(data);
+    }
+  }
+}
+
+impl Mapper21 {
+  pub fn new(header: &RomHeader, chr_rom: Vec<u8>, prg_rom: Vec<u8>) -> Self {
+    let chr = !chr_rom.is_empty();
+    let wiring = Mapper21::wiring(header, &prg_rom);
+
+    let mut mapper = Mapper21 {
+      mirroring: header.mirroring,
+      battery: header.battery,
+      wiring,
+      // VRC2a ignores the lowest bit of each CHR bank
+      chr_shift: if header.mapper == 22 { 1 } else { 0 },
+      chr: Banks::new(0x0000, 0x1FFF, CHR_BANK_SIZE, if chr { chr_rom } else { vec![0; header.chr_ram()] }, !chr),
+      prg_ram: Banks::new(0x6000, 0x7FFF, PRG_BANK_SIZE, vec![0; header.prg_ram()], true),
+      prg_rom: Banks::new(0x8000, 0xFFFF, PRG_BANK_SIZE, prg_rom, false),
+      prg_banks: [0x0, 0x1],
+      chr_banks: [0x0; 0x08],
+      swap_mode: false,
+      latch: 0x0,
+      irq: VRCIRQ::new(),
+    };
+
+    mapper.update_prg();
+    mapper
+  }
+
+  fn wiring(header: &RomHeader, prg_rom: &[u8]) -> Wiring {
+    match (header.mapper, header.submapper) {
+      (21, 1) => VRC4A,
+      (21, 2) => VRC4C,
+      (21, _) => Mapper21::detect(prg_rom, VRC4A, VRC4C, None),
+      (22, _) => VRC2A,
+      (23, 1) => VRC4F,
+      (23, 2) => VRC4E,
+      (23, 3) => VRC2B,
+      (23, _) => Mapper21::detect(prg_rom, VRC4F, VRC4E, Some(VRC2B)),
+      (25, 1) => VRC4B,
+      (25, 2) => VRC4D,
+      (25, 3) => VRC2C,
+      _ => Mapper21::detect(prg_rom, VRC4B, VRC4D, Some(VRC2C)),
+    }
+  }
+
+  // iNES headers don't say which board a game uses, so look for absolute
+  // stores to the register space and see which address lines they toggle.
+  // If both wirings appear the lines are combined, which works for nearly
+  // every game. A VRC2 shares its wiring with the first VRC4 board, and is
+  // picked when the game never touches the IRQ registers.
+  fn detect(prg_rom: &[u8], first: Wiring, second: Wiring, vrc2: Option<Wiring>) -> Wiring {
+    let lines = first.lo | first.hi | second.lo | second.hi;
+
+    let writes: Vec<u16> = prg_rom.windows(3)
+      .filter(|op| matches!(op[0], 0x8C ..= 0x8E) && op[2] >= 0x80)
+      .map(|op| u16::from_le_bytes([op[1], op[2]]))
+      .filter(|addr| addr & 0x0FFF & !lines == 0)
+      .collect();
+
+    let uses = |wiring: &Wiring| writes.iter().any(|addr| addr & (wiring.lo | wiring.hi) != 0);
+    let irq = writes.iter().any(|addr| addr & 0xF000 == 0xF000);
+
+    match (uses(&first), uses(&second)) {
+      (true, false) => vrc2.filter(|_| !irq).unwrap_or(first),
+      (false, true) => second,
+      _ => Wiring { lo: first.lo | second.lo, hi: first.hi | second.hi, vrc2: false },
+    }
+  }
+
+  fn translate(&self, addr: u16) -> u16 {
+    let lo = if addr & self.wiring.lo != 0 { 0x1 } else { 0x0 };
+    let hi = if addr & self.wiring.hi != 0 { 0x2 } else { 0x0 };
+    (addr & 0xF000) | hi | lo
+  }
+
+  fn update_prg(&mut self) {
+    let second_last = self.prg_rom.last().saturating_sub(1);
+
+    match self.swap_mode {
+      true => {
+        self.prg_rom.set(0, second_last);
+        self.prg_rom.set(2, self.prg_banks[0] as usize);
+      }
+      false => {
+        self.prg_rom.set(0, self.prg_banks[0] as usize);
+        self.prg_rom.set(2, second_last);
+      }
+    }
+
+    self.prg_rom.set(1, self.prg_banks[1] as usize);
+    self.prg_rom.set(3, self.prg_rom.last());
+  }
+
+  fn write_chr(&mut self, addr: u16, val: u8) {
+    let reg = (((addr - 0xB000) >> 12) * 2 + ((addr & 0x02) >> 1)) as usize;
+    let bank = self.chr_banks[reg];
+
+    self.chr_banks[reg] = match addr & 0x01 {
+      0 => (bank & 0x1F0) | (val as u16 & 0x0F),
+      _ => (bank & 0x00F) | ((val as u16 & 0x1F) << 4),
+    };
+
+    self.chr.set(reg, (self.chr_banks[reg] >> self.chr_shift) as usize);
+  }
+
+  fn write_registers(&mut self, addr: u16, val: u8) {
+    match addr {
+      0x8000 ..= 0x8003 => {
+        self.prg_banks[0] = val & 0x1F;
+        self.update_prg();
+      }
+      0x9000 ..= 0x9003 if self.wiring.vrc2 => {
+        self.mirroring = match val & 0x01 {
+          0 => Mirroring::Vertical,
+          _ => Mirroring::Horizontal,
+        };
+      }
+      0x9000 | 0x9001 => {
+        self.mirroring = match val & 0x03 {
+          0 => Mirroring::Vertical,
+          1 => Mirroring::Horizontal,
+          2 => Mirroring::Single0,
+          _ => Mirroring::Single1,
+        };
+      }
+      0x9002 | 0x9003 => {
+        self.swap_mode = val & 0x02 == 0x02;
+        self.update_prg();
+      }
+      0xA000 ..= 0xA003 => {
+        self.prg_banks[1] = val & 0x1F;
+        self.update_prg();
+      }
+      0xB000 ..= 0xEFFF => self.write_chr(addr, val),
+      _ if self.wiring.vrc2 => { },
+      0xF000 => self.irq.write_latch_lo(val),
+      0xF001 => self.irq.write_latch_hi(val),
+      0xF002 => self.irq.write_control(val),
+      0xF003 => self.irq.acknowledge(),
+      _ => { },
+    }
+  }
+}
+
+impl Snapshot for Mapper21 {
+  fn save(&self, state: &mut Writer) {
+    self.mirroring.save(state);
+    self.chr.save(state);
+    self.prg_ram.save(state);
+    self.prg_rom.save(state);
+
+    state.bytes(&self.prg_banks);
+    for bank in self.chr_banks {
+      state.u16(bank);
+    }
+    state.bool(self.swap_mode);
+    state.u8(self.latch);
+
+    self.irq.save(state);
+  }
+
+  fn load(&mut self, state: &mut Reader) -> Result<(), StateError> {
+    self.mirroring.load(state)?;
+    self.chr.load(state)?;
+    self.prg_ram.load(state)?;
+    self.prg_rom.load(state)?;
+
+    state.bytes_into(&mut self.prg_banks, "VRC2/VRC4 PRG banks")?;
+    for bank in self.chr_banks.iter_mut() {
+      *bank = state.u16()?;
+    }
+    self.swap_mode = state.bool()?;
+    self.latch = state.u8()?;
+
+    self.irq.load(state)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{Mapper21, Wiring, VRC2A, VRC2B, VRC4A, VRC4C, VRC4D, VRC4E, VRC4F};
+  use crate::system::{cartridge::RomHeader, mapper::Mapper};
+
+  // NES 2.0 header for 128K of PRG ROM and CHR RAM without any PRG RAM
+  fn header(mapper: u16, submapper: u8) -> RomHeader {
+    let flags_6 = (mapper as u8) << 4;
+    let flags_7 = (mapper as u8 & 0xF0) | 0x08;
+    let header = [b"NES\x1A".as_slice(), &[0x08, 0x00, flags_6, flags_7, submapper << 4, 0x00, 0x00, 0x07], &[0x00; 4]].concat();
+    RomHeader::new(&header).unwrap()
+  }
+
+  // PRG ROM that stores to each of the given addresses
+  fn program(writes: &[u16]) -> Vec<u8> {
+    let mut prg = writes.iter().flat_map(|addr| [0x8D, *addr as u8, (addr >> 8) as u8]).collect::<Vec<_>>();
+    prg.resize(0x20000, 0x00);
+    prg
+  }
+
+  fn wiring(mapper: u16, submapper: u8, writes: &[u16]) -> Wiring {
+    Mapper21::wiring(&header(mapper, submapper), &program(writes))
+  }
+
+  #[test]
+  fn submappers_name_the_wiring() {
+    assert_eq!(wiring(21, 1, &[0x9080]), VRC4A);
+    assert_eq!(wiring(21, 2, &[0x9004]), VRC4C);
+    assert_eq!(wiring(22, 0, &[]), VRC2A);
+    assert_eq!(wiring(23, 3, &[0xF008]), VRC2B);
+    assert_eq!(wiring(25, 2, &[]), VRC4D);
+  }
+
+  #[test]
+  fn ines_wiring_follows_the_stores() {
+    assert_eq!(wiring(21, 0, &[0x9040, 0xB080]), VRC4C);
+    assert_eq!(wiring(23, 0, &[0x9004, 0xF008]), VRC4E);
+    assert_eq!(wiring(23, 0, &[0x9001, 0xF002]), VRC4F);
+  }
+
+  #[test]
+  fn ines_wiring_without_irq_stores_is_vrc2() {
+    assert_eq!(wiring(23, 0, &[0x9001, 0xB002]), VRC2B);
+  }
+
+  #[test]
+  fn ambiguous_ines_wiring_combines_the_lines() {
+    let combined = Wiring { lo: 0x05, hi: 0x0A, vrc2: false };
+
+    assert_eq!(wiring(23, 0, &[0x9001, 0x9004]), combined);
+    assert_eq!(wiring(23, 0, &[]), combined);
+  }
+
+  #[test]
+  fn vrc2_latches_a_bit_without_prg_ram() {
+    let mut mapper = Mapper21::new(&header(23, 3), vec![], program(&[]));
+
+    mapper.write(0x6000, 0xFF);
+    assert_eq!(mapper.read(0x6000), Some(0x01));
+  }
+}
@@ -0,0 +1,111 @@
+use crate::savestate::{Reader, Snapshot, StateError, Writer};
+
+use super::{banks::Banks, Mapper, Mirroring, RomHeader};
+
+const PRG_ROM_BANK_SIZE: usize = 0x4000;
+
+// Camerica Quattro. $8000 selects a 64K block and $C000 a 16K bank within
+// it, while the last bank of the block stays at $C000. The Aladdin Deck
+// Enhancer (submapper 1) has the two block bits swapped.
+pub struct Mapper232 {
+  mirroring: Mirroring,
+  battery: bool,
+
+  chr: Banks,
+  prg_ram: Banks,
+  prg_rom: Banks,
+
+  aladdin: bool,
+  block: usize,
+  bank: usize,
+}
+
+impl Mapper for Mapper232 {
+  fn mirroring(&self) -> Mirroring {
+    self.mirroring
+  }
+
+  fn read(&self, addr: u16) -> Option<u8> {
+    match addr {
+      0x0000 ..= 0x1FFF => self.chr.read(addr),
+      0x6000 ..= 0x7FFF => self.prg_ram.read(addr),
+      0x8000 ..= 0xFFFF => self.prg_rom.read(addr),
+      _ => None,
+    }
+  }
+
+  fn write(&mut self, addr: u16, val: u8) {
+    match addr {
+      0x0000 ..= 0x1FFF => self.chr.write(addr, val),
+      0x6000 ..= 0x7FFF => self.prg_ram.write(addr, val),
+      0x8000 ..= 0xBFFF => {
+        self.block = match self.aladdin {
+          true => ((val as usize >> 4) & 0x01) | ((val as usize >> 2) & 0x02),
+          false => (val as usize >> 3) & 0x03,
+        };
+        self.update_prg();
+      }
+      0xC000 ..= 0xFFFF => {
+        self.bank = val as usize & 0x03;
+        self.update_prg();
+      }
+      _ => { },
+    }
+  }
+
+  fn battery_ram(&self) -> Option<&[u8]> {
+    self.battery.then(|| self.prg_ram.memory())
+  }
+
+  fn load_battery_ram(&mut self, data: &[u8]) {
+    if self.battery {
+      self.prg_ram.restore(data);
+    }
+  }
+}
+
+impl Mapper232 {
+  pub fn new(header: &RomHeader, chr_rom: Vec<u8>, prg_rom: Vec<u8>) -> Self {
+    let chr = !chr_rom.is_empty();
+
+    let mut mapper = Mapper232 {
+      mirroring: header.mirroring,
+      battery: header.battery,
+      chr: Banks::new(0x0000, 0x1FFF, 0x2000, if chr { chr_rom } else { vec![0; header.chr_ram()] }, !chr),
+      prg_ram: Banks::new(0x6000, 0x7FFF, 0x2000, vec![0; header.prg_ram()], true),
+      prg_rom: Banks::new(0x8000, 0xFFFF, PRG_ROM_BANK_SIZE, prg_rom, false),
+      aladdin: header.submapper == 1,
+      block: 0x0,
+      bank: 0x0,
+    };
+
+    mapper.update_prg();
+    mapper
+  }
+
+  fn update_prg(&mut self) {
+    self.prg_rom.set(0, self.block * 4 + self.bank);
+    self.prg_rom.set(1, self.block * 4 + 3);
+  }
+}
+
+impl Snapshot for Mapper232 {
+  fn save(&self, state: &mut Writer) {
+    self.mirroring.save(state);
+    self.chr.save(state);
+    self.prg_ram.save(state);
+    self.prg_rom.save(state);
+    state.usize(self.block);
+    state.usize(self.bank);
+  }
+
+  fn load(&mut self, state: &mut Reader) -> Result<(), StateError> {
+    self.mirroring.load(state)?;
+    self.chr.load(state)?;
+    self.prg_ram.load(state)?;
+    self.prg_rom.load(state)?;
+    self.block = state.usize()?;
+    self.bank = state.usize()?;
+    Ok(())
+  }
+}
@@ -0,0 +1,147 @@
+use crate::savestate::{Reader, Snapshot, StateError, Writer};
+
+use super::{banks::Banks, vrc6audio::VRC6Audio, vrcirq::VRCIRQ, Mapper, Mirroring, RomHeader};
+
+const CHR_BANK_SIZE: usize = 0x400;
+
+// VRC6a (mapper 24) and VRC6b (mapper 26) differ only in having A0 and A1
+// swapped.
+pub struct Mapper24 {
+  mirroring: Mirroring,
+  battery: bool,
+  swapped: bool,
+
+  chr: Banks,
+  prg_ram: Banks,
+  prg_rom: Banks,
+  ram_enabled: bool,
+
+  irq: VRCIRQ,
+  audio: VRC6Audio,
+}
+
+impl Mapper for Mapper24 {
+  fn mirroring(&self) -> Mirroring {
+    self.mirroring
+  }
+
+  fn read(&self, addr: u16) -> Option<u8> {
+    match addr {
+      0x0000 ..= 0x1FFF => self.chr.read(addr),
+      0x6000 ..= 0x7FFF if self.ram_enabled => self.prg_ram.read(addr),
+      0x8000 ..= 0xFFFF => self.prg_rom.read(addr),
+      _ => None,
+    }
+  }
+
+  fn write(&mut self, addr: u16, val: u8) {
+    match addr {
+      0x0000 ..= 0x1FFF => self.chr.write(addr, val),
+      0x6000 ..= 0x7FFF if self.ram_enabled => self.prg_ram.write(addr, val),
+      0x8000 ..= 0xFFFF => self.write_registers(self.translate(addr), val),
+      _ => { },
+    }
+  }
+
+  fn tick(&mut self) {
+    self.irq.tick();
+    self.audio.tick();
+  }
+
+  fn signal(&self) -> f32 {
+    self.audio.signal()
+  }
+
+  fn mute(&mut self, channel: u8, muted: bool) {
+    self.audio.mute(channel, muted);
+  }
+
+  fn poll(&self) -> bool {
+    self.irq.poll()
+  }
+
+  fn battery_ram(&self) -> Option<&[u8]> {
+    self.battery.then(|| self.prg_ram.memory())
+  }
+
+  fn load_battery_ram(&mut self, data: &[u8]) {
+    if self.battery {
+      self.prg_ram.restore(data);
+    }
+  }
+}
+
+impl Mapper24 {
+  pub fn new(header: &RomHeader, chr_rom: Vec<u8>, prg_rom: Vec<u8>) -> Self {
+    let chr = !chr_rom.is_empty();
+
+    let mut mapper = Mapper24 {
+      mirroring: header.mirroring,
+      battery: header.battery,
+      swapped: header.mapper == 26,
+      chr: Banks::new(0x0000, 0x1FFF, CHR_BANK_SIZE, if chr { chr_rom } else { vec![0; header.chr_ram()] }, !chr),
+      prg_ram: Banks::new(0x6000, 0x7FFF, 0x2000, vec![0; header.prg_ram()], true),
+      prg_rom: Banks::new(0x8000, 0xFFFF, 0x2000, prg_rom, false),
+      ram_enabled: false,
+      irq: VRCIRQ::new(),
+      audio: VRC6Audio::new(),
+    };
+
+    mapper.prg_rom.set(3, mapper.prg_rom.last());
+    mapper
+  }
+
+  fn translate(&self, addr: u16) -> u16 {
+    match self.swapped {
+      true => (addr & 0xF000) | ((addr & 0x01) << 1) | ((addr & 0x02) >> 1),
+      false => addr & 0xF003,
+    }
+  }
+
+  fn write_registers(&mut self, addr: u16, val: u8) {
+    match addr {
+      0x8000 ..= 0x8003 => self.prg_rom.set_range(0, 1, (val as usize & 0x0F) * 2),
+      0x9000 ..= 0xB002 => self.audio.write(addr, val),
+      0xB003 => {
+        self.ram_enabled = val & 0x80 == 0x80;
+        self.mirroring = match val & 0x0C {
+          0x0 => Mirroring::Vertical,
+          0x4 => Mirroring::Horizontal,
+          0x8 => Mirroring::Single0,
+          _ => Mirroring::Single1,
+        };
+      }
+      0xC000 ..= 0xC003 => self.prg_rom.set(2, val as usize & 0x1F),
+      0xD000 ..= 0xD003 => self.chr.set((addr & 0x03) as usize, val as usize),
+      0xE000 ..= 0xE003 => self.chr.set(4 + (addr & 0x03) as usize, val as usize),
+      0xF000 => self.irq.write_latch(val),
+      0xF001 => self.irq.write_control(val),
+      0xF002 => self.irq.acknowledge(),
+      _ => { },
+    }
+  }
+}
+
+impl Snapshot for Mapper24 {
+  fn save(&self, state: &mut Writer) {
+    self.mirroring.save(state);
+    self.chr.save(state);
+    self.prg_ram.save(state);
+    self.prg_rom.save(state);
+    state.bool(self.ram_enabled);
+
+    self.irq.save(state);
+    self.audio.save(state);
+  }
+
+  fn load(&mut self, state: &mut Reader) -> Result<(), StateError> {
+    self.mirroring.load(state)?;
+    self.chr.load(state)?;
+    self.prg_ram.load(state)?;
+    self.prg_rom.load(state)?;
+    self.ram_enabled = state.bool()?;
+
+    self.irq.load(state)?;
+    self.audio.load(state)
+  }
+}
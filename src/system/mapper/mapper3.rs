@@ -1,4 +1,6 @@
-use super::{banks::Banks, Mapper, Mirroring};
+use crate::savestate::{Reader, Snapshot, StateError, Writer};
+
+use super::{banks::Banks, Mapper, Mirroring, RomHeader};
 
 const CHR_BANK_SIZE: usize = 0x2000;
 
@@ -14,11 +16,11 @@ impl Mapper for Mapper3 {
     self.mirroring
   }
 
-  fn read(&self, addr: u16) -> u8 {
+  fn read(&self, addr: u16) -> Option<u8> {
     match addr {
       0x0000 ..= 0x1FFF => self.chr.read(addr),
       0x8000 ..= 0xFFFF => self.prg_rom.read(addr),
-      _ => 0,
+      _ => None,
     }
   }
 
@@ -32,13 +34,27 @@ impl Mapper for Mapper3 {
 }
 
 impl Mapper3 {
-  pub fn new(chr_rom: Vec<u8>, prg_rom: Vec<u8>, mirroring: Mirroring) -> Self {
+  pub fn new(header: &RomHeader, chr_rom: Vec<u8>, prg_rom: Vec<u8>) -> Self {
     let chr = !chr_rom.is_empty();
 
     Mapper3 {
-      mirroring,
-      chr: Banks::new(0x0000, 0x1FFF, CHR_BANK_SIZE, if chr { chr_rom } else { vec![0; 0x2000] }, !chr),
+      mirroring: header.mirroring,
+      chr: Banks::new(0x0000, 0x1FFF, CHR_BANK_SIZE, if chr { chr_rom } else { vec![0; header.chr_ram()] }, !chr),
       prg_rom: Banks::new(0x8000, 0xFFFF, 0x4000, prg_rom, false),
     }
   }
 }
+
+impl Snapshot for Mapper3 {
+  fn save(&self, state: &mut Writer) {
+    self.mirroring.save(state);
+    self.chr.save(state);
+    self.prg_rom.save(state);
+  }
+
+  fn load(&mut self, state: &mut Reader) -> Result<(), StateError> {
+    self.mirroring.load(state)?;
+    self.chr.load(state)?;
+    self.prg_rom.load(state)
+  }
+}
@@ -1,23 +1,40 @@
-use super::{banks::Banks, Mapper, MapperEvent, Mirroring};
+use crate::savestate::{Reader, Snapshot, StateError, Writer};
+use crate::system::cartridge::Format;
+
+use super::{banks::Banks, Mapper, MapperEvent, Mirroring, RomHeader};
 
 const PRG_BANK_SIZE: usize = 0x2000;
 const CHR_BANK_SIZE: usize = 0x400;
+const MMC6_RAM_SIZE: usize = 0x400;
+
+// TxSROM (118) takes nametable selection from bit 7 of the CHR banks, and
+// TQROM (119) maps CHR RAM into any slot whose bank has bit 6 set.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Board {
+  MMC3, MMC6, TxSROM, TQROM,
+}
 
 pub struct Mapper4 {
   mirroring: Mirroring,
+  battery: bool,
+  board: Board,
 
   chr: Banks,
+  chr_ram: Banks,
   prg_ram: Banks,
   prg_rom: Banks,
 
   select: u8,
   registers: [u8; 0x08],
+  protect: u8,
+  protectable: bool,
 
   irq: IRQ,
   last: bool,
 }
 
 struct IRQ {
+  alternate: bool,
   enabled: bool,
   pending: bool,
   reload: bool,
@@ -28,6 +45,7 @@ struct IRQ {
 impl IRQ {
   fn new() -> Self {
     IRQ {
+      alternate: false,
       enabled: false,
       pending: false,
       reload: false,
@@ -42,19 +60,23 @@ impl Mapper for Mapper4 {
     self.mirroring
   }
 
-  fn read(&self, addr: u16) -> u8 {
+  fn read(&self, addr: u16) -> Option<u8> {
     match addr {
+      0x0000 ..= 0x1FFF if self.chr_ram_mapped(addr) => self.chr_ram.read(addr),
       0x0000 ..= 0x1FFF => self.chr.read(addr),
-      0x6000 ..= 0x7FFF => self.prg_ram.read(addr),
+      0x6000 ..= 0x7FFF if self.board == Board::MMC6 => self.mmc6_read(addr),
+      0x6000 ..= 0x7FFF if self.protect & 0x80 == 0x80 => self.prg_ram.read(addr),
       0x8000 ..= 0xFFFF => self.prg_rom.read(addr),
-      _ => 0,
+      _ => None,
     }
   }
 
   fn write(&mut self, addr: u16, val: u8) {
     match addr {
+      0x0000 ..= 0x1FFF if self.chr_ram_mapped(addr) => self.chr_ram.write(addr, val),
       0x0000 ..= 0x1FFF => self.chr.write(addr, val),
-      0x6000 ..= 0x7FFF => self.prg_ram.write(addr, val),
+      0x6000 ..= 0x7FFF if self.board == Board::MMC6 => self.mmc6_write(addr, val),
+      0x6000 ..= 0x7FFF if self.protect & 0xC0 == 0x80 => self.prg_ram.write(addr, val),
       0x8000 ..= 0xFFFF => self.write_registers(addr, val),
       _ => { },
     }
@@ -72,32 +94,85 @@ impl Mapper for Mapper4 {
 
         self.last = next;
       }
+      _ => { },
+    }
+  }
+
+  fn read_nametable(&mut self, addr: u16, vram: &[u8]) -> u8 {
+    match self.board {
+      Board::TxSROM => vram[self.txsrom_index(addr)],
+      _ => vram[self.mirroring.index(addr)],
+    }
+  }
+
+  fn write_nametable(&mut self, addr: u16, val: u8, vram: &mut [u8]) {
+    match self.board {
+      Board::TxSROM => vram[self.txsrom_index(addr)] = val,
+      _ => vram[self.mirroring.index(addr)] = val,
     }
   }
 
   fn poll(&self) -> bool {
     self.irq.pending
   }
+
+  fn battery_ram(&self) -> Option<&[u8]> {
+    self.battery.then(|| self.prg_ram.memory())
+  }
+
+  fn load_battery_ram(&mut self, data: &[u8]) {
+    if self.battery {
+      self.prg_ram.restore(data);
+    }
+  }
 }
 
 impl Mapper4 {
-  pub fn new(chr_rom: Vec<u8>, prg_rom: Vec<u8>, mirroring: Mirroring) -> Self {
+  pub fn new(header: &RomHeader, chr_rom: Vec<u8>, prg_rom: Vec<u8>) -> Self {
     let chr = !chr_rom.is_empty();
 
+    let board = match (header.mapper, header.submapper) {
+      (118, _) => Board::TxSROM,
+      (119, _) => Board::TQROM,
+      (_, 1) => Board::MMC6,
+      _ => Board::MMC3,
+    };
+
+    let prg_ram = match board {
+      Board::MMC6 => MMC6_RAM_SIZE,
+      _ => header.prg_ram(),
+    };
+
+    let chr_ram = match board {
+      Board::TQROM => std::cmp::max(0x2000, header.chr_ram()),
+      _ => 0,
+    };
+
     let mut mapper = Mapper4 {
-      mirroring,
-      chr: Banks::new(0x0000, 0x1FFFF, CHR_BANK_SIZE, if chr { chr_rom } else { vec![0; 0x2000] }, !chr),
-      prg_ram: Banks::new(0x6000, 0x7FFF, PRG_BANK_SIZE, vec![0; 0x2000], true),
+      mirroring: header.mirroring,
+      battery: header.battery,
+      board,
+      chr: Banks::new(0x0000, 0x1FFFF, CHR_BANK_SIZE, if chr { chr_rom } else { vec![0; header.chr_ram()] }, !chr),
+      chr_ram: Banks::new(0x0000, 0x1FFF, CHR_BANK_SIZE, vec![0; chr_ram], true),
+      prg_ram: Banks::new(0x6000, 0x7FFF, PRG_BANK_SIZE, vec![0; prg_ram], true),
       prg_rom: Banks::new(0x8000, 0xFFFF, PRG_BANK_SIZE, prg_rom, false),
 
       select: 0x0,
       registers: [0; 0x08],
+      protect: if board == Board::MMC6 { 0x0 } else { 0x80 },
+      // An iNES header can't tell MMC3 from MMC6, whose games write
+      // protect values that would lock an MMC3 out of its own RAM.
+      protectable: header.format != Format::INES,
 
       irq: IRQ::new(),
       last: false,
     };
 
-    mapper.prg_rom.set(2, mapper.prg_rom.last() - 1);
+    // Submapper 4 is the MMC3A, which only raises an IRQ when the counter
+    // reaches zero from a nonzero value or a forced reload.
+    mapper.irq.alternate = header.submapper == 4;
+
+    mapper.prg_rom.set(2, mapper.prg_rom.last().saturating_sub(1));
     mapper.prg_rom.set(3, mapper.prg_rom.last());
     mapper
   }
@@ -105,28 +180,80 @@ impl Mapper4 {
   fn update_banks(&mut self) {
     if self.select & 0x40 == 0x0 {
       self.prg_rom.set(0, self.registers[6] as usize);
-      self.prg_rom.set(2, self.prg_rom.last() - 1);
+      self.prg_rom.set(2, self.prg_rom.last().saturating_sub(1));
     } else {
-      self.prg_rom.set(0, self.prg_rom.last() - 1);
+      self.prg_rom.set(0, self.prg_rom.last().saturating_sub(1));
       self.prg_rom.set(2, self.registers[6] as usize);
     }
     self.prg_rom.set(1, self.registers[7] as usize);
     self.prg_rom.set(3, self.prg_rom.last());
 
-    if self.select & 0x80 == 0x0 {
-      self.chr.set_range(0, 1, self.registers[0] as usize & 0xFE);
-      self.chr.set_range(2, 3, self.registers[1] as usize & 0xFE);
-      self.chr.set(4, self.registers[2] as usize);
-      self.chr.set(5, self.registers[3] as usize);
-      self.chr.set(6, self.registers[4] as usize);
-      self.chr.set(7, self.registers[5] as usize);
-    } else {
-      self.chr.set(0, self.registers[2] as usize);
-      self.chr.set(1, self.registers[3] as usize);
-      self.chr.set(2, self.registers[4] as usize);
-      self.chr.set(3, self.registers[5] as usize);
-      self.chr.set_range(4, 5, self.registers[0] as usize & 0xFE);
-      self.chr.set_range(6, 7, self.registers[1] as usize & 0xFE);
+    for slot in 0 .. 8 {
+      let bank = self.chr_bank(slot);
+
+      match self.board {
+        Board::TQROM => {
+          self.chr.set(slot, bank & 0x3F);
+          self.chr_ram.set(slot, bank & 0x07);
+        }
+        _ => self.chr.set(slot, bank),
+      }
+    }
+  }
+
+  // The two 2K banks cover either the lower or the upper pattern table
+  fn chr_bank(&self, slot: usize) -> usize {
+    let slot = if self.select & 0x80 == 0x80 { slot ^ 0x04 } else { slot };
+
+    match slot {
+      0 ..= 3 => (self.registers[slot / 2] as usize & 0xFE) | (slot & 0x01),
+      _ => self.registers[slot - 2] as usize,
+    }
+  }
+
+  fn chr_ram_mapped(&self, addr: u16) -> bool {
+    self.board == Board::TQROM && self.chr_bank(addr as usize >> 10) & 0x40 == 0x40
+  }
+
+  fn txsrom_index(&self, addr: u16) -> usize {
+    let addr = (addr as usize - 0x2000) % 0x1000;
+    let page = self.chr_bank(addr / 0x400) >> 7;
+    page * 0x400 + addr % 0x400
+  }
+
+  // MMC6 has 1K of RAM at $7000, mirrored, with separate read and write
+  // enables for each 512 byte half. It only responds while enabled in $8000.
+  fn mmc6_enables(&self, addr: u16) -> (bool, bool) {
+    let bits = if addr & 0x200 == 0x200 { self.protect >> 6 } else { self.protect >> 4 };
+    (bits & 0x01 == 0x01, bits & 0x02 == 0x02)
+  }
+
+  fn mmc6_read(&self, addr: u16) -> Option<u8> {
+    let (read, _) = self.mmc6_enables(addr);
+    let (other, _) = self.mmc6_enables(addr ^ 0x200);
+
+    match addr {
+      0x7000 ..= 0x7FFF if self.select & 0x20 == 0x20 && read => self.prg_ram.read(0x6000 | (addr & 0x3FF)),
+      // A disabled half only reads as zero while the other half is readable
+      0x7000 ..= 0x7FFF if self.select & 0x20 == 0x20 && other => Some(0),
+      _ => None,
+    }
+  }
+
+  fn mmc6_write(&mut self, addr: u16, val: u8) {
+    let (read, write) = self.mmc6_enables(addr);
+
+    if (0x7000 ..= 0x7FFF).contains(&addr) && self.select & 0x20 == 0x20 && read && write {
+      self.prg_ram.write(0x6000 | (addr & 0x3FF), val);
+    }
+  }
+
+  fn write_protect(&mut self, val: u8) {
+    match self.board {
+      Board::MMC6 if self.select & 0x20 == 0x0 => { },
+      Board::MMC6 => self.protect = val,
+      _ if self.protectable => self.protect = val,
+      _ => { },
     }
   }
 
@@ -135,7 +262,7 @@ impl Mapper4 {
       0x8000 ..= 0x9FFF if addr % 2 == 0 => self.bank_select(val),
       0x8000 ..= 0x9FFF if addr % 2 != 0 => self.bank_data(val),
       0xA000 ..= 0xBFFF if addr % 2 == 0 => self.set_mirroring(val),
-      0xA000 ..= 0xBFFF if addr % 2 != 0 => { /* PRG RAM protect ?! */ },
+      0xA000 ..= 0xBFFF if addr % 2 != 0 => self.write_protect(val),
       0xC000 ..= 0xDFFF if addr % 2 == 0 => self.irq_latch(val),
       0xC000 ..= 0xDFFF if addr % 2 != 0 => self.irq_reload(),
       0xE000 ..= 0xFFFF if addr % 2 == 0 => self.irq_disable(),
@@ -145,13 +272,20 @@ impl Mapper4 {
   }
 
   fn irq_tick(&mut self) {
+    let previous = self.irq.counter;
+
     if self.irq.counter == 0 || self.irq.reload {
       self.irq.counter = self.irq.latch;
     } else {
       self.irq.counter -= 1;
     }
 
-    if self.irq.counter == 0 && self.irq.enabled {
+    let fire = match self.irq.alternate {
+      true => self.irq.counter == 0 && (previous != 0 || self.irq.reload),
+      false => self.irq.counter == 0,
+    };
+
+    if fire && self.irq.enabled {
       self.irq.pending = true;
     }
 
@@ -170,6 +304,7 @@ impl Mapper4 {
 
   fn set_mirroring(&mut self, val: u8) {
     match self.mirroring {
+      _ if self.board == Board::TxSROM => { }
       Mirroring::FourScreen =>  { }
       _ => {
         self.mirroring = match val & 0x01 == 0x01  {
@@ -198,3 +333,44 @@ impl Mapper4 {
     self.irq.enabled = true;
   }
 }
+
+impl Snapshot for Mapper4 {
+  fn save(&self, state: &mut Writer) {
+    self.mirroring.save(state);
+    self.chr.save(state);
+    self.chr_ram.save(state);
+    self.prg_ram.save(state);
+    self.prg_rom.save(state);
+
+    state.u8(self.select);
+    state.bytes(&self.registers);
+    state.u8(self.protect);
+
+    state.bool(self.irq.enabled);
+    state.bool(self.irq.pending);
+    state.bool(self.irq.reload);
+    state.u8(self.irq.latch);
+    state.u8(self.irq.counter);
+    state.bool(self.last);
+  }
+
+  fn load(&mut self, state: &mut Reader) -> Result<(), StateError> {
+    self.mirroring.load(state)?;
+    self.chr.load(state)?;
+    self.chr_ram.load(state)?;
+    self.prg_ram.load(state)?;
+    self.prg_rom.load(state)?;
+
+    self.select = state.u8()?;
+    state.bytes_into(&mut self.registers, "MMC3 registers")?;
+    self.protect = state.u8()?;
+
+    self.irq.enabled = state.bool()?;
+    self.irq.pending = state.bool()?;
+    self.irq.reload = state.bool()?;
+    self.irq.latch = state.u8()?;
+    self.irq.counter = state.u8()?;
+    self.last = state.bool()?;
+    Ok(())
+  }
+}
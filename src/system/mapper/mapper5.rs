@@ -0,0 +1,439 @@
+use crate::savestate::{Reader, Snapshot, StateError, Writer};
+use crate::system::cartridge::Format;
+
+use super::{Mapper, MapperEvent, Mirroring, RomHeader};
+
+const EXRAM_SIZE: usize = 0x400;
+
+pub struct Mapper5 {
+  battery: bool,
+
+  chr: Vec<u8>,
+  chr_ram: bool,
+  prg_ram: Vec<u8>,
+  prg_rom: Vec<u8>,
+  exram: [u8; EXRAM_SIZE],
+
+  prg_mode: u8,
+  chr_mode: u8,
+  exram_mode: u8,
+  protect: [u8; 0x02],
+  nametables: u8,
+  fill_tile: u8,
+  fill_attr: u8,
+
+  prg_banks: [u8; 0x05],
+  chr_banks: [u16; 0x0C],
+  chr_upper: u8,
+  last_bg: bool,
+
+  split: [u8; 0x03],
+  multiplier: [u8; 0x02],
+  irq: IRQ,
+
+  large_sprites: bool,
+  sprite_fetch: bool,
+  line: u16,
+  tile: u8,
+  ext: u8,
+  in_split: bool,
+}
+
+struct IRQ {
+  enabled: bool,
+  pending: bool,
+  in_frame: bool,
+  target: u8,
+}
+
+impl IRQ {
+  fn new() -> Self {
+    IRQ {
+      enabled: false,
+      pending: false,
+      in_frame: false,
+      target: 0x0,
+    }
+  }
+}
+
+impl Mapper for Mapper5 {
+  fn mirroring(&self) -> Mirroring {
+    match self.nametables {
+      0x00 => Mirroring::Single0,
+      0x44 => Mirroring::Vertical,
+      0x50 => Mirroring::Horizontal,
+      0x55 => Mirroring::Single1,
+      _ => Mirroring::FourScreen,
+    }
+  }
+
+  fn read(&self, addr: u16) -> Option<u8> {
+    match addr {
+      0x0000 ..= 0x1FFF => Some(self.read_chr(addr)),
+      0x6000 ..= 0xFFFF => match self.prg_address(addr) {
+        (true, offset) => self.prg_rom.get(offset).copied(),
+        (false, offset) => self.prg_ram.get(offset).copied(),
+      },
+      _ => None,
+    }
+  }
+
+  fn write(&mut self, addr: u16, val: u8) {
+    match addr {
+      0x0000 ..= 0x1FFF if self.chr_ram => {
+        let offset = self.chr_address(addr, self.last_bg);
+        self.chr[offset] = val;
+      }
+      0x5000 ..= 0x5BFF => self.write_registers(addr, val),
+      0x5C00 ..= 0x5FFF => {
+        let offset = (addr - 0x5C00) as usize;
+        match self.exram_mode {
+          0 | 1 => self.exram[offset] = if self.irq.in_frame { val } else { 0x0 },
+          2 => self.exram[offset] = val,
+          _ => { },
+        }
+      }
+      0x6000 ..= 0xFFFF => {
+        if let (false, offset) = self.prg_address(addr) {
+          if self.protect == [0x02, 0x01] && offset < self.prg_ram.len() {
+            self.prg_ram[offset] = val;
+          }
+        }
+      }
+      _ => { },
+    }
+  }
+
+  fn read_expansion(&mut self, addr: u16) -> Option<u8> {
+    match addr {
+      0x5204 => {
+        let res = ((self.irq.pending as u8) << 7) | ((self.irq.in_frame as u8) << 6);
+        self.irq.pending = false;
+        Some(res)
+      }
+      0x5205 => Some(self.product() as u8),
+      0x5206 => Some((self.product() >> 8) as u8),
+      0x5C00 ..= 0x5FFF if self.exram_mode >= 2 => Some(self.exram[(addr - 0x5C00) as usize]),
+      _ => None,
+    }
+  }
+
+  fn read_nametable(&mut self, addr: u16, vram: &[u8]) -> u8 {
+    let offset = (addr & 0x3FF) as usize;
+    let attribute = offset >= 0x3C0;
+
+    if self.irq.in_frame && !self.sprite_fetch {
+      if !attribute {
+        self.in_split = self.split_tile(self.tile);
+        self.ext = self.exram[offset];
+        self.tile = self.tile.wrapping_add(1);
+
+        if self.in_split {
+          let column = (self.tile.wrapping_sub(1) & 0x1F) as usize;
+          return self.exram[(self.split_line() / 8) * 32 + column];
+        }
+      } else if self.in_split {
+        let column = (self.tile.wrapping_sub(1) & 0x1F) as usize;
+        let line = self.split_line();
+        let byte = self.exram[0x3C0 + (line / 32) * 8 + column / 4];
+        let shift = ((line / 16) & 0x01) * 4 + ((column / 2) & 0x01) * 2;
+        return ((byte >> shift) & 0x03) * 0x55;
+      } else if self.exram_mode == 1 {
+        return (self.ext >> 6) * 0x55;
+      }
+    }
+
+    match (self.nametables >> (2 * ((addr >> 10) & 0x03))) & 0x03 {
+      0 => vram[offset],
+      1 => vram[0x400 + offset],
+      2 if self.exram_mode <= 1 => self.exram[offset],
+      2 => 0x0,
+      _ if attribute => self.fill_attr * 0x55,
+      _ => self.fill_tile,
+    }
+  }
+
+  fn write_nametable(&mut self, addr: u16, val: u8, vram: &mut [u8]) {
+    let offset = (addr & 0x3FF) as usize;
+
+    match (self.nametables >> (2 * ((addr >> 10) & 0x03))) & 0x03 {
+      0 => vram[offset] = val,
+      1 => vram[0x400 + offset] = val,
+      2 if self.exram_mode <= 1 => self.exram[offset] = val,
+      _ => { },
+    }
+  }
+
+  fn notify(&mut self, event: MapperEvent) {
+    match event {
+      MapperEvent::ControlChanged(val) => self.large_sprites = val & 0x20 == 0x20,
+      MapperEvent::MaskChanged(val) if val & 0x18 == 0x0 => self.irq.in_frame = false,
+      MapperEvent::SpriteFetch(active) => self.sprite_fetch = active,
+      MapperEvent::Scanline(line) => {
+        self.tile = 0;
+
+        // The counter starts at whichever scanline rendering was first seen on
+        if self.irq.in_frame {
+          self.line += 1;
+
+          if self.line == self.irq.target as u16 {
+            self.irq.pending = true;
+          }
+        } else {
+          self.irq.in_frame = true;
+          self.irq.pending = false;
+          self.line = 0;
+        }
+
+        if line >= 240 {
+          self.irq.in_frame = false;
+        }
+      }
+      _ => { },
+    }
+  }
+
+  fn poll(&self) -> bool {
+    self.irq.enabled && self.irq.pending
+  }
+
+  fn battery_ram(&self) -> Option<&[u8]> {
+    self.battery.then_some(self.prg_ram.as_slice())
+  }
+
+  fn load_battery_ram(&mut self, data: &[u8]) {
+    if self.battery {
+      let len = std::cmp::min(data.len(), self.prg_ram.len());
+      self.prg_ram[..len].copy_from_slice(&data[..len]);
+    }
+  }
+}
+
+impl Mapper5 {
+  pub fn new(header: &RomHeader, chr_rom: Vec<u8>, prg_rom: Vec<u8>) -> Self {
+    let chr_ram = chr_rom.is_empty();
+
+    // iNES headers cannot describe MMC5 boards' PRG RAM, so assume the largest
+    let prg_ram = match header.format {
+      Format::INES => 0x10000,
+      Format::NES2 | Format::UNIF => header.prg_ram(),
+    };
+
+    Mapper5 {
+      battery: header.battery,
+      chr: if chr_ram { vec![0; header.chr_ram()] } else { chr_rom },
+      chr_ram,
+      prg_ram: vec![0; prg_ram],
+      prg_rom,
+      exram: [0x0; EXRAM_SIZE],
+      prg_mode: 0x03,
+      chr_mode: 0x0,
+      exram_mode: 0x0,
+      protect: [0x0; 0x02],
+      nametables: 0x0,
+      fill_tile: 0x0,
+      fill_attr: 0x0,
+      prg_banks: [0x0, 0xFF, 0xFF, 0xFF, 0xFF],
+      chr_banks: [0x0; 0x0C],
+      chr_upper: 0x0,
+      last_bg: false,
+      split: [0x0; 0x03],
+      multiplier: [0xFF; 0x02],
+      irq: IRQ::new(),
+      large_sprites: false,
+      sprite_fetch: false,
+      line: 0,
+      tile: 0,
+      ext: 0x0,
+      in_split: false,
+    }
+  }
+
+  fn write_registers(&mut self, addr: u16, val: u8) {
+    match addr {
+      0x5100 => self.prg_mode = val & 0x03,
+      0x5101 => self.chr_mode = val & 0x03,
+      0x5102 | 0x5103 => self.protect[(addr - 0x5102) as usize] = val & 0x03,
+      0x5104 => self.exram_mode = val & 0x03,
+      0x5105 => self.nametables = val,
+      0x5106 => self.fill_tile = val,
+      0x5107 => self.fill_attr = val & 0x03,
+      0x5113 ..= 0x5117 => self.prg_banks[(addr - 0x5113) as usize] = val,
+      0x5120 ..= 0x512B => {
+        self.chr_banks[(addr - 0x5120) as usize] = ((self.chr_upper as u16) << 8) | val as u16;
+        self.last_bg = addr >= 0x5128;
+      }
+      0x5130 => self.chr_upper = val & 0x03,
+      0x5200 ..= 0x5202 => self.split[(addr - 0x5200) as usize] = val,
+      0x5203 => self.irq.target = val,
+      0x5204 => self.irq.enabled = val & 0x80 == 0x80,
+      0x5205 | 0x5206 => self.multiplier[(addr - 0x5205) as usize] = val,
+      _ => { },
+    }
+  }
+
+  fn product(&self) -> u16 {
+    self.multiplier[0] as u16 * self.multiplier[1] as u16
+  }
+
+  fn prg_address(&self, addr: u16) -> (bool, usize) {
+    if addr < 0x8000 {
+      let bank = (self.prg_banks[0] & 0x07) as usize;
+      return (false, Mapper5::wrap(bank * 0x2000 + (addr as usize % 0x2000), self.prg_ram.len()));
+    }
+
+    let (register, size) = match (self.prg_mode, (addr - 0x8000) / 0x2000) {
+      (0, _) => (4, 0x8000),
+      (1, 0 | 1) => (2, 0x4000),
+      (1, _) => (4, 0x4000),
+      (2, 0 | 1) => (2, 0x4000),
+      (2, 2) => (3, 0x2000),
+      (2, _) => (4, 0x2000),
+      (_, slot) => (slot as usize + 1, 0x2000),
+    };
+
+    let val = self.prg_banks[register];
+    let rom = register == 4 || val & 0x80 == 0x80;
+    let bank = (val & 0x7F) as usize & !(size / 0x2000 - 1);
+    let offset = bank * 0x2000 + (addr as usize % size);
+
+    match rom {
+      true => (true, Mapper5::wrap(offset, self.prg_rom.len())),
+      false => (false, Mapper5::wrap(offset & 0xFFFF, self.prg_ram.len())),
+    }
+  }
+
+  fn read_chr(&self, addr: u16) -> u8 {
+    if self.chr.is_empty() {
+      return 0;
+    }
+
+    let background = self.irq.in_frame && !self.sprite_fetch;
+
+    let offset = if background && self.in_split {
+      let line = self.split_line() as u16;
+      self.split[2] as usize * 0x1000 + ((addr & 0x0FF8) | (line & 0x07)) as usize
+    } else if background && self.exram_mode == 1 {
+      let bank = ((self.chr_upper as usize) << 6) | (self.ext & 0x3F) as usize;
+      bank * 0x1000 + (addr & 0x0FFF) as usize
+    } else {
+      // Only 8x16 sprites keep the two sets apart, otherwise every fetch uses
+      // whichever set was written last
+      let bg = if self.large_sprites && self.irq.in_frame { background } else { self.last_bg };
+      self.chr_address(addr, bg)
+    };
+
+    self.chr[offset % self.chr.len()]
+  }
+
+  fn chr_address(&self, addr: u16, bg: bool) -> usize {
+    let addr = addr as usize;
+    let (size, register) = match self.chr_mode {
+      0 => (0x2000, 7),
+      1 => (0x1000, 3 + 4 * (addr / 0x1000)),
+      2 => (0x800, 1 + 2 * (addr / 0x800)),
+      _ => (0x400, addr / 0x400),
+    };
+
+    let bank = if bg { self.chr_banks[8 + (register & 0x03)] } else { self.chr_banks[register] };
+    Mapper5::wrap(bank as usize * size + addr % size, self.chr.len())
+  }
+
+  fn split_tile(&self, tile: u8) -> bool {
+    let control = self.split[0];
+    let threshold = control & 0x1F;
+
+    if control & 0x80 == 0x0 || self.exram_mode > 1 {
+      return false;
+    }
+
+    match control & 0x40 == 0x40 {
+      true => tile >= threshold,
+      false => tile < threshold,
+    }
+  }
+
+  fn split_line(&self) -> usize {
+    (self.line as usize + self.split[1] as usize) % 240
+  }
+
+  fn wrap(offset: usize, len: usize) -> usize {
+    if len == 0 { offset } else { offset % len }
+  }
+}
+
+impl Snapshot for Mapper5 {
+  fn save(&self, state: &mut Writer) {
+    if self.chr_ram {
+      state.bytes(&self.chr);
+    }
+    state.bytes(&self.prg_ram);
+    state.bytes(&self.exram);
+
+    state.u8(self.prg_mode);
+    state.u8(self.chr_mode);
+    state.u8(self.exram_mode);
+    state.bytes(&self.protect);
+    state.u8(self.nametables);
+    state.u8(self.fill_tile);
+    state.u8(self.fill_attr);
+
+    state.bytes(&self.prg_banks);
+    self.chr_banks.iter().for_each(|bank| state.u16(*bank));
+    state.u8(self.chr_upper);
+    state.bool(self.last_bg);
+
+    state.bytes(&self.split);
+    state.bytes(&self.multiplier);
+    state.bool(self.irq.enabled);
+    state.bool(self.irq.pending);
+    state.bool(self.irq.in_frame);
+    state.u8(self.irq.target);
+
+    state.bool(self.large_sprites);
+    state.bool(self.sprite_fetch);
+    state.u16(self.line);
+    state.u8(self.tile);
+    state.u8(self.ext);
+    state.bool(self.in_split);
+  }
+
+  fn load(&mut self, state: &mut Reader) -> Result<(), StateError> {
+    if self.chr_ram {
+      state.bytes_into(&mut self.chr, "CHR RAM size")?;
+    }
+    state.bytes_into(&mut self.prg_ram, "PRG RAM size")?;
+    state.bytes_into(&mut self.exram, "ExRAM size")?;
+
+    self.prg_mode = state.u8()?;
+    self.chr_mode = state.u8()?;
+    self.exram_mode = state.u8()?;
+    state.bytes_into(&mut self.protect, "MMC5 RAM protect")?;
+    self.nametables = state.u8()?;
+    self.fill_tile = state.u8()?;
+    self.fill_attr = state.u8()?;
+
+    state.bytes_into(&mut self.prg_banks, "MMC5 PRG banks")?;
+    for bank in self.chr_banks.iter_mut() {
+      *bank = state.u16()?;
+    }
+    self.chr_upper = state.u8()?;
+    self.last_bg = state.bool()?;
+
+    state.bytes_into(&mut self.split, "MMC5 split")?;
+    state.bytes_into(&mut self.multiplier, "MMC5 multiplier")?;
+    self.irq.enabled = state.bool()?;
+    self.irq.pending = state.bool()?;
+    self.irq.in_frame = state.bool()?;
+    self.irq.target = state.u8()?;
+
+    self.large_sprites = state.bool()?;
+    self.sprite_fetch = state.bool()?;
+    self.line = state.u16()?;
+    self.tile = state.u8()?;
+    self.ext = state.u8()?;
+    self.in_split = state.bool()?;
+    Ok(())
+  }
+}
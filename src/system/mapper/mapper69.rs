@@ -0,0 +1,192 @@
+use crate::savestate::{Reader, Snapshot, StateError, Writer};
+
+use super::{banks::Banks, sunsoft5b::Sunsoft5B, Mapper, Mirroring, RomHeader};
+
+const PRG_BANK_SIZE: usize = 0x2000;
+const CHR_BANK_SIZE: usize = 0x400;
+
+pub struct Mapper69 {
+  mirroring: Mirroring,
+  battery: bool,
+
+  chr: Banks,
+  prg_ram: Banks,
+  prg_low: Banks,
+  prg_rom: Banks,
+
+  command: u8,
+  ram_select: bool,
+  ram_enabled: bool,
+
+  irq: IRQ,
+  audio: Sunsoft5B,
+}
+
+struct IRQ {
+  enabled: bool,
+  counting: bool,
+  pending: bool,
+  counter: u16,
+}
+
+impl IRQ {
+  fn new() -> Self {
+    IRQ {
+      enabled: false,
+      counting: false,
+      pending: false,
+      counter: 0x0,
+    }
+  }
+}
+
+impl Mapper for Mapper69 {
+  fn mirroring(&self) -> Mirroring {
+    self.mirroring
+  }
+
+  fn read(&self, addr: u16) -> Option<u8> {
+    match addr {
+      0x0000 ..= 0x1FFF => self.chr.read(addr),
+      0x6000 ..= 0x7FFF if !self.ram_select => self.prg_low.read(addr),
+      0x6000 ..= 0x7FFF if self.ram_enabled => self.prg_ram.read(addr),
+      0x8000 ..= 0xFFFF => self.prg_rom.read(addr),
+      _ => None,
+    }
+  }
+
+  fn write(&mut self, addr: u16, val: u8) {
+    match addr {
+      0x0000 ..= 0x1FFF => self.chr.write(addr, val),
+      0x6000 ..= 0x7FFF if self.ram_select && self.ram_enabled => self.prg_ram.write(addr, val),
+      0x8000 ..= 0x9FFF => self.command = val & 0x0F,
+      0xA000 ..= 0xBFFF => self.write_parameter(val),
+      0xC000 ..= 0xDFFF => self.audio.write_select(val),
+      0xE000 ..= 0xFFFF => self.audio.write_data(val),
+      _ => { },
+    }
+  }
+
+  fn tick(&mut self) {
+    if self.irq.counting {
+      self.irq.counter = self.irq.counter.wrapping_sub(1);
+
+      if self.irq.counter == 0xFFFF && self.irq.enabled {
+        self.irq.pending = true;
+      }
+    }
+
+    self.audio.tick();
+  }
+
+  fn signal(&self) -> f32 {
+    self.audio.signal()
+  }
+
+  fn mute(&mut self, channel: u8, muted: bool) {
+    self.audio.mute(channel, muted);
+  }
+
+  fn poll(&self) -> bool {
+    self.irq.pending
+  }
+
+  fn battery_ram(&self) -> Option<&[u8]> {
+    self.battery.then(|| self.prg_ram.memory())
+  }
+
+  fn load_battery_ram(&mut self, data: &[u8]) {
+    if self.battery {
+      self.prg_ram.restore(data);
+    }
+  }
+}
+
+impl Mapper69 {
+  pub fn new(header: &RomHeader, chr_rom: Vec<u8>, prg_rom: Vec<u8>) -> Self {
+    let chr = !chr_rom.is_empty();
+
+    let mut mapper = Mapper69 {
+      mirroring: header.mirroring,
+      battery: header.battery,
+      chr: Banks::new(0x0000, 0x1FFF, CHR_BANK_SIZE, if chr { chr_rom } else { vec![0; header.chr_ram()] }, !chr),
+      prg_ram: Banks::new(0x6000, 0x7FFF, PRG_BANK_SIZE, vec![0; header.prg_ram()], true),
+      prg_low: Banks::new(0x6000, 0x7FFF, PRG_BANK_SIZE, prg_rom.clone(), false),
+      prg_rom: Banks::new(0x8000, 0xFFFF, PRG_BANK_SIZE, prg_rom, false),
+      command: 0x0,
+      ram_select: false,
+      ram_enabled: false,
+      irq: IRQ::new(),
+      audio: Sunsoft5B::new(),
+    };
+
+    mapper.prg_rom.set(3, mapper.prg_rom.last());
+    mapper
+  }
+
+  fn write_parameter(&mut self, val: u8) {
+    match self.command {
+      0x0 ..= 0x7 => self.chr.set(self.command as usize, val as usize),
+      0x8 => {
+        self.ram_select = val & 0x40 == 0x40;
+        self.ram_enabled = val & 0x80 == 0x80;
+        self.prg_ram.set(0, val as usize & 0x3F);
+        self.prg_low.set(0, val as usize & 0x3F);
+      }
+      0x9 ..= 0xB => self.prg_rom.set((self.command - 0x9) as usize, val as usize & 0x3F),
+      0xC => {
+        self.mirroring = match val & 0x03 {
+          0 => Mirroring::Vertical,
+          1 => Mirroring::Horizontal,
+          2 => Mirroring::Single0,
+          _ => Mirroring::Single1,
+        }
+      }
+      0xD => {
+        self.irq.enabled = val & 0x01 == 0x01;
+        self.irq.counting = val & 0x80 == 0x80;
+        self.irq.pending = false;
+      }
+      0xE => self.irq.counter = (self.irq.counter & 0xFF00) | val as u16,
+      _ => self.irq.counter = (self.irq.counter & 0x00FF) | ((val as u16) << 8),
+    }
+  }
+}
+
+impl Snapshot for Mapper69 {
+  fn save(&self, state: &mut Writer) {
+    self.mirroring.save(state);
+    self.chr.save(state);
+    self.prg_ram.save(state);
+    self.prg_low.save(state);
+    self.prg_rom.save(state);
+
+    state.u8(self.command);
+    state.bool(self.ram_select);
+    state.bool(self.ram_enabled);
+
+    state.bool(self.irq.enabled);
+    state.bool(self.irq.counting);
+    state.bool(self.irq.pending);
+    state.u16(self.irq.counter);
+    self.audio.save(state);
+  }
+
+  fn load(&mut self, state: &mut Reader) -> Result<(), StateError> {
+    self.mirroring.load(state)?;
+    self.chr.load(state)?;
+    self.prg_ram.load(state)?;
+    self.prg_low.load(state)?;
+    self.prg_rom.load(state)?;
+
+    self.command = state.u8()?;
+    self.ram_select = state.bool()?;
+    self.ram_enabled = state.bool()?;
+
+    self.irq.enabled = state.bool()?;
+    self.irq.counting = state.bool()?;
+    self.irq.pending = state.bool()?;
+    self.irq.counter = state.u16()?;
+    self.audio.load(state)
+  }
+}
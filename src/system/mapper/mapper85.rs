@@ -0,0 +1,149 @@
+use crate::savestate::{Reader, Snapshot, StateError, Writer};
+
+use super::{banks::Banks, vrc7audio::VRC7Audio, vrcirq::VRCIRQ, Mapper, Mirroring, RomHeader};
+
+const PRG_BANK_SIZE: usize = 0x2000;
+const CHR_BANK_SIZE: usize = 0x400;
+
+// VRC7a selects its odd registers with A4 and VRC7b with A3. Neither board
+// uses the other line, so both are decoded at once.
+pub struct Mapper85 {
+  mirroring: Mirroring,
+  battery: bool,
+
+  chr: Banks,
+  prg_ram: Banks,
+  prg_rom: Banks,
+  ram_enabled: bool,
+
+  irq: VRCIRQ,
+  audio: VRC7Audio,
+}
+
+impl Mapper for Mapper85 {
+  fn mirroring(&self) -> Mirroring {
+    self.mirroring
+  }
+
+  fn read(&self, addr: u16) -> Option<u8> {
+    match addr {
+      0x0000 ..= 0x1FFF => self.chr.read(addr),
+      0x6000 ..= 0x7FFF if self.ram_enabled => self.prg_ram.read(addr),
+      0x8000 ..= 0xFFFF => self.prg_rom.read(addr),
+      _ => None,
+    }
+  }
+
+  fn write(&mut self, addr: u16, val: u8) {
+    match addr {
+      0x0000 ..= 0x1FFF => self.chr.write(addr, val),
+      0x6000 ..= 0x7FFF if self.ram_enabled => self.prg_ram.write(addr, val),
+      0x8000 ..= 0xFFFF => self.write_registers(Mapper85::translate(addr), val),
+      _ => { },
+    }
+  }
+
+  fn tick(&mut self) {
+    self.irq.tick();
+    self.audio.tick();
+  }
+
+  fn signal(&self) -> f32 {
+    self.audio.signal()
+  }
+
+  fn mute(&mut self, channel: u8, muted: bool) {
+    self.audio.mute(channel, muted);
+  }
+
+  fn poll(&self) -> bool {
+    self.irq.poll()
+  }
+
+  fn battery_ram(&self) -> Option<&[u8]> {
+    self.battery.then(|| self.prg_ram.memory())
+  }
+
+  fn load_battery_ram(&mut self, data: &[u8]) {
+    if self.battery {
+      self.prg_ram.restore(data);
+    }
+  }
+}
+
+impl Mapper85 {
+  pub fn new(header: &RomHeader, chr_rom: Vec<u8>, prg_rom: Vec<u8>) -> Self {
+    let chr = !chr_rom.is_empty();
+
+    let mut mapper = Mapper85 {
+      mirroring: header.mirroring,
+      battery: header.battery,
+      chr: Banks::new(0x0000, 0x1FFF, CHR_BANK_SIZE, if chr { chr_rom } else { vec![0; header.chr_ram()] }, !chr),
+      prg_ram: Banks::new(0x6000, 0x7FFF, PRG_BANK_SIZE, vec![0; header.prg_ram()], true),
+      prg_rom: Banks::new(0x8000, 0xFFFF, PRG_BANK_SIZE, prg_rom, false),
+      ram_enabled: false,
+      irq: VRCIRQ::new(),
+      audio: VRC7Audio::new(),
+    };
+
+    mapper.prg_rom.set(3, mapper.prg_rom.last());
+    mapper
+  }
+
+  fn translate(addr: u16) -> u16 {
+    let odd = if addr & 0x18 != 0 { 0x10 } else { 0x00 };
+    (addr & 0xF020) | odd
+  }
+
+  fn write_registers(&mut self, addr: u16, val: u8) {
+    match addr {
+      0x8000 => self.prg_rom.set(0, val as usize & 0x3F),
+      0x8010 => self.prg_rom.set(1, val as usize & 0x3F),
+      0x9000 => self.prg_rom.set(2, val as usize & 0x3F),
+      0x9010 => self.audio.write_address(val),
+      0x9030 => self.audio.write_data(val),
+      0xA000 ..= 0xDFFF => {
+        let slot = ((addr - 0xA000) >> 12) * 2 + ((addr & 0x10) >> 4);
+        self.chr.set(slot as usize, val as usize);
+      }
+      0xE000 => {
+        self.mirroring = match val & 0x03 {
+          0 => Mirroring::Vertical,
+          1 => Mirroring::Horizontal,
+          2 => Mirroring::Single0,
+          _ => Mirroring::Single1,
+        };
+        self.audio.silence(val & 0x40 == 0x40);
+        self.ram_enabled = val & 0x80 == 0x80;
+      }
+      0xE010 => self.irq.write_latch(val),
+      0xF000 => self.irq.write_control(val),
+      0xF010 => self.irq.acknowledge(),
+      _ => { },
+    }
+  }
+}
+
+impl Snapshot for Mapper85 {
+  fn save(&self, state: &mut Writer) {
+    self.mirroring.save(state);
+    self.chr.save(state);
+    self.prg_ram.save(state);
+    self.prg_rom.save(state);
+    state.bool(self.ram_enabled);
+
+    self.irq.save(state);
+    self.audio.save(state);
+  }
+
+  fn load(&mut self, state: &mut Reader) -> Result<(), StateError> {
+    self.mirroring.load(state)?;
+    self.chr.load(state)?;
+    self.prg_ram.load(state)?;
+    self.prg_rom.load(state)?;
+    self.ram_enabled = state.bool()?;
+
+    self.irq.load(state)?;
+    self.audio.load(state)
+  }
+}
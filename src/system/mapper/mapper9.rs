@@ -0,0 +1,137 @@
+use crate::savestate::{Reader, Snapshot, StateError, Writer};
+
+use super::{banks::Banks, Mapper, MapperEvent, Mirroring, RomHeader};
+
+const CHR_BANK_SIZE: usize = 0x1000;
+
+// MMC2 (mapper 9) and MMC4 (mapper 10) only differ in PRG banking and how
+// precisely the left pattern table latch is triggered.
+pub struct Mapper9 {
+  mirroring: Mirroring,
+  battery: bool,
+  mmc4: bool,
+
+  chr: Banks,
+  prg_ram: Banks,
+  prg_rom: Banks,
+
+  registers: [u8; 0x04],
+  latches: [u8; 0x02],
+}
+
+impl Mapper for Mapper9 {
+  fn mirroring(&self) -> Mirroring {
+    self.mirroring
+  }
+
+  fn read(&self, addr: u16) -> Option<u8> {
+    match addr {
+      0x0000 ..= 0x1FFF => self.chr.read(addr),
+      0x6000 ..= 0x7FFF => self.prg_ram.read(addr),
+      0x8000 ..= 0xFFFF => self.prg_rom.read(addr),
+      _ => None,
+    }
+  }
+
+  fn write(&mut self, addr: u16, val: u8) {
+    match addr {
+      0x0000 ..= 0x1FFF => self.chr.write(addr, val),
+      0x6000 ..= 0x7FFF => self.prg_ram.write(addr, val),
+      0xA000 ..= 0xAFFF => self.prg_rom.set(0, val as usize & 0x0F),
+      0xB000 ..= 0xEFFF => {
+        self.registers[((addr - 0xB000) >> 12) as usize] = val & 0x1F;
+        self.update_chr();
+      }
+      0xF000 ..= 0xFFFF => {
+        self.mirroring = if val & 0x01 == 0x01 { Mirroring::Horizontal } else { Mirroring::Vertical };
+      }
+      _ => { },
+    }
+  }
+
+  fn notify(&mut self, event: MapperEvent) {
+    if let MapperEvent::PatternFetch(addr) = event {
+      let (latch, val) = match (addr, self.mmc4) {
+        (0x0FD8, _) | (0x0FD9 ..= 0x0FDF, true) => (0, 0xFD),
+        (0x0FE8, _) | (0x0FE9 ..= 0x0FEF, true) => (0, 0xFE),
+        (0x1FD8 ..= 0x1FDF, _) => (1, 0xFD),
+        (0x1FE8 ..= 0x1FEF, _) => (1, 0xFE),
+        _ => return,
+      };
+
+      self.latches[latch] = val;
+      self.update_chr();
+    }
+  }
+
+  fn battery_ram(&self) -> Option<&[u8]> {
+    self.battery.then(|| self.prg_ram.memory())
+  }
+
+  fn load_battery_ram(&mut self, data: &[u8]) {
+    if self.battery {
+      self.prg_ram.restore(data);
+    }
+  }
+}
+
+impl Mapper9 {
+  pub fn new(header: &RomHeader, chr_rom: Vec<u8>, prg_rom: Vec<u8>) -> Self {
+    let chr = !chr_rom.is_empty();
+    let mmc4 = header.mapper == 10;
+    let prg_bank_size = if mmc4 { 0x4000 } else { 0x2000 };
+
+    let mut mapper = Mapper9 {
+      mirroring: header.mirroring,
+      battery: header.battery,
+      mmc4,
+      chr: Banks::new(0x0000, 0x1FFF, CHR_BANK_SIZE, if chr { chr_rom } else { vec![0; header.chr_ram()] }, !chr),
+      prg_ram: Banks::new(0x6000, 0x7FFF, 0x2000, vec![0; header.prg_ram()], true),
+      prg_rom: Banks::new(0x8000, 0xFFFF, prg_bank_size, prg_rom, false),
+      registers: [0x0; 0x04],
+      latches: [0xFE; 0x02],
+    };
+
+    let last = mapper.prg_rom.last();
+    if mmc4 {
+      mapper.prg_rom.set(1, last);
+    } else {
+      mapper.prg_rom.set(1, last.saturating_sub(2));
+      mapper.prg_rom.set(2, last.saturating_sub(1));
+      mapper.prg_rom.set(3, last);
+    }
+
+    mapper.update_chr();
+    mapper
+  }
+
+  fn update_chr(&mut self) {
+    let lo = if self.latches[0] == 0xFD { self.registers[0] } else { self.registers[1] };
+    let hi = if self.latches[1] == 0xFD { self.registers[2] } else { self.registers[3] };
+
+    self.chr.set(0, lo as usize);
+    self.chr.set(1, hi as usize);
+  }
+}
+
+impl Snapshot for Mapper9 {
+  fn save(&self, state: &mut Writer) {
+    self.mirroring.save(state);
+    self.chr.save(state);
+    self.prg_ram.save(state);
+    self.prg_rom.save(state);
+
+    state.bytes(&self.registers);
+    state.bytes(&self.latches);
+  }
+
+  fn load(&mut self, state: &mut Reader) -> Result<(), StateError> {
+    self.mirroring.load(state)?;
+    self.chr.load(state)?;
+    self.prg_ram.load(state)?;
+    self.prg_rom.load(state)?;
+
+    state.bytes_into(&mut self.registers, "MMC2 registers")?;
+    state.bytes_into(&mut self.latches, "MMC2 latches")
+  }
+}
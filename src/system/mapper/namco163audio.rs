@@ -0,0 +1,120 @@
+use crate::savestate::{Reader, Snapshot, StateError, Writer};
+
+// Channel registers and phases live in the chip's 128 bytes of sound RAM,
+// which the mapper owns so it can be saved alongside the battery RAM. Only
+// one channel is updated every 15 CPU cycles and the chip outputs each in
+// turn, so the channels are averaged the way the output filter hears them.
+pub struct Namco163Audio {
+  disabled: bool,
+  delay: u8,
+  channel: u8,
+  active: u8,
+  outputs: [i16; 0x08],
+  muted: [bool; 0x08],
+}
+
+impl Namco163Audio {
+  // Scaled so a lone full volume channel is close to a full volume 2A03 pulse
+  const LEVEL: f32 = 0.00125;
+
+  pub fn new() -> Self {
+    Namco163Audio {
+      disabled: false,
+      delay: 0x0,
+      channel: 0x7,
+      active: 0x1,
+      outputs: [0x0; 0x08],
+      muted: [false; 0x08],
+    }
+  }
+
+  pub fn disable(&mut self, disabled: bool) {
+    self.disabled = disabled;
+  }
+
+  pub fn mute(&mut self, channel: u8, muted: bool) {
+    if let Some(mute) = self.muted.get_mut(channel as usize) {
+      *mute = muted;
+    }
+  }
+
+  pub fn tick(&mut self, ram: &mut [u8]) {
+    if self.disabled {
+      return;
+    }
+
+    self.delay += 1;
+
+    if self.delay < 15 {
+      return;
+    }
+
+    self.delay = 0;
+    self.active = ((ram[0x7F] >> 4) & 0x07) + 1;
+
+    if self.channel < 8 - self.active {
+      self.channel = 7;
+    }
+
+    self.outputs[self.channel as usize] = Namco163Audio::update(self.channel, ram);
+    self.channel = if self.channel == 8 - self.active { 7 } else { self.channel - 1 };
+  }
+
+  fn update(channel: u8, ram: &mut [u8]) -> i16 {
+    let base = 0x40 + channel as usize * 8;
+    let reg = &mut ram[base .. base + 8];
+
+    let freq = reg[0] as u32 | (reg[2] as u32) << 8 | (reg[4] as u32 & 0x03) << 16;
+    let length = 0x100 - (reg[4] as u32 & 0xFC);
+    let mut phase = reg[1] as u32 | (reg[3] as u32) << 8 | (reg[5] as u32) << 16;
+
+    phase = (phase + freq) % (length << 16);
+    reg[1] = phase as u8;
+    reg[3] = (phase >> 8) as u8;
+    reg[5] = (phase >> 16) as u8;
+
+    let volume = (reg[7] & 0x0F) as i16;
+    let addr = (reg[6] as usize + (phase >> 16) as usize) & 0xFF;
+    let sample = (ram[addr >> 1] >> ((addr & 0x01) * 4)) & 0x0F;
+
+    (sample as i16 - 8) * volume
+  }
+
+  pub fn signal(&self) -> f32 {
+    if self.disabled {
+      return 0.0;
+    }
+
+    let sum: i16 = self.outputs.iter().zip(self.muted).enumerate()
+      .filter(|&(i, (_, muted))| i >= (8 - self.active) as usize && !muted)
+      .map(|(_, (output, _))| *output)
+      .sum();
+
+    sum as f32 / self.active as f32 * Namco163Audio::LEVEL
+  }
+}
+
+impl Snapshot for Namco163Audio {
+  fn save(&self, state: &mut Writer) {
+    state.bool(self.disabled);
+    state.u8(self.delay);
+    state.u8(self.channel);
+    state.u8(self.active);
+
+    for output in self.outputs {
+      state.u16(output as u16);
+    }
+  }
+
+  fn load(&mut self, state: &mut Reader) -> Result<(), StateError> {
+    self.disabled = state.bool()?;
+    self.delay = state.u8()?;
+    self.channel = state.u8()? & 0x07;
+    self.active = state.u8()?.clamp(1, 8);
+
+    for output in self.outputs.iter_mut() {
+      *output = state.u16()? as i16;
+    }
+    Ok(())
+  }
+}
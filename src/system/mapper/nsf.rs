@@ -0,0 +1,422 @@
+use crate::savestate::{Reader, Snapshot, StateError, Writer};
+use crate::system::cartridge::{NsfHeader, Timing};
+
+use super::{
+  banks::Banks, fdsaudio::FDSAudio, namco163audio::Namco163Audio, sunsoft5b::Sunsoft5B,
+  vrc6audio::VRC6Audio, vrc7audio::VRC7Audio, Mapper, Mirroring, RomHeader,
+};
+
+const BANK_SIZE: usize = 0x1000;
+const CLOCK_RATE: u64 = 1789773;
+
+const DRIVER: u16 = 0x4100;
+const PLAY: u16 = 0x41F0;
+const TRACK: u16 = 0x41F1;
+const REGION: u16 = 0x41F2;
+const RETURN: u16 = 0x414D;
+
+// A pseudo-cartridge for NSF rips. The mapper supplies a small driver at
+// $4100 which resets the APU, clears RAM and calls the tune's init routine,
+// then calls play whenever the play timer has expired. The vectors always
+// point into the driver. MMC5 tunes get ExRAM and the multiplier, but not its
+// pulse channels.
+pub struct NSF {
+  prg_rom: Banks,
+  ram: Vec<u8>,
+  exram: [u8; 0x400],
+  multiplier: [u8; 0x02],
+
+  driver: Vec<u8>,
+  banks: [u8; 0x08],
+  load: u16,
+  bankswitched: bool,
+  fds: bool,
+
+  track: u8,
+  region: u8,
+  period: u64,
+  counter: u64,
+  pending: bool,
+
+  chips: u8,
+  vrc6: Option<VRC6Audio>,
+  vrc7: Option<VRC7Audio>,
+  fds_audio: Option<FDSAudio>,
+  n163: Option<Namco163Audio>,
+  n163_ram: [u8; 0x80],
+  n163_address: u8,
+  sunsoft: Option<Sunsoft5B>,
+}
+
+impl Mapper for NSF {
+  fn mirroring(&self) -> Mirroring {
+    Mirroring::Vertical
+  }
+
+  fn read(&self, addr: u16) -> Option<u8> {
+    match addr {
+      0xFFFA ..= 0xFFFF => {
+        let vector = if addr & 0xFFFE == 0xFFFC { DRIVER } else { RETURN };
+        Some(vector.to_le_bytes()[addr as usize & 0x01])
+      }
+      0x6000 ..= 0xFFFF if self.fds => Some(self.ram[addr as usize - 0x6000]),
+      0x6000 ..= 0x7FFF => Some(self.ram[addr as usize - 0x6000]),
+      0x8000 ..= 0xFFFF => self.prg_rom.read(addr),
+      _ => None,
+    }
+  }
+
+  fn write(&mut self, addr: u16, val: u8) {
+    match addr {
+      0x4040 ..= 0x408A => {
+        if let Some(audio) = &mut self.fds_audio {
+          audio.write(addr, val);
+        }
+      }
+      0x4800 ..= 0x4FFF if self.n163.is_some() => {
+        let index = self.n163_index();
+        self.n163_ram[index] = val;
+      }
+      0x5205 ..= 0x5206 if self.chips & NsfHeader::MMC5 != 0 => self.multiplier[addr as usize - 0x5205] = val,
+      0x5C00 ..= 0x5FF5 if self.chips & NsfHeader::MMC5 != 0 => self.exram[addr as usize - 0x5C00] = val,
+      0x5FF6 ..= 0x5FFF if self.bankswitched => self.switch((addr - 0x5FF6) as usize, val),
+      0x6000 ..= 0xDFFF if self.fds => self.ram[addr as usize - 0x6000] = val,
+      0x6000 ..= 0x7FFF => self.ram[addr as usize - 0x6000] = val,
+      _ => self.write_audio(addr, val),
+    }
+  }
+
+  fn read_expansion(&mut self, addr: u16) -> Option<u8> {
+    match addr {
+      PLAY => Some(std::mem::take(&mut self.pending) as u8),
+      TRACK => Some(self.track),
+      REGION => Some(self.region),
+      0x4100 ..= 0x41FF => self.driver.get((addr - DRIVER) as usize).copied(),
+      0x4040 ..= 0x4092 => self.fds_audio.as_ref().and_then(|audio| audio.read(addr)),
+      0x4800 ..= 0x4FFF if self.n163.is_some() => {
+        let index = self.n163_index();
+        Some(self.n163_ram[index])
+      }
+      0x5205 if self.chips & NsfHeader::MMC5 != 0 => Some((self.multiplier[0] as u16 * self.multiplier[1] as u16) as u8),
+      0x5206 if self.chips & NsfHeader::MMC5 != 0 => Some(((self.multiplier[0] as u16 * self.multiplier[1] as u16) >> 8) as u8),
+      0x5C00 ..= 0x5FF5 if self.chips & NsfHeader::MMC5 != 0 => Some(self.exram[addr as usize - 0x5C00]),
+      _ => None,
+    }
+  }
+
+  fn tick(&mut self) {
+    self.counter += 1_000_000;
+
+    if self.counter >= self.period {
+      self.counter -= self.period;
+      self.pending = true;
+    }
+
+    if let Some(audio) = &mut self.vrc6 {
+      audio.tick();
+    }
+    if let Some(audio) = &mut self.vrc7 {
+      audio.tick();
+    }
+    if let Some(audio) = &mut self.fds_audio {
+      audio.tick();
+    }
+    if let Some(audio) = &mut self.n163 {
+      audio.tick(&mut self.n163_ram);
+    }
+    if let Some(audio) = &mut self.sunsoft {
+      audio.tick();
+    }
+  }
+
+  fn signal(&self) -> f32 {
+    self.vrc6.as_ref().map_or(0.0, |audio| audio.signal())
+      + self.vrc7.as_ref().map_or(0.0, |audio| audio.signal())
+      + self.fds_audio.as_ref().map_or(0.0, |audio| audio.signal())
+      + self.n163.as_ref().map_or(0.0, |audio| audio.signal())
+      + self.sunsoft.as_ref().map_or(0.0, |audio| audio.signal())
+  }
+
+  // Expansion channels are numbered across the chips a tune uses, in the
+  // order of the header's chip flags.
+  fn mute(&mut self, channel: u8, muted: bool) {
+    let mut channel = channel;
+
+    if let Some(audio) = &mut self.vrc6 {
+      if channel < 3 {
+        audio.mute(channel, muted);
+        return;
+      }
+      channel -= 3;
+    }
+
+    if let Some(audio) = &mut self.vrc7 {
+      if channel < 6 {
+        audio.mute(channel, muted);
+        return;
+      }
+      channel -= 6;
+    }
+
+    if let Some(audio) = &mut self.fds_audio {
+      if channel < 1 {
+        audio.mute(muted);
+        return;
+      }
+      channel -= 1;
+    }
+
+    if let Some(audio) = &mut self.n163 {
+      if channel < 8 {
+        audio.mute(channel, muted);
+        return;
+      }
+      channel -= 8;
+    }
+
+    if let Some(audio) = &mut self.sunsoft {
+      audio.mute(channel, muted);
+    }
+  }
+
+  fn select_track(&mut self, track: u8) {
+    self.track = track;
+    self.reset();
+  }
+}
+
+impl NSF {
+  pub fn new(header: &RomHeader, nsf: &NsfHeader, data: Vec<u8>) -> Self {
+    let fds = nsf.chips & NsfHeader::FDS != 0;
+    let bankswitched = nsf.bankswitched();
+
+    // Banked data is laid out from the start of the 4K bank holding the load
+    // address, while unbanked data sits where it is loaded in $8000-$FFFF.
+    let offset = if bankswitched { nsf.load as usize & 0x0FFF } else { (nsf.load as usize).saturating_sub(0x8000) };
+    let mut prg = vec![0; offset];
+    prg.extend(data);
+    prg.resize(std::cmp::max(prg.len().next_multiple_of(BANK_SIZE), 0x8000), 0);
+
+    let (period, region) = match header.timing {
+      Timing::PAL => (nsf.pal_speed, 1),
+      _ => (nsf.ntsc_speed, 0),
+    };
+
+    let mut mapper = NSF {
+      prg_rom: Banks::new(0x8000, 0xFFFF, BANK_SIZE, prg, false),
+      ram: vec![0; if fds { 0xA000 } else { header.prg_ram() }],
+      exram: [0x0; 0x400],
+      multiplier: [0x0; 0x02],
+      driver: NSF::driver(nsf.init, nsf.play),
+      banks: nsf.banks,
+      load: nsf.load,
+      bankswitched,
+      fds,
+      track: nsf.start,
+      region,
+      period: period as u64 * CLOCK_RATE,
+      counter: 0,
+      pending: false,
+      chips: nsf.chips,
+      vrc6: None,
+      vrc7: None,
+      fds_audio: None,
+      n163: None,
+      n163_ram: [0x0; 0x80],
+      n163_address: 0x0,
+      sunsoft: None,
+    };
+
+    mapper.reset();
+    mapper
+  }
+
+  fn driver(init: u16, play: u16) -> Vec<u8> {
+    let [init_lo, init_hi] = init.to_le_bytes();
+    let [play_lo, play_hi] = play.to_le_bytes();
+
+    vec![
+      0x78,                   // SEI
+      0xD8,                   // CLD
+      0xA2, 0xFF,             // LDX #$FF
+      0x9A,                   // TXS
+      0xA9, 0x00,             // LDA #$00
+      0x8D, 0x15, 0x40,       // STA $4015
+      0xA2, 0x13,             // LDX #$13
+      0x9D, 0x00, 0x40,       // STA $4000,X
+      0xCA,                   // DEX
+      0x10, 0xFA,             // BPL -6
+      0xA9, 0x0F,             // LDA #$0F
+      0x8D, 0x15, 0x40,       // STA $4015
+      0xA9, 0x40,             // LDA #$40
+      0x8D, 0x17, 0x40,       // STA $4017
+      0xA9, 0x00,             // LDA #$00
+      0xAA,                   // TAX
+      0x95, 0x00,             // STA $00,X
+      0x9D, 0x00, 0x01,       // STA $0100,X
+      0x9D, 0x00, 0x02,       // STA $0200,X
+      0x9D, 0x00, 0x03,       // STA $0300,X
+      0x9D, 0x00, 0x04,       // STA $0400,X
+      0x9D, 0x00, 0x05,       // STA $0500,X
+      0x9D, 0x00, 0x06,       // STA $0600,X
+      0x9D, 0x00, 0x07,       // STA $0700,X
+      0xE8,                   // INX
+      0xD0, 0xE6,             // BNE -26
+      0xAD, 0xF1, 0x41,       // LDA TRACK
+      0xAE, 0xF2, 0x41,       // LDX REGION
+      0x20, init_lo, init_hi, // JSR init
+      0xAD, 0xF0, 0x41,       // LDA PLAY
+      0xF0, 0xFB,             // BEQ -5
+      0x20, play_lo, play_hi, // JSR play
+      0x4C, 0x42, 0x41,       // JMP $4142
+      0x40,                   // RTI
+    ]
+  }
+
+  // Puts the banks, RAM and expansion audio back to how a tune expects them
+  // before its init routine runs.
+  fn reset(&mut self) {
+    self.ram.fill(0);
+    self.exram.fill(0);
+    self.counter = 0;
+    self.pending = false;
+
+    if self.bankswitched {
+      if self.fds {
+        self.switch(0, self.banks[6]);
+        self.switch(1, self.banks[7]);
+      }
+
+      for (slot, bank) in self.banks.into_iter().enumerate() {
+        self.switch(slot + 2, bank);
+      }
+    } else if self.fds {
+      // Unbanked FDS tunes are copied into RAM wholesale
+      let start = (self.load as usize).saturating_sub(0x6000);
+      let prg = self.prg_rom.memory();
+      let offset = (self.load as usize).saturating_sub(0x8000);
+      let len = std::cmp::min(self.ram.len() - start, prg.len() - offset);
+      self.ram[start .. start + len].copy_from_slice(&prg[offset .. offset + len]);
+    }
+
+    self.vrc6 = (self.chips & NsfHeader::VRC6 != 0).then(VRC6Audio::new);
+    self.vrc7 = (self.chips & NsfHeader::VRC7 != 0).then(VRC7Audio::new);
+    self.fds_audio = (self.chips & NsfHeader::FDS != 0).then(|| {
+      let mut audio = FDSAudio::new();
+      audio.enable(true);
+      audio
+    });
+    self.n163 = (self.chips & NsfHeader::N163 != 0).then(Namco163Audio::new);
+    self.n163_ram.fill(0);
+    self.n163_address = 0;
+    self.sunsoft = (self.chips & NsfHeader::SUNSOFT5B != 0).then(Sunsoft5B::new);
+  }
+
+  // Slots 0 and 1 are $6000 and $7000, which only FDS tunes can bank. FDS
+  // tunes run from RAM, so their banks are copied in rather than mapped.
+  fn switch(&mut self, slot: usize, bank: u8) {
+    match slot {
+      0 | 1 if !self.fds => { },
+      _ if self.fds => {
+        let prg = self.prg_rom.memory();
+        let start = (bank as usize * BANK_SIZE) % prg.len();
+        let dest = slot * BANK_SIZE;
+        self.ram[dest .. dest + BANK_SIZE].copy_from_slice(&prg[start .. start + BANK_SIZE]);
+      }
+      _ => self.prg_rom.set(slot - 2, bank as usize),
+    }
+  }
+
+  fn n163_index(&mut self) -> usize {
+    let index = self.n163_address as usize & 0x7F;
+
+    if self.n163_address & 0x80 == 0x80 {
+      self.n163_address = (self.n163_address & 0x80) | (((self.n163_address & 0x7F) + 1) & 0x7F);
+    }
+
+    index
+  }
+
+  fn write_audio(&mut self, addr: u16, val: u8) {
+    match addr {
+      0x9000 ..= 0x9003 | 0xA000 ..= 0xA002 | 0xB000 ..= 0xB002 => {
+        if let Some(audio) = &mut self.vrc6 {
+          audio.write(addr, val);
+        }
+      }
+      0x9010 | 0x9030 => {
+        if let Some(audio) = &mut self.vrc7 {
+          if addr == 0x9010 { audio.write_address(val) } else { audio.write_data(val) }
+        }
+      }
+      0xC000 ..= 0xFFFF if self.sunsoft.is_some() => {
+        if let Some(audio) = &mut self.sunsoft {
+          if addr < 0xE000 { audio.write_select(val) } else { audio.write_data(val) }
+        }
+      }
+      0xF800 ..= 0xFFFF => self.n163_address = val,
+      _ => { },
+    }
+  }
+}
+
+impl Snapshot for NSF {
+  fn save(&self, state: &mut Writer) {
+    self.prg_rom.save(state);
+    state.bytes(&self.ram);
+    state.bytes(&self.exram);
+    state.bytes(&self.multiplier);
+
+    state.u8(self.track);
+    state.u64(self.counter);
+    state.bool(self.pending);
+
+    if let Some(audio) = &self.vrc6 {
+      audio.save(state);
+    }
+    if let Some(audio) = &self.vrc7 {
+      audio.save(state);
+    }
+    if let Some(audio) = &self.fds_audio {
+      audio.save(state);
+    }
+    if let Some(audio) = &self.n163 {
+      audio.save(state);
+    }
+    state.bytes(&self.n163_ram);
+    state.u8(self.n163_address);
+    if let Some(audio) = &self.sunsoft {
+      audio.save(state);
+    }
+  }
+
+  fn load(&mut self, state: &mut Reader) -> Result<(), StateError> {
+    self.prg_rom.load(state)?;
+    state.bytes_into(&mut self.ram, "NSF RAM size")?;
+    state.bytes_into(&mut self.exram, "NSF ExRAM size")?;
+    state.bytes_into(&mut self.multiplier, "NSF multiplier")?;
+
+    self.track = state.u8()?;
+    self.counter = state.u64()?;
+    self.pending = state.bool()?;
+
+    if let Some(audio) = &mut self.vrc6 {
+      audio.load(state)?;
+    }
+    if let Some(audio) = &mut self.vrc7 {
+      audio.load(state)?;
+    }
+    if let Some(audio) = &mut self.fds_audio {
+      audio.load(state)?;
+    }
+    if let Some(audio) = &mut self.n163 {
+      audio.load(state)?;
+    }
+    state.bytes_into(&mut self.n163_ram, "Namco 163 sound RAM")?;
+    self.n163_address = state.u8()?;
+    if let Some(audio) = &mut self.sunsoft {
+      audio.load(state)?;
+    }
+    Ok(())
+  }
+}
@@ -0,0 +1,125 @@
+use crate::savestate::{Reader, Snapshot, StateError, Writer};
+
+// Only the three square channels are emulated; the noise and envelope
+// generators were never used by a released game.
+pub struct Sunsoft5B {
+  select: u8,
+  disabled: u8,
+  muted: u8,
+  channels: [Square; 0x03],
+}
+
+struct Square {
+  period: u16,
+  volume: u8,
+  counter: u16,
+  high: bool,
+}
+
+impl Square {
+  fn new() -> Self {
+    Square {
+      period: 0,
+      volume: 0,
+      counter: 0,
+      high: false,
+    }
+  }
+
+  fn tick(&mut self) {
+    self.counter += 1;
+
+    if self.counter >= 16 * std::cmp::max(1, self.period) {
+      self.counter = 0;
+      self.high = !self.high;
+    }
+  }
+}
+
+impl Sunsoft5B {
+  const CHANNEL_VOLUME: f32 = 0.15;
+
+  pub fn new() -> Self {
+    Sunsoft5B {
+      select: 0x0,
+      disabled: 0x0,
+      muted: 0x0,
+      channels: [Square::new(), Square::new(), Square::new()],
+    }
+  }
+
+  pub fn write_select(&mut self, val: u8) {
+    self.select = val & 0x0F;
+  }
+
+  pub fn write_data(&mut self, val: u8) {
+    match self.select {
+      0x0 | 0x2 | 0x4 => {
+        let channel = &mut self.channels[(self.select / 2) as usize];
+        channel.period = (channel.period & 0xF00) | val as u16;
+      }
+      0x1 | 0x3 | 0x5 => {
+        let channel = &mut self.channels[(self.select / 2) as usize];
+        channel.period = (channel.period & 0x0FF) | ((val as u16 & 0x0F) << 8);
+      }
+      0x7 => self.disabled = val & 0x07,
+      0x8 ..= 0xA => self.channels[(self.select - 0x8) as usize].volume = val & 0x0F,
+      _ => { },
+    }
+  }
+
+  pub fn mute(&mut self, channel: u8, muted: bool) {
+    if channel as usize >= self.channels.len() {
+      return;
+    }
+
+    match muted {
+      true => self.muted |= 1 << channel,
+      false => self.muted &= !(1 << channel),
+    }
+  }
+
+  pub fn tick(&mut self) {
+    self.channels.iter_mut().for_each(Square::tick);
+  }
+
+  pub fn signal(&self) -> f32 {
+    self.channels.iter().enumerate().map(|(i, channel)| {
+      let disabled = self.disabled & (1 << i) != 0;
+
+      if channel.volume == 0 || !(channel.high || disabled) || self.muted & (1 << i) != 0 {
+        return 0.0;
+      }
+
+      // Each volume step is 3dB
+      Sunsoft5B::CHANNEL_VOLUME * 10f32.powf((channel.volume as f32 - 15.0) * 3.0 / 20.0)
+    }).sum()
+  }
+}
+
+impl Snapshot for Sunsoft5B {
+  fn save(&self, state: &mut Writer) {
+    state.u8(self.select);
+    state.u8(self.disabled);
+
+    for channel in &self.channels {
+      state.u16(channel.period);
+      state.u8(channel.volume);
+      state.u16(channel.counter);
+      state.bool(channel.high);
+    }
+  }
+
+  fn load(&mut self, state: &mut Reader) -> Result<(), StateError> {
+    self.select = state.u8()?;
+    self.disabled = state.u8()?;
+
+    for channel in self.channels.iter_mut() {
+      channel.period = state.u16()?;
+      channel.volume = state.u8()?;
+      channel.counter = state.u16()?;
+      channel.high = state.bool()?;
+    }
+    Ok(())
+  }
+}
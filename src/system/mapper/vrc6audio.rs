@@ -0,0 +1,238 @@
+use crate::savestate::{Reader, Snapshot, StateError, Writer};
+
+pub struct VRC6Audio {
+  halted: bool,
+  shift: u8,
+  muted: [bool; 0x03],
+  pulses: [Pulse; 0x02],
+  saw: Saw,
+}
+
+struct Pulse {
+  enabled: bool,
+  constant: bool,
+  duty: u8,
+  volume: u8,
+  period: u16,
+  counter: u16,
+  step: u8,
+}
+
+struct Saw {
+  enabled: bool,
+  rate: u8,
+  period: u16,
+  counter: u16,
+  step: u8,
+  accumulator: u8,
+}
+
+impl Pulse {
+  fn new() -> Self {
+    Pulse {
+      enabled: false,
+      constant: false,
+      duty: 0x0,
+      volume: 0x0,
+      period: 0x0,
+      counter: 0x0,
+      step: 0x0,
+    }
+  }
+
+  fn write(&mut self, reg: u16, val: u8) {
+    match reg {
+      0 => {
+        self.constant = val & 0x80 == 0x80;
+        self.duty = (val >> 4) & 0x07;
+        self.volume = val & 0x0F;
+      }
+      1 => self.period = (self.period & 0xF00) | val as u16,
+      _ => {
+        self.period = (self.period & 0x0FF) | ((val as u16 & 0x0F) << 8);
+        self.enabled = val & 0x80 == 0x80;
+
+        if !self.enabled {
+          self.step = 0x0F;
+        }
+      }
+    }
+  }
+
+  fn tick(&mut self, shift: u8) {
+    if !self.enabled {
+      return;
+    }
+
+    if self.counter == 0 {
+      self.counter = self.period >> shift;
+      self.step = self.step.wrapping_sub(1) & 0x0F;
+    } else {
+      self.counter -= 1;
+    }
+  }
+
+  fn signal(&self) -> u8 {
+    if self.enabled && (self.constant || self.step <= self.duty) { self.volume } else { 0 }
+  }
+}
+
+impl Saw {
+  fn new() -> Self {
+    Saw {
+      enabled: false,
+      rate: 0x0,
+      period: 0x0,
+      counter: 0x0,
+      step: 0x0,
+      accumulator: 0x0,
+    }
+  }
+
+  fn write(&mut self, reg: u16, val: u8) {
+    match reg {
+      0 => self.rate = val & 0x3F,
+      1 => self.period = (self.period & 0xF00) | val as u16,
+      _ => {
+        self.period = (self.period & 0x0FF) | ((val as u16 & 0x0F) << 8);
+        self.enabled = val & 0x80 == 0x80;
+
+        if !self.enabled {
+          self.step = 0;
+          self.accumulator = 0;
+        }
+      }
+    }
+  }
+
+  fn tick(&mut self, shift: u8) {
+    if !self.enabled {
+      return;
+    }
+
+    if self.counter > 0 {
+      self.counter -= 1;
+      return;
+    }
+
+    self.counter = self.period >> shift;
+    self.step += 1;
+
+    // The accumulator grows on every other clock, and the seventh of those
+    // clocks resets it instead, leaving six additions per cycle
+    if self.step == 14 {
+      self.step = 0;
+      self.accumulator = 0;
+    } else if self.step.is_multiple_of(2) {
+      self.accumulator = self.accumulator.wrapping_add(self.rate);
+    }
+  }
+
+  fn signal(&self) -> u8 {
+    if self.enabled { self.accumulator >> 3 } else { 0 }
+  }
+}
+
+impl VRC6Audio {
+  // Scaled so a full volume VRC6 pulse matches a full volume 2A03 pulse
+  const LEVEL: f32 = 0.00996;
+
+  pub fn new() -> Self {
+    VRC6Audio {
+      halted: false,
+      shift: 0,
+      muted: [false; 0x03],
+      pulses: [Pulse::new(), Pulse::new()],
+      saw: Saw::new(),
+    }
+  }
+
+  pub fn write(&mut self, addr: u16, val: u8) {
+    let reg = addr & 0x03;
+
+    match (addr & 0xF000, reg) {
+      (0x9000, 3) => {
+        self.halted = val & 0x01 == 0x01;
+        self.shift = match val & 0x06 {
+          0x0 => 0,
+          0x2 => 4,
+          _ => 8,
+        };
+      }
+      (0x9000, _) => self.pulses[0].write(reg, val),
+      (0xA000, _) => self.pulses[1].write(reg, val),
+      (0xB000, _) => self.saw.write(reg, val),
+      _ => { },
+    }
+  }
+
+  pub fn mute(&mut self, channel: u8, muted: bool) {
+    if let Some(mute) = self.muted.get_mut(channel as usize) {
+      *mute = muted;
+    }
+  }
+
+  pub fn tick(&mut self) {
+    if self.halted {
+      return;
+    }
+
+    self.pulses.iter_mut().for_each(|pulse| pulse.tick(self.shift));
+    self.saw.tick(self.shift);
+  }
+
+  pub fn signal(&self) -> f32 {
+    let output = [self.pulses[0].signal(), self.pulses[1].signal(), self.saw.signal()];
+
+    output.iter().zip(self.muted).map(|(signal, muted)| {
+      if muted { 0.0 } else { *signal as f32 * VRC6Audio::LEVEL }
+    }).sum()
+  }
+}
+
+impl Snapshot for VRC6Audio {
+  fn save(&self, state: &mut Writer) {
+    state.bool(self.halted);
+    state.u8(self.shift);
+
+    for pulse in &self.pulses {
+      state.bool(pulse.enabled);
+      state.bool(pulse.constant);
+      state.u8(pulse.duty);
+      state.u8(pulse.volume);
+      state.u16(pulse.period);
+      state.u16(pulse.counter);
+      state.u8(pulse.step);
+    }
+
+    state.bool(self.saw.enabled);
+    state.u8(self.saw.rate);
+    state.u16(self.saw.period);
+    state.u16(self.saw.counter);
+    state.u8(self.saw.step);
+    state.u8(self.saw.accumulator);
+  }
+
+  fn load(&mut self, state: &mut Reader) -> Result<(), StateError> {
+    self.halted = state.bool()?;
+    self.shift = state.u8()?;
+
+    for pulse in self.pulses.iter_mut() {
+      pulse.enabled = state.bool()?;
+      pulse.constant = state.bool()?;
+      pulse.duty = state.u8()?;
+      pulse.volume = state.u8()?;
+      pulse.period = state.u16()?;
+      pulse.counter = state.u16()?;
+      pulse.step = state.u8()?;
+    }
+
+    self.saw.enabled = state.bool()?;
+    self.saw.rate = state.u8()?;
+    self.saw.period = state.u16()?;
+    self.saw.counter = state.u16()?;
+    self.saw.step = state.u8()?;
+    self.saw.accumulator = state.u8()?;
+    Ok(())
+  }
+}
@@ -0,0 +1,111 @@
+use crate::savestate::{Reader, Snapshot, StateError, Writer};
+
+// The IRQ counter shared by Konami's VRC4, VRC6 and VRC7 boards
+pub struct VRCIRQ {
+  latch: u8,
+  counter: u8,
+  prescaler: i16,
+  enabled: bool,
+  enable_after_ack: bool,
+  cycle_mode: bool,
+  pending: bool,
+}
+
+impl VRCIRQ {
+  const PRESCALER: i16 = 341;
+
+  pub fn new() -> Self {
+    VRCIRQ {
+      latch: 0x0,
+      counter: 0x0,
+      prescaler: VRCIRQ::PRESCALER,
+      enabled: false,
+      enable_after_ack: false,
+      cycle_mode: false,
+      pending: false,
+    }
+  }
+
+  pub fn write_latch(&mut self, val: u8) {
+    self.latch = val;
+  }
+
+  pub fn write_latch_lo(&mut self, val: u8) {
+    self.latch = (self.latch & 0xF0) | (val & 0x0F);
+  }
+
+  pub fn write_latch_hi(&mut self, val: u8) {
+    self.latch = (self.latch & 0x0F) | (val << 4);
+  }
+
+  pub fn write_control(&mut self, val: u8) {
+    self.enable_after_ack = val & 0x01 == 0x01;
+    self.enabled = val & 0x02 == 0x02;
+    self.cycle_mode = val & 0x04 == 0x04;
+    self.pending = false;
+
+    if self.enabled {
+      self.counter = self.latch;
+      self.prescaler = VRCIRQ::PRESCALER;
+    }
+  }
+
+  pub fn acknowledge(&mut self) {
+    self.pending = false;
+    self.enabled = self.enable_after_ack;
+  }
+
+  pub fn tick(&mut self) {
+    if !self.enabled {
+      return;
+    }
+
+    if self.cycle_mode {
+      self.clock();
+    } else {
+      // Scanline mode divides the CPU clock by 113.667
+      self.prescaler -= 3;
+
+      if self.prescaler <= 0 {
+        self.prescaler += VRCIRQ::PRESCALER;
+        self.clock();
+      }
+    }
+  }
+
+  fn clock(&mut self) {
+    if self.counter == 0xFF {
+      self.counter = self.latch;
+      self.pending = true;
+    } else {
+      self.counter += 1;
+    }
+  }
+
+  pub fn poll(&self) -> bool {
+    self.pending
+  }
+}
+
+impl Snapshot for VRCIRQ {
+  fn save(&self, state: &mut Writer) {
+    state.u8(self.latch);
+    state.u8(self.counter);
+    state.u16(self.prescaler as u16);
+    state.bool(self.enabled);
+    state.bool(self.enable_after_ack);
+    state.bool(self.cycle_mode);
+    state.bool(self.pending);
+  }
+
+  fn load(&mut self, state: &mut Reader) -> Result<(), StateError> {
+    self.latch = state.u8()?;
+    self.counter = state.u8()?;
+    self.prescaler = state.u16()? as i16;
+    self.enabled = state.bool()?;
+    self.enable_after_ack = state.bool()?;
+    self.cycle_mode = state.bool()?;
+    self.pending = state.bool()?;
+    Ok(())
+  }
+}
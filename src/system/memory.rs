@@ -1,3 +1,5 @@
+use crate::savestate::{Reader, Snapshot, StateError, Writer};
+
 pub struct Memory {
   vram: [u8; 0x800],
 }
@@ -9,7 +11,7 @@ impl Memory {
     }
   }
 
-  pub fn read(&mut self, addr: u16) -> u8 {
+  pub fn read(&self, addr: u16) -> u8 {
     self.vram[(addr & 0x7FF) as usize]
   }
 
@@ -17,3 +19,13 @@ impl Memory {
     self.vram[(addr & 0x7FF) as usize] = data
   }
 }
+
+impl Snapshot for Memory {
+  fn save(&self, state: &mut Writer) {
+    state.bytes(&self.vram);
+  }
+
+  fn load(&mut self, state: &mut Reader) -> Result<(), StateError> {
+    state.bytes_into(&mut self.vram, "RAM size")
+  }
+}
@@ -0,0 +1,114 @@
+use std::{fs, path::Path};
+
+use neones::{neones::NeoNES, ppu::frame::Frame};
+
+// blargg's test ROMs are not redistributed with the emulator. Copy them into
+// tests/roms, keeping the layout of the original archives, and run these with
+// `cargo test -- --ignored`.
+const ROMS: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/roms");
+
+// About a minute of emulated time, well past the slowest of these
+const FRAME_LIMIT: usize = 60 * 60;
+
+const SIGNATURE: [u8; 3] = [0xDE, 0xB0, 0x61];
+const RUNNING: u8 = 0x80;
+const NEEDS_RESET: u8 = 0x81;
+
+// The ROMs report through $6000: once $6001-$6003 hold the signature, $6000
+// reads $80 while the test runs and then its result code, with 0 meaning it
+// passed. A message for the result is left at $6004 as a C string.
+fn run(rom: &str) {
+  let path = Path::new(ROMS).join(rom);
+  let file = fs::read(&path).unwrap_or_else(|e| panic!("{}: {e}", path.display()));
+  let mut nes = Box::new(NeoNES::headless(file).unwrap());
+
+  for _ in 0 .. FRAME_LIMIT {
+    nes.step_frame().unwrap();
+
+    if (0 .. 3).map(|i| nes.peek(0x6001 + i)).ne(SIGNATURE) {
+      continue;
+    }
+
+    match nes.peek(0x6000) {
+      RUNNING => { },
+      NEEDS_RESET => panic!("{rom} asked for a reset"),
+      status => {
+        let text = (0x6004 ..= 0x7FFF)
+          .map(|addr| nes.peek(addr))
+          .take_while(|c| *c != 0)
+          .map(char::from)
+          .collect::<String>();
+
+        assert_eq!(status, 0, "{rom} failed with {status}:\n{}", text.trim_end());
+        return;
+      }
+    }
+  }
+
+  panic!("{rom} did not finish within {FRAME_LIMIT} frames");
+}
+
+// Demos that only show their result on screen are checked by hashing a frame
+// once they have settled. The hash is kept beside the ROM, recorded after
+// comparing the picture with the one the readme describes, and until then the
+// frame is written out as a PPM to look at.
+fn run_demo(rom: &str, frames: usize) {
+  let path = Path::new(ROMS).join(rom);
+  let file = fs::read(&path).unwrap_or_else(|e| panic!("{}: {e}", path.display()));
+  let mut nes = Box::new(NeoNES::headless(file).unwrap());
+
+  for _ in 0 .. frames {
+    nes.step_frame().unwrap();
+  }
+
+  // FNV-1a
+  let hash = nes.frame().data.iter().fold(0xCBF29CE484222325u64, |hash, byte| {
+    (hash ^ *byte as u64).wrapping_mul(0x100000001B3)
+  });
+
+  match fs::read_to_string(path.with_extension("hash")) {
+    Ok(expected) => assert_eq!(format!("{hash:016X}"), expected.trim(), "{rom} drew a different frame"),
+    Err(_) => {
+      let header = format!("P6 {} {} 255\n", Frame::WIDTH, Frame::HEIGHT);
+      let image = [header.as_bytes(), &nes.frame().data].concat();
+      fs::write(path.with_extension("ppm"), image).unwrap();
+      panic!("{rom} has no hash to check against; its frame hashed to {hash:016X}");
+    }
+  }
+}
+
+#[test]
+#[ignore]
+fn cli_latency() {
+  run("cpu_interrupts_v2/rom_singles/1-cli_latency.nes");
+}
+
+#[test]
+#[ignore]
+fn nmi_and_brk() {
+  run("cpu_interrupts_v2/rom_singles/2-nmi_and_brk.nes");
+}
+
+#[test]
+#[ignore]
+fn nmi_and_irq() {
+  run("cpu_interrupts_v2/rom_singles/3-nmi_and_irq.nes");
+}
+
+#[test]
+#[ignore]
+fn irq_and_dma() {
+  run("cpu_interrupts_v2/rom_singles/4-irq_and_dma.nes");
+}
+
+#[test]
+#[ignore]
+fn branch_delays_irq() {
+  run("cpu_interrupts_v2/rom_singles/5-branch_delays_irq.nes");
+}
+
+#[test]
+#[ignore]
+fn nmi_sync() {
+  run_demo("nmi_sync/demo_ntsc.nes", 300);
+}
@@ -5,6 +5,7 @@ use std::{cell::RefCell, rc::Rc};
 use wasm_bindgen::prelude::*;
 use neones::{
   apu::mixer::NESAudioCallback,
+  error::NeoNESError,
   neones::NeoNES as InnerNES,
   ppu::frame::Frame,
   renderer::Renderer,
@@ -44,26 +45,26 @@ pub struct NeoNES {
 #[wasm_bindgen]
 impl NeoNES {
   #[wasm_bindgen(constructor)]
-  pub fn new(rom: Vec<u8>) -> Self {
+  pub fn new(rom: Vec<u8>) -> Result<NeoNES, JsValue> {
     utils::set_panic_hook();
     let frame = Rc::from(RefCell::from(vec![255; Frame::WIDTH * Frame::HEIGHT * 4]));
     let renderer = WebRenderer::new(frame.clone());
-    let mut emulator = InnerNES::new(rom, Rc::from(RefCell::from(renderer)));
+    let mut emulator = InnerNES::new(rom, Rc::from(RefCell::from(renderer))).map_err(to_js)?;
     let audio = emulator.audio();
 
-    NeoNES {
+    Ok(NeoNES {
       emulator,
       frame,
       audio
-    }
+    })
   }
 
   pub fn frame(&self) -> *const u8 {
     self.frame.borrow().as_ptr()
   }
 
-  pub fn step(&mut self) {
-    self.emulator.step_frame();
+  pub fn step(&mut self) -> Result<(), JsValue> {
+    self.emulator.step_frame().map_err(to_js)
   }
 
   pub fn push(&mut self, key: &str) {
@@ -101,6 +102,26 @@ impl NeoNES {
   pub fn signal(&mut self, out: &mut [f32]) {
     self.audio.signal(out);
   }
+
+  pub fn battery_ram(&self) -> Option<Vec<u8>> {
+    self.emulator.battery_ram().map(|ram| ram.to_vec())
+  }
+
+  pub fn load_battery_ram(&mut self, data: &[u8]) {
+    self.emulator.load_battery_ram(data);
+  }
+
+  pub fn save_state(&self) -> Vec<u8> {
+    self.emulator.save_state()
+  }
+
+  pub fn load_state(&mut self, state: &[u8]) -> Result<(), JsValue> {
+    self.emulator.load_state(state).map_err(to_js)
+  }
+}
+
+fn to_js(e: NeoNESError) -> JsValue {
+  JsValue::from_str(&e.to_string())
 }
 
 #[wasm_bindgen]